@@ -49,18 +49,10 @@ fn get_pixels_for_y(schem: &Litematic, y: i32) -> Vec<Vec<MapColor>> {
     for z in enclosing.z_range() {
         let mut row = vec![];
         for x in enclosing.x_range() {
-            // find all blocks in all regions at this position
-            let blocks = schem
-                .regions
-                .iter()
-                .filter_map(|region| region.get_block_global_opt(BlockPos::new(x, y, z)))
-                .collect::<Vec<_>>();
-            // log it, if multiple regions overlap here
-            if blocks.len() > 1 {
-                println!("multiple blocks at ({x}, {y}, {z}) : {blocks:?}");
-            }
+            // where regions overlap, the block of the last region is used
+            let block = schem.get_block(BlockPos::new(x, y, z));
             // save the map color
-            row.push(blocks.first().map_or(MapColor::None, |b| b.map_color()));
+            row.push(block.map_or(MapColor::None, |b| b.map_color()));
         }
         pixels.push(row);
     }
@@ -1,80 +1,76 @@
-#[cfg(feature = "_mcdata-all")]
-use rustmatica::{Litematic, LitematicMetadata};
+#[cfg(feature = "mc-all")]
+use rustmatica::AnyLitematic;
 
-#[cfg(not(feature = "_mcdata-all"))]
+#[cfg(not(feature = "mc-all"))]
 fn main() {
-    panic!("this test requires the `_mcdata-all` feature to be enabled");
+    panic!("this test requires the `mc-all` feature to be enabled");
 }
 
-#[cfg(feature = "_mcdata-all")]
-macro_rules! try_for {
-    ($version:ident, $path:expr, $meta:expr) => {{
-        let schem: rustmatica::Result<
-            Litematic<
-                mcdata::$version::BlockState,
-                mcdata::$version::Entity,
-                mcdata::$version::BlockEntity,
-            >,
-        > = Litematic::read_file_with_metadata($path, $meta);
-
-        match schem {
-            Ok(schem) => {
-                for region in schem.regions {
-                    for block in region.block_palette() {
-                        if let mcdata::$version::BlockState::Other(generic) = block {
-                            println!("\x1b[1;33mcontains generic block state:\x1b[22m {generic:?}\x1b[0m");
-                        }
-                    }
-                    for entity in region.entities {
-                        if let mcdata::$version::Entity::Other(generic) = entity {
-                            println!("\x1b[1;33mcontains generic entity:\x1b[22m {generic:?}\x1b[0m");
-                        }
-                    }
-                    for entity in region.block_entities {
-                        if let mcdata::$version::BlockEntity::Other(generic) = entity {
-                            println!("\x1b[1;33mcontains generic block entity:\x1b[22m {generic:?}\x1b[0m");
-                        }
-                    }
+#[cfg(feature = "mc-all")]
+macro_rules! print_generic {
+    ($version:ident, $schem:expr) => {{
+        for region in $schem.regions {
+            for block in region.block_palette() {
+                if let mcdata::$version::BlockState::Other(generic) = block {
+                    println!("\x1b[1;33mcontains generic block state:\x1b[22m {generic:?}\x1b[0m");
+                }
+            }
+            for entity in region.entities {
+                if let mcdata::$version::Entity::Other(generic) = entity {
+                    println!("\x1b[1;33mcontains generic entity:\x1b[22m {generic:?}\x1b[0m");
+                }
+            }
+            for entity in region.block_entities {
+                if let mcdata::$version::BlockEntity::Other(generic) = entity {
+                    println!("\x1b[1;33mcontains generic block entity:\x1b[22m {generic:?}\x1b[0m");
                 }
             }
-            Err(e) => eprintln!(
-                "\x1b[1;31mcould not read '{}' as litematic: {e}\x1b[0m",
-                $path.display()
-            ),
         }
     }};
 }
 
-#[cfg(feature = "_mcdata-all")]
+#[cfg(feature = "mc-all")]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let path = std::env::args().nth(1);
     let path = path.as_deref().unwrap_or("test_files/tmc_catalogue");
     for file in std::fs::read_dir(path)? {
         let file = file?;
         let path = file.path();
-        let meta = LitematicMetadata::read_file(&path)?;
+        let schem = match AnyLitematic::read_file(&path) {
+            Ok(schem) => schem,
+            Err(e) => {
+                eprintln!(
+                    "\x1b[1;31mcould not read '{}' as litematic: {e}\x1b[0m",
+                    path.display()
+                );
+                continue;
+            }
+        };
         println!(
             "\n\x1b[1m{}\x1b[0m with MC data version {}",
             path.display(),
-            meta.minecraft_data_version
+            schem.metadata().minecraft_data_version
         );
-        match meta.minecraft_data_version {
-            mcdata::data_version::MC_1_20_5..=i32::MAX => try_for!(mc1_20_5, &path, meta),
-            mcdata::data_version::MC_1_20_3..=i32::MAX => try_for!(mc1_20_3, &path, meta),
-            mcdata::data_version::MC_1_20_2..=i32::MAX => try_for!(mc1_20_2, &path, meta),
-            mcdata::data_version::MC_1_20..=i32::MAX => try_for!(mc1_20, &path, meta),
-            mcdata::data_version::MC_1_19_4..=i32::MAX => try_for!(mc1_19_4, &path, meta),
-            mcdata::data_version::MC_1_19_3..=i32::MAX => try_for!(mc1_19_3, &path, meta),
-            mcdata::data_version::MC_1_19_1..=i32::MAX => try_for!(mc1_19_1, &path, meta),
-            mcdata::data_version::MC_1_19..=i32::MAX => try_for!(mc1_19, &path, meta),
-            mcdata::data_version::MC_1_18..=i32::MAX => try_for!(mc1_18, &path, meta),
-            mcdata::data_version::MC_1_17..=i32::MAX => try_for!(mc1_17, &path, meta),
-            mcdata::data_version::MC_1_16_2..=i32::MAX => try_for!(mc1_16_2, &path, meta),
-            mcdata::data_version::MC_1_16..=i32::MAX => try_for!(mc1_16, &path, meta),
-            mcdata::data_version::MC_1_15_2..=i32::MAX => try_for!(mc1_15_2, &path, meta),
-            mcdata::data_version::MC_1_15..=i32::MAX => try_for!(mc1_15, &path, meta),
-            mcdata::data_version::MC_1_14..=i32::MAX => try_for!(mc1_14, &path, meta),
-            v => eprintln!("\x1b[1;31mMinecraft data version {v} not supported\x1b[0m"),
+        match schem {
+            AnyLitematic::Mc1_20_5(schem) => print_generic!(mc1_20_5, schem),
+            AnyLitematic::Mc1_20_3(schem) => print_generic!(mc1_20_3, schem),
+            AnyLitematic::Mc1_20_2(schem) => print_generic!(mc1_20_2, schem),
+            AnyLitematic::Mc1_20(schem) => print_generic!(mc1_20, schem),
+            AnyLitematic::Mc1_19_4(schem) => print_generic!(mc1_19_4, schem),
+            AnyLitematic::Mc1_19_3(schem) => print_generic!(mc1_19_3, schem),
+            AnyLitematic::Mc1_19_1(schem) => print_generic!(mc1_19_1, schem),
+            AnyLitematic::Mc1_19(schem) => print_generic!(mc1_19, schem),
+            AnyLitematic::Mc1_18(schem) => print_generic!(mc1_18, schem),
+            AnyLitematic::Mc1_17(schem) => print_generic!(mc1_17, schem),
+            AnyLitematic::Mc1_16_2(schem) => print_generic!(mc1_16_2, schem),
+            AnyLitematic::Mc1_16(schem) => print_generic!(mc1_16, schem),
+            AnyLitematic::Mc1_15_2(schem) => print_generic!(mc1_15_2, schem),
+            AnyLitematic::Mc1_15(schem) => print_generic!(mc1_15, schem),
+            AnyLitematic::Mc1_14(schem) => print_generic!(mc1_14, schem),
+            schem => eprintln!(
+                "\x1b[1;31mMinecraft data version {} not supported\x1b[0m",
+                schem.metadata().minecraft_data_version
+            ),
         }
     }
 
@@ -0,0 +1,176 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+#[allow(unused_imports)]
+use mcdata::data_version::*;
+
+#[allow(unused_imports)]
+use crate::upgrade::MC_1_21;
+use crate::{Litematic, LitematicMetadata, Result};
+
+macro_rules! any_litematic {
+    ($($feature:literal => $variant:ident($module:ident) for $versions:expr,)*) => {
+        /// A litematica schematic using the [`mcdata`] types matching its
+        /// [Minecraft data version](LitematicMetadata::minecraft_data_version).
+        ///
+        /// When loading a schematic, the metadata is read first, and the regions are then
+        /// deserialized with the types of the matching [`mcdata`] version module. This requires the
+        /// `mc-*` feature for that version to be enabled. Schematics with data versions for which
+        /// no feature is enabled, or which aren't covered by any [`mcdata`] version module, use the
+        /// [generic](Self::Generic) types.
+        ///
+        /// The available variants depend on the enabled features, so matching must always include
+        /// a wildcard arm.
+        #[derive(Debug)]
+        #[non_exhaustive]
+        pub enum AnyLitematic {
+            $(
+                #[doc = concat!("A schematic using the types of [`mcdata::", stringify!($module), "`].")]
+                #[cfg(feature = $feature)]
+                $variant(
+                    Litematic<
+                        mcdata::$module::BlockState,
+                        mcdata::$module::Entity,
+                        mcdata::$module::BlockEntity,
+                    >,
+                ),
+            )*
+            /// A schematic using [`mcdata`]s generic types.
+            Generic(Litematic),
+        }
+
+        impl AnyLitematic {
+            /// Load an [`AnyLitematic`] from uncompressed bytes and existing metadata, choosing the
+            /// types based on the Minecraft data version of the metadata.
+            fn from_uncompressed_bytes_with_metadata(
+                bytes: &[u8],
+                metadata: LitematicMetadata,
+            ) -> Result<Self> {
+                #[allow(unused_variables)]
+                let data_version = metadata.minecraft_data_version;
+                $(
+                    #[cfg(feature = $feature)]
+                    if ($versions).contains(&data_version) {
+                        return Litematic::from_uncompressed_bytes_with_metadata(bytes, metadata)
+                            .map(Self::$variant);
+                    }
+                )*
+                Litematic::from_uncompressed_bytes_with_metadata(bytes, metadata).map(Self::Generic)
+            }
+
+            /// The metadata of this schematic.
+            pub fn metadata(&self) -> &LitematicMetadata {
+                match self {
+                    $(
+                        #[cfg(feature = $feature)]
+                        Self::$variant(schem) => &schem.metadata,
+                    )*
+                    Self::Generic(schem) => &schem.metadata,
+                }
+            }
+
+            /// The metadata of this schematic, mutably.
+            pub fn metadata_mut(&mut self) -> &mut LitematicMetadata {
+                match self {
+                    $(
+                        #[cfg(feature = $feature)]
+                        Self::$variant(schem) => &mut schem.metadata,
+                    )*
+                    Self::Generic(schem) => &mut schem.metadata,
+                }
+            }
+
+            /// Write this schematic to uncompressed bytes.
+            pub fn to_uncompressed_bytes(&self) -> Result<Vec<u8>> {
+                match self {
+                    $(
+                        #[cfg(feature = $feature)]
+                        Self::$variant(schem) => schem.to_uncompressed_bytes(),
+                    )*
+                    Self::Generic(schem) => schem.to_uncompressed_bytes(),
+                }
+            }
+
+            /// Write this schematic to a writer as gzip compressed bytes.
+            pub fn write_to(&self, writer: impl Write) -> Result<()> {
+                match self {
+                    $(
+                        #[cfg(feature = $feature)]
+                        Self::$variant(schem) => schem.write_to(writer),
+                    )*
+                    Self::Generic(schem) => schem.write_to(writer),
+                }
+            }
+        }
+    };
+}
+
+any_litematic! {
+    "mc-1.14" => Mc1_14(mc1_14) for MC_1_14..MC_1_15,
+    "mc-1.15" => Mc1_15(mc1_15) for MC_1_15..MC_1_15_2,
+    "mc-1.15.2" => Mc1_15_2(mc1_15_2) for MC_1_15_2..MC_1_16,
+    "mc-1.16" => Mc1_16(mc1_16) for MC_1_16..MC_1_16_2,
+    "mc-1.16.2" => Mc1_16_2(mc1_16_2) for MC_1_16_2..MC_1_17,
+    "mc-1.17" => Mc1_17(mc1_17) for MC_1_17..MC_1_18,
+    "mc-1.18" => Mc1_18(mc1_18) for MC_1_18..MC_1_19,
+    "mc-1.19" => Mc1_19(mc1_19) for MC_1_19..MC_1_19_1,
+    "mc-1.19.1" => Mc1_19_1(mc1_19_1) for MC_1_19_1..MC_1_19_3,
+    "mc-1.19.3" => Mc1_19_3(mc1_19_3) for MC_1_19_3..MC_1_19_4,
+    "mc-1.19.4" => Mc1_19_4(mc1_19_4) for MC_1_19_4..MC_1_20,
+    "mc-1.20" => Mc1_20(mc1_20) for MC_1_20..MC_1_20_2,
+    "mc-1.20.2" => Mc1_20_2(mc1_20_2) for MC_1_20_2..MC_1_20_3,
+    "mc-1.20.3" => Mc1_20_3(mc1_20_3) for MC_1_20_3..MC_1_20_5,
+    "mc-1.20.5" => Mc1_20_5(mc1_20_5) for MC_1_20_5..MC_1_21,
+}
+
+impl AnyLitematic {
+    /// Load a schematic from uncompressed bytes.
+    pub fn from_uncompressed_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_uncompressed_bytes_with_metadata(
+            bytes,
+            LitematicMetadata::from_uncompressed_bytes(bytes)?,
+        )
+    }
+
+    /// Load a schematic from gzip compressed bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_reader(bytes)
+    }
+
+    /// Load a schematic from a reader of gzip compressed bytes.
+    ///
+    /// Unlike [`Litematic::from_reader`], the decompressed data is buffered, as the types of the
+    /// regions depend on the metadata, which is read in a first pass.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        let mut bytes = vec![];
+        GzDecoder::new(reader).read_to_end(&mut bytes)?;
+        Self::from_uncompressed_bytes(&bytes)
+    }
+
+    /// Load a schematic from a file.
+    pub fn read_file(filename: impl AsRef<Path>) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(filename)?))
+    }
+
+    /// Write this schematic to gzip compressed bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.write_to(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Write this schematic to a file.
+    pub fn write_file(&self, filename: impl AsRef<Path>) -> Result<()> {
+        self.write_to(BufWriter::new(File::create(filename)?))
+    }
+}
+
+impl From<Litematic> for AnyLitematic {
+    fn from(schem: Litematic) -> Self {
+        Self::Generic(schem)
+    }
+}
@@ -0,0 +1,152 @@
+/// A fixed length array of unsigned integers which are tightly packed into 64 bit words, just like
+/// litematica's `LitematicaBitArray`.
+///
+/// Every entry uses the same number of bits and entries may span two words. The words are stored
+/// in the same layout as the `BlockStates` long array of litematica regions, so no unpacking is
+/// required when reading or writing schematics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BitArray {
+    bits: usize,
+    len: usize,
+    data: Vec<u64>,
+}
+
+impl BitArray {
+    /// Create a new array of `len` zeroes using `bits` bits per entry.
+    pub(crate) fn new(bits: usize, len: usize) -> Self {
+        debug_assert!((1..=32).contains(&bits), "invalid number of bits: {bits}");
+        Self {
+            bits,
+            len,
+            data: vec![0; Self::words_for(bits, len)],
+        }
+    }
+
+    /// Create a new array from already packed words.
+    ///
+    /// Missing words are filled with zeroes and excess words are discarded.
+    pub(crate) fn from_raw(bits: usize, len: usize, data: impl IntoIterator<Item = i64>) -> Self {
+        let words = Self::words_for(bits, len);
+        let mut data = data
+            .into_iter()
+            .take(words)
+            .map(|word| word as u64)
+            .collect::<Vec<_>>();
+        data.resize(words, 0);
+        Self { bits, len, data }
+    }
+
+    /// Create a new array from unpacked values using `bits` bits per entry.
+    pub(crate) fn from_values(bits: usize, values: impl ExactSizeIterator<Item = usize>) -> Self {
+        let mut new = Self::new(bits, values.len());
+        for (index, value) in values.enumerate() {
+            new.set(index, value);
+        }
+        new
+    }
+
+    /// The number of words required to store `len` entries with `bits` bits each.
+    pub(crate) fn words_for(bits: usize, len: usize) -> usize {
+        (len * bits).div_ceil(64)
+    }
+
+    /// Get the packed words.
+    pub(crate) fn to_raw(&self) -> Vec<i64> {
+        self.data.iter().map(|word| *word as i64).collect()
+    }
+
+    /// The number of bits used per entry.
+    pub(crate) fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// The number of entries.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    /// Get the entry at `index`.
+    ///
+    /// Panics if the index is out of bounds.
+    pub(crate) fn get(&self, index: usize) -> usize {
+        assert!(
+            index < self.len,
+            "index {index} out of bounds for {}",
+            self.len
+        );
+        let start = index * self.bits;
+        let (word, offset) = (start / 64, start % 64);
+        let mut value = self.data[word] >> offset;
+        if offset + self.bits > 64 {
+            value |= self.data[word + 1] << (64 - offset);
+        }
+        (value & self.mask()) as usize
+    }
+
+    /// Set the entry at `index` to `value`.
+    ///
+    /// Panics if the index is out of bounds. Bits of `value` that don't fit into the entry are
+    /// discarded.
+    pub(crate) fn set(&mut self, index: usize, value: usize) {
+        assert!(
+            index < self.len,
+            "index {index} out of bounds for {}",
+            self.len
+        );
+        let mask = self.mask();
+        let value = value as u64 & mask;
+        let start = index * self.bits;
+        let (word, offset) = (start / 64, start % 64);
+        self.data[word] = self.data[word] & !(mask << offset) | value << offset;
+        if offset + self.bits > 64 {
+            let shift = 64 - offset;
+            self.data[word + 1] = self.data[word + 1] & !(mask >> shift) | value >> shift;
+        }
+    }
+
+    /// Repack this array to use `bits` bits per entry.
+    pub(crate) fn resize_bits(&mut self, bits: usize) {
+        if bits != self.bits {
+            *self = Self::from_values(bits, self.iter());
+        }
+    }
+
+    /// Create an iterator over all entries.
+    pub(crate) fn iter(&self) -> Iter<'_> {
+        Iter {
+            array: self,
+            index: 0,
+        }
+    }
+}
+
+/// An iterator over the entries of a [`BitArray`].
+#[derive(Debug, Clone)]
+pub(crate) struct Iter<'a> {
+    array: &'a BitArray,
+    index: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.array.len {
+            return None;
+        }
+        let value = self.array.get(self.index);
+        self.index += 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.array.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
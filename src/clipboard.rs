@@ -0,0 +1,518 @@
+use fastnbt::Value;
+use mcdata::util::{BlockPos, Cuboid};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{transform, util, Litematic, PendingBlockTick, PendingFluidTick, Region};
+
+/// Options for [`Region::paste`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PasteOptions {
+    /// Which blocks are pasted. Defaults to [`PasteMode::ReplaceAll`].
+    pub mode: PasteMode,
+
+    /// Whether entities are pasted. Defaults to `true`.
+    pub entities: bool,
+}
+
+impl Default for PasteOptions {
+    fn default() -> Self {
+        Self {
+            mode: PasteMode::default(),
+            entities: true,
+        }
+    }
+}
+
+/// Which blocks are pasted by [`Region::paste`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PasteMode {
+    /// Paste all blocks, including air.
+    #[default]
+    ReplaceAll,
+
+    /// Paste all blocks except air, keeping the existing blocks where the pasted region has air.
+    SkipAir,
+
+    /// Only paste blocks at positions where there currently is air.
+    ReplaceAir,
+}
+
+/// The part of a region that stays in place when using [`Region::resize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ResizeAnchor {
+    /// Keep the [position](Region::position) of the region.
+    #[default]
+    Position,
+
+    /// Keep the minimum corner of the region.
+    Min,
+
+    /// Keep the maximum corner of the region.
+    Max,
+}
+
+impl<BlockState, Entity, BlockEntity> Region<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Copy everything inside the given local area into a new region.
+    ///
+    /// The area is clipped to the bounds of this region. The new region has the same name and a
+    /// positive size, and is positioned such that all blocks keep their global coordinates.
+    /// Block entities, entities, and pending ticks inside the area are copied and moved to the
+    /// coordinates of the new region. If the area doesn't overlap this region, the new region has
+    /// a size of zero.
+    pub fn extract(&self, area: Cuboid) -> Self {
+        let size = self.size.abs();
+        let start = BlockPos::new(
+            area.origin.x.clamp(0, size.x),
+            area.origin.y.clamp(0, size.y),
+            area.origin.z.clamp(0, size.z),
+        );
+        let end = BlockPos::new(
+            (area.origin.x + area.size.x).clamp(start.x, size.x),
+            (area.origin.y + area.size.y).clamp(start.y, size.y),
+            (area.origin.z + area.size.z).clamp(start.z, size.z),
+        );
+        let mut new_size = end - start;
+        if new_size.x == 0 || new_size.y == 0 || new_size.z == 0 {
+            new_size = BlockPos::new(0, 0, 0);
+        }
+        let mut new = Region::new(self.name.clone(), min_corner(self) + start, new_size);
+        new.extra = self.extra.clone();
+
+        let mut ids = vec![None; self.block_palette().len()];
+        for y in 0..new.size.y {
+            for z in 0..new.size.z {
+                for x in 0..new.size.x {
+                    let pos = BlockPos::new(x, y, z);
+                    let id = self.get_block_id(pos + start);
+                    let new_id = *ids[id].get_or_insert_with(|| {
+                        new.add_to_palette(self.block_palette()[id].clone())
+                    });
+                    new.set_block_id(pos, new_id);
+                }
+            }
+        }
+
+        let in_area = |pos: BlockPos| {
+            (start.x..end.x).contains(&pos.x)
+                && (start.y..end.y).contains(&pos.y)
+                && (start.z..end.z).contains(&pos.z)
+        };
+        new.block_entities = self
+            .block_entities
+            .iter()
+            .filter(|e| in_area(e.position()))
+            .map(|e| move_block_entity(e, e.position() - start))
+            .collect();
+        let shift = entity_offset(self) - start - entity_offset(&new);
+        new.entities = self
+            .entities
+            .iter()
+            .filter(|e| entity_block_pos(self, *e).is_some_and(in_area))
+            .map(|e| move_entity(e, shift))
+            .collect();
+        new.pending_block_ticks = move_ticks(&self.pending_block_ticks, in_area, -start);
+        new.pending_fluid_ticks = move_ticks(&self.pending_fluid_ticks, in_area, -start);
+        new
+    }
+
+    /// Shrink this region to the smallest box containing all non-air blocks.
+    ///
+    /// The [position](Self::position) is moved so that all blocks keep their global coordinates,
+    /// and the [size](Self::size) becomes positive. Block entities, entities, and pending ticks are
+    /// moved to the new local coordinates, and the ones outside the new bounds are removed. A
+    /// region without any non-air blocks is shrunk to a size of zero.
+    ///
+    /// This also removes all unused entries from the palette.
+    pub fn trim(&mut self) {
+        let air = BlockState::air();
+        let is_air = self
+            .block_palette()
+            .iter()
+            .map(|state| state == &air)
+            .collect::<Vec<_>>();
+        let size = self.size.abs();
+        let mut bounds: Option<(BlockPos, BlockPos)> = None;
+        for (index, id) in self.block_ids().enumerate() {
+            if is_air[id] {
+                continue;
+            }
+            let index = index as i32;
+            let pos = BlockPos::new(
+                index % size.x,
+                index / size.x / size.z % size.y,
+                index / size.x % size.z,
+            );
+            bounds = Some(match bounds {
+                Some((min, max)) => (
+                    BlockPos::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z)),
+                    BlockPos::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z)),
+                ),
+                None => (pos, pos),
+            });
+        }
+        let area = match bounds {
+            Some((min, max)) => Cuboid::new(min, max - min + BlockPos::new(1, 1, 1)),
+            None => Cuboid::new(BlockPos::new(0, 0, 0), BlockPos::new(0, 0, 0)),
+        };
+        *self = self.extract(area);
+    }
+
+    /// Split this region into regions with a size of at most `max_size`.
+    ///
+    /// The parts are created with [`Self::extract`], so they keep all blocks, block entities,
+    /// entities, and pending ticks at their global coordinates. Each part is named after this
+    /// region and its index along each axis, e.g. `Region (0, 1, 0)`. A region with a size of
+    /// zero results in no parts.
+    ///
+    /// Panics if any component of `max_size` is not positive.
+    pub fn split_by(&self, max_size: BlockPos) -> Vec<Self> {
+        assert!(
+            max_size.x > 0 && max_size.y > 0 && max_size.z > 0,
+            "max size '{max_size:?}' must be positive",
+        );
+        let size = self.size.abs();
+        let mut parts = vec![];
+        for (iy, y) in (0..size.y).step_by(max_size.y as usize).enumerate() {
+            for (iz, z) in (0..size.z).step_by(max_size.z as usize).enumerate() {
+                for (ix, x) in (0..size.x).step_by(max_size.x as usize).enumerate() {
+                    let mut part = self.extract(Cuboid::new(BlockPos::new(x, y, z), max_size));
+                    part.name = format!("{} ({ix}, {iy}, {iz})", self.name).into();
+                    parts.push(part);
+                }
+            }
+        }
+        parts
+    }
+
+    /// Change the [size](Self::size) of this region.
+    ///
+    /// The `anchor` determines which part of the region stays in place. Just like the current
+    /// size, `new_size` may be negative, in which case the region grows in the negative direction
+    /// from its [position](Self::position). All blocks, block entities, entities, and pending
+    /// ticks keep their global coordinates, and the ones outside the new bounds are removed. New
+    /// positions are filled with air.
+    pub fn resize(&mut self, new_size: BlockPos, anchor: ResizeAnchor) {
+        let size = new_size.abs();
+        let position = match anchor {
+            ResizeAnchor::Position => self.position,
+            ResizeAnchor::Min => {
+                let min = min_corner(self);
+                let at_min = |min: i32, new_size: i32, size: i32| match new_size < 0 {
+                    true => min + size - 1,
+                    false => min,
+                };
+                BlockPos::new(
+                    at_min(min.x, new_size.x, size.x),
+                    at_min(min.y, new_size.y, size.y),
+                    at_min(min.z, new_size.z, size.z),
+                )
+            }
+            ResizeAnchor::Max => {
+                let max = BlockPos::new(
+                    self.max_global_x(),
+                    self.max_global_y(),
+                    self.max_global_z(),
+                );
+                let at_max = |max: i32, new_size: i32, size: i32| match new_size < 0 {
+                    true => max,
+                    false => max - size + 1,
+                };
+                BlockPos::new(
+                    at_max(max.x, new_size.x, size.x),
+                    at_max(max.y, new_size.y, size.y),
+                    at_max(max.z, new_size.z, size.z),
+                )
+            }
+        };
+        self.reshape(position, new_size);
+    }
+
+    /// Move the [position](Self::position) of this region to `origin` while keeping the opposite
+    /// corner in place.
+    ///
+    /// The [size](Self::size) is changed accordingly and may change its sign. All blocks, block
+    /// entities, entities, and pending ticks keep their global coordinates, and the ones outside
+    /// the new bounds are removed. New positions are filled with air. Axes along which this region
+    /// has a size of zero keep a size of zero.
+    pub fn set_origin(&mut self, origin: BlockPos) {
+        let size_for = |position: i32, size: i32, origin: i32| {
+            let opposite = match size < 0 {
+                true => position + size + 1,
+                false => position + size - 1,
+            };
+            match size {
+                0 => 0,
+                _ if opposite < origin => opposite - origin - 1,
+                _ => opposite - origin + 1,
+            }
+        };
+        let new_size = BlockPos::new(
+            size_for(self.position.x, self.size.x, origin.x),
+            size_for(self.position.y, self.size.y, origin.y),
+            size_for(self.position.z, self.size.z, origin.z),
+        );
+        self.reshape(origin, new_size);
+    }
+
+    /// Replace this region by one with the given position and size, which contains everything
+    /// of this region at the same global coordinates.
+    fn reshape(&mut self, position: BlockPos, size: BlockPos) {
+        let mut new = Region::new(self.name.clone(), position, size);
+        new.extra = std::mem::take(&mut self.extra);
+        new.paste(
+            self,
+            min_corner(self) - min_corner(&new),
+            PasteOptions::default(),
+        );
+        *self = new;
+    }
+
+    /// Paste another region into this one, with the minimum corner of `source` at the local
+    /// position `offset`.
+    ///
+    /// Which blocks are pasted is determined by the [mode](PasteOptions::mode). Parts of `source`
+    /// outside the bounds of this region are ignored. The block entities and pending ticks at
+    /// pasted positions are replaced by the ones of `source`, moved to the coordinates of this
+    /// region. Entities are copied if they end up inside this region and
+    /// [`entities`](PasteOptions::entities) is set.
+    ///
+    /// Returns the number of pasted blocks.
+    pub fn paste(&mut self, source: &Self, offset: BlockPos, options: PasteOptions) -> usize {
+        let air = BlockState::air();
+        let source_air = source
+            .block_palette()
+            .iter()
+            .map(|state| state == &air)
+            .collect::<Vec<_>>();
+        let pastes = |target: &Self, pos: BlockPos| match options.mode {
+            PasteMode::ReplaceAll => true,
+            PasteMode::SkipAir => !source_air[source.get_block_id(pos)],
+            PasteMode::ReplaceAir => target.get_block(pos + offset) == &air,
+        };
+
+        let size = self.size.abs();
+        // computed in usize, as the volume of a region can exceed i32 even though each side fits
+        let index = |pos: BlockPos| {
+            ((0..size.x).contains(&pos.x)
+                && (0..size.y).contains(&pos.y)
+                && (0..size.z).contains(&pos.z))
+            .then(|| {
+                let [x, y, z, width, length] =
+                    [pos.x, pos.y, pos.z, size.x, size.z].map(|n| n as usize);
+                x + (y * length + z) * width
+            })
+        };
+        let volume = util::volume(size).expect("the blocks of the region fit into memory");
+        let mut pasted = vec![false; volume];
+        let mut ids = vec![None; source.block_palette().len()];
+        let mut count = 0;
+        let source_size = source.size.abs();
+        for y in 0..source_size.y {
+            for z in 0..source_size.z {
+                for x in 0..source_size.x {
+                    let pos = BlockPos::new(x, y, z);
+                    let Some(index) = index(pos + offset) else {
+                        continue;
+                    };
+                    if !pastes(self, pos) {
+                        continue;
+                    }
+                    let id = source.get_block_id(pos);
+                    let new_id = *ids[id].get_or_insert_with(|| {
+                        self.add_to_palette(source.block_palette()[id].clone())
+                    });
+                    self.set_block_id(pos + offset, new_id);
+                    pasted[index] = true;
+                    count += 1;
+                }
+            }
+        }
+
+        let is_pasted = |pos: BlockPos| index(pos).is_some_and(|index| pasted[index]);
+        self.block_entities.retain(|e| !is_pasted(e.position()));
+        self.block_entities.extend(
+            source
+                .block_entities
+                .iter()
+                .filter(|e| source.is_in_bounds(e.position()) && is_pasted(e.position() + offset))
+                .map(|e| move_block_entity(e, e.position() + offset)),
+        );
+        let source_pasted = |pos: BlockPos| source.is_in_bounds(pos) && is_pasted(pos + offset);
+        self.pending_block_ticks
+            .retain(|tick| !is_pasted(tick.pos()));
+        self.pending_block_ticks.extend(move_ticks(
+            &source.pending_block_ticks,
+            source_pasted,
+            offset,
+        ));
+        self.pending_fluid_ticks
+            .retain(|tick| !is_pasted(tick.pos()));
+        self.pending_fluid_ticks.extend(move_ticks(
+            &source.pending_fluid_ticks,
+            source_pasted,
+            offset,
+        ));
+
+        if options.entities {
+            let shift = entity_offset(source) + offset - entity_offset(self);
+            for entity in source.entities.iter() {
+                let entity = move_entity(entity, shift);
+                if entity_block_pos(self, &entity).is_some_and(|pos| index(pos).is_some()) {
+                    self.entities.push(entity);
+                }
+            }
+        }
+        count
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> Litematic<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Replace all regions by a single region covering the [enclosing box](Self::enclosing_box).
+    ///
+    /// The regions are pasted into the new region in order using [`Region::paste`], so `mode`
+    /// decides which blocks win where regions overlap: with [`PasteMode::ReplaceAll`] later
+    /// regions win, with [`PasteMode::SkipAir`] later regions win unless they have air, and with
+    /// [`PasteMode::ReplaceAir`] earlier regions win unless they have air. Block entities and
+    /// pending ticks follow their blocks, and all entities inside the enclosing box are kept. The
+    /// new region is named after the schematic.
+    ///
+    /// A schematic without regions is left unchanged.
+    pub fn merge_regions(&mut self, mode: PasteMode) {
+        if self.regions.is_empty() {
+            return;
+        }
+        let area = self.enclosing_box();
+        let mut merged = Region::new(self.metadata.name.clone(), area.origin, area.size);
+        let options = PasteOptions {
+            mode,
+            entities: true,
+        };
+        for region in self.regions.iter() {
+            merged.paste(region, min_corner(region) - area.origin, options);
+        }
+        self.regions = vec![merged].into();
+    }
+}
+
+/// A pending block or fluid tick.
+trait Tick: Clone {
+    fn pos(&self) -> BlockPos;
+    fn set_pos(&mut self, pos: BlockPos);
+}
+
+impl Tick for PendingBlockTick {
+    fn pos(&self) -> BlockPos {
+        BlockPos::new(self.x, self.y, self.z)
+    }
+
+    fn set_pos(&mut self, pos: BlockPos) {
+        (self.x, self.y, self.z) = (pos.x, pos.y, pos.z);
+    }
+}
+
+impl Tick for PendingFluidTick {
+    fn pos(&self) -> BlockPos {
+        BlockPos::new(self.x, self.y, self.z)
+    }
+
+    fn set_pos(&mut self, pos: BlockPos) {
+        (self.x, self.y, self.z) = (pos.x, pos.y, pos.z);
+    }
+}
+
+/// Copy all ticks at positions matching `filter` and move them by `offset`.
+fn move_ticks<T: Tick>(
+    ticks: &[T],
+    mut filter: impl FnMut(BlockPos) -> bool,
+    offset: BlockPos,
+) -> Vec<T> {
+    ticks
+        .iter()
+        .filter(|tick| filter(tick.pos()))
+        .map(|tick| {
+            let mut tick = tick.clone();
+            tick.set_pos(tick.pos() + offset);
+            tick
+        })
+        .collect()
+}
+
+/// The minimum corner of a region in the schematic's coordinate system.
+fn min_corner<BlockState, Entity, BlockEntity>(
+    region: &Region<BlockState, Entity, BlockEntity>,
+) -> BlockPos
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    BlockPos::new(
+        region.min_global_x(),
+        region.min_global_y(),
+        region.min_global_z(),
+    )
+}
+
+/// The offset from entity positions to local coordinates.
+///
+/// Entity positions are relative to the [position](Region::position) of the region and not to the
+/// minimum corner.
+fn entity_offset<BlockState, Entity, BlockEntity>(
+    region: &Region<BlockState, Entity, BlockEntity>,
+) -> BlockPos
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    region.position - min_corner(region)
+}
+
+/// The local position of the block an entity is in.
+fn entity_block_pos<BlockState, Entity, BlockEntity>(
+    region: &Region<BlockState, Entity, BlockEntity>,
+    entity: &Entity,
+) -> Option<BlockPos>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    let [x, y, z] = util::entity_pos(entity)?;
+    Some(
+        entity_offset(region) + BlockPos::new(x.floor() as i32, y.floor() as i32, z.floor() as i32),
+    )
+}
+
+fn move_block_entity<T: Serialize + DeserializeOwned + Clone>(
+    block_entity: &T,
+    pos: BlockPos,
+) -> T {
+    transform::map_nbt(block_entity, |nbt| {
+        nbt.insert("x".into(), Value::Int(pos.x));
+        nbt.insert("y".into(), Value::Int(pos.y));
+        nbt.insert("z".into(), Value::Int(pos.z));
+    })
+}
+
+fn move_entity<T: Serialize + DeserializeOwned + Clone>(entity: &T, offset: BlockPos) -> T {
+    transform::map_nbt(entity, |nbt| {
+        if let Some(Value::List(pos)) = nbt.get_mut("Pos") {
+            if let [Value::Double(x), Value::Double(y), Value::Double(z)] = &mut pos[..] {
+                *x += offset.x as f64;
+                *y += offset.y as f64;
+                *z += offset.z as f64;
+            }
+        }
+    })
+}
@@ -0,0 +1,216 @@
+use std::{any::TypeId, collections::HashMap};
+
+use fastnbt::Value;
+use mcdata::{util::BlockPos, GenericBlockEntity, GenericBlockState, GenericEntity};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{util, Litematic, Region, Result};
+
+/// The result of [`Litematic::convert`] and [`Region::convert`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConversionReport {
+    /// Everything that isn't known to the target types and fell back to their `Other` variant.
+    pub fallbacks: Vec<Fallback>,
+}
+
+/// Something that fell back to the `Other` variant of an [`mcdata`] type during conversion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fallback {
+    /// A palette entry of a region.
+    Block {
+        /// The name of the region.
+        region: String,
+        /// The block state.
+        block: GenericBlockState,
+    },
+
+    /// A block entity.
+    BlockEntity {
+        /// The name of the region.
+        region: String,
+        /// The local position of the block entity.
+        pos: BlockPos,
+        /// The id of the block entity.
+        id: String,
+    },
+
+    /// An entity.
+    Entity {
+        /// The name of the region.
+        region: String,
+        /// The UUID of the entity.
+        uuid: u128,
+        /// The id of the entity.
+        id: String,
+    },
+}
+
+impl<BlockState, Entity, BlockEntity> Region<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Convert this region to use different types for block states, entities, and block entities.
+    ///
+    /// The palette, entities, and block entities are converted through their NBT representation,
+    /// so this works between any pair of [`mcdata`] types, e.g. from the generic types to the
+    /// typed ones of `mcdata::latest` and back. Anything the target types don't know is kept in
+    /// their `Other` variant and listed in the returned report. Block entities of types which keep
+    /// unknown NBT keys, like furnaces, can't be told apart from the `Other` variant and are always
+    /// listed.
+    ///
+    /// Returns an error if a value is rejected by the target type.
+    pub fn convert<BlockState2, Entity2, BlockEntity2>(
+        &self,
+    ) -> Result<(Region<BlockState2, Entity2, BlockEntity2>, ConversionReport)>
+    where
+        BlockState2: mcdata::BlockState + Serialize + DeserializeOwned + 'static,
+        Entity2: mcdata::Entity + Serialize + DeserializeOwned + 'static,
+        BlockEntity2: mcdata::BlockEntity + Serialize + DeserializeOwned + 'static,
+    {
+        let mut report = ConversionReport::default();
+        let name = || self.name.clone().into_owned();
+
+        let mut palette = Vec::with_capacity(self.block_palette().len());
+        for state in self.block_palette() {
+            let converted: BlockState2 = util::convert(state)?;
+            if is_other(&converted, Some("Properties"))? {
+                report.fallbacks.push(Fallback::Block {
+                    region: name(),
+                    block: util::convert(state)?,
+                });
+            }
+            palette.push(converted);
+        }
+
+        let mut new = Region::from_parts(
+            self.name.clone(),
+            self.position,
+            self.size,
+            palette,
+            self.block_ids().collect(),
+        );
+        for block_entity in self.block_entities.iter() {
+            let converted: BlockEntity2 = util::convert(block_entity)?;
+            if is_other(&converted, None)? {
+                let generic: GenericBlockEntity = util::convert(block_entity)?;
+                report.fallbacks.push(Fallback::BlockEntity {
+                    region: name(),
+                    pos: generic.pos,
+                    id: generic.id.into_owned(),
+                });
+            }
+            new.block_entities.push(converted);
+        }
+        for entity in self.entities.iter() {
+            let converted: Entity2 = util::convert(entity)?;
+            if is_other(&converted, None)? {
+                let generic: GenericEntity = util::convert(entity)?;
+                report.fallbacks.push(Fallback::Entity {
+                    region: name(),
+                    uuid: generic.uuid,
+                    id: generic.id.into_owned(),
+                });
+            }
+            new.entities.push(converted);
+        }
+        new.pending_block_ticks = self.pending_block_ticks.clone();
+        new.pending_fluid_ticks = self.pending_fluid_ticks.clone();
+        new.extra = self.extra.clone();
+
+        Ok((new, report))
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> Litematic<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Convert this schematic to use different types for block states, entities, and block
+    /// entities.
+    ///
+    /// See [`Region::convert`] for details. The reports of all regions are combined in order.
+    pub fn convert<BlockState2, Entity2, BlockEntity2>(
+        &self,
+    ) -> Result<(
+        Litematic<BlockState2, Entity2, BlockEntity2>,
+        ConversionReport,
+    )>
+    where
+        BlockState2: mcdata::BlockState + Serialize + DeserializeOwned + 'static,
+        Entity2: mcdata::Entity + Serialize + DeserializeOwned + 'static,
+        BlockEntity2: mcdata::BlockEntity + Serialize + DeserializeOwned + 'static,
+    {
+        let mut report = ConversionReport::default();
+        let mut regions = Vec::with_capacity(self.regions.len());
+        for region in self.regions.iter() {
+            let (region, region_report) = region.convert()?;
+            regions.push(region);
+            report.fallbacks.extend(region_report.fallbacks);
+        }
+        Ok((
+            Litematic {
+                regions: regions.into(),
+                metadata: self.metadata.clone(),
+                compact_palettes: self.compact_palettes,
+            },
+            report,
+        ))
+    }
+}
+
+/// A block state property or NBT key which no [`mcdata`] type knows, see [`is_other`].
+const PROBE: &str = "rustmatica:probe";
+
+/// Whether a converted value is the `Other` variant of one of [`mcdata`]s typed enums.
+///
+/// The traits of [`mcdata`] don't expose this, and both variants are serialized the same way. But
+/// `Other` keeps the NBT it was created from as is, while the known variants only keep what their
+/// type defines. So an unknown property is added to the NBT of the value, which is then converted
+/// again. The properties of block states are nested, so `properties` is the key of the compound the
+/// property is added to. The generic types of [`mcdata`] keep everything as well, but don't have an
+/// `Other` variant.
+fn is_other<T>(value: &T, properties: Option<&str>) -> Result<bool>
+where
+    T: Serialize + DeserializeOwned + 'static,
+{
+    let generic = [
+        TypeId::of::<GenericBlockState>(),
+        TypeId::of::<GenericEntity>(),
+        TypeId::of::<GenericBlockEntity>(),
+        TypeId::of::<Value>(),
+    ];
+    if generic.contains(&TypeId::of::<T>()) {
+        return Ok(false);
+    }
+
+    /// The compound the probe is added to, which is created if it's missing.
+    fn compound<'a>(
+        nbt: &'a mut Value,
+        key: Option<&str>,
+    ) -> Option<&'a mut HashMap<String, Value>> {
+        let nbt = match (nbt, key) {
+            (Value::Compound(map), Some(key)) => map
+                .entry(key.to_owned())
+                .or_insert_with(|| Value::Compound(HashMap::new())),
+            (nbt, None) => nbt,
+            _ => return None,
+        };
+        match nbt {
+            Value::Compound(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    let mut nbt = fastnbt::to_value(value)?;
+    let Some(map) = compound(&mut nbt, properties) else {
+        return Ok(false);
+    };
+    map.insert(PROBE.to_owned(), Value::String(String::new()));
+    let probed: T = fastnbt::from_value(&nbt)?;
+    let mut nbt = fastnbt::to_value(&probed)?;
+    Ok(compound(&mut nbt, properties).is_some_and(|map| map.contains_key(PROBE)))
+}
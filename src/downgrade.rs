@@ -0,0 +1,663 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use fastnbt::Value;
+use mcdata::{
+    data_version::{MC_1_14, MC_1_15, MC_1_16, MC_1_17, MC_1_19, MC_1_20, MC_1_20_3, MC_1_20_5},
+    util::BlockPos,
+    GenericBlockEntity, GenericBlockState, GenericEntity,
+};
+
+use crate::{
+    upgrade::{
+        has_tag, rename, EMPTY_TEXT, EQUIPMENT_TAGS, ITEM_TAGS, MC_1_21, MC_1_21_4, MC_1_21_5,
+        MC_1_21_6, MC_1_21_9, TEXT_TAGS,
+    },
+    util, Litematic,
+};
+
+type CowStr = Cow<'static, str>;
+
+/// Options for [`Litematic::downgrade`].
+///
+/// The [`Default`] options contain replacements for the wood types added since 1.19.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DowngradeOptions {
+    /// Replacements for blocks which don't exist in the target version, as a map from the name of
+    /// the missing block to the name of its replacement, e.g. `minecraft:cherry_planks` to
+    /// `minecraft:birch_planks`.
+    ///
+    /// Properties are kept when replacing a block. Blocks without a replacement are replaced with
+    /// air.
+    pub substitutions: HashMap<CowStr, CowStr>,
+
+    /// The data versions in which blocks were added, in addition to the built-in list of vanilla
+    /// blocks. Use this for blocks missing from the built-in list or for modded blocks.
+    pub added_blocks: HashMap<CowStr, i32>,
+
+    /// The data versions in which properties were added to existing blocks, as a map from the
+    /// block name and property name, in addition to the built-in list.
+    pub added_properties: HashMap<(CowStr, CowStr), i32>,
+}
+
+impl Default for DowngradeOptions {
+    fn default() -> Self {
+        const WOOD_SUFFIXES: &[&str] = &[
+            "planks",
+            "log",
+            "wood",
+            "leaves",
+            "slab",
+            "stairs",
+            "fence",
+            "fence_gate",
+            "door",
+            "trapdoor",
+            "pressure_plate",
+            "button",
+            "sign",
+            "wall_sign",
+        ];
+        let mut substitutions = HashMap::new();
+        for (new, old) in [
+            ("mangrove", "jungle"),
+            ("cherry", "birch"),
+            ("bamboo", "oak"),
+            ("pale_oak", "birch"),
+        ] {
+            for suffix in WOOD_SUFFIXES {
+                substitutions.insert(
+                    format!("minecraft:{new}_{suffix}").into(),
+                    format!("minecraft:{old}_{suffix}").into(),
+                );
+            }
+            for suffix in ["log", "wood"] {
+                substitutions.insert(
+                    format!("minecraft:stripped_{new}_{suffix}").into(),
+                    format!("minecraft:stripped_{old}_{suffix}").into(),
+                );
+            }
+        }
+        for (new, old) in [
+            ("minecraft:cherry_sapling", "minecraft:birch_sapling"),
+            ("minecraft:pale_oak_sapling", "minecraft:birch_sapling"),
+            ("minecraft:mangrove_propagule", "minecraft:jungle_sapling"),
+            ("minecraft:bamboo_block", "minecraft:oak_log"),
+            (
+                "minecraft:stripped_bamboo_block",
+                "minecraft:stripped_oak_log",
+            ),
+            ("minecraft:bamboo_mosaic", "minecraft:oak_planks"),
+            ("minecraft:bamboo_mosaic_slab", "minecraft:oak_slab"),
+            ("minecraft:bamboo_mosaic_stairs", "minecraft:oak_stairs"),
+        ] {
+            substitutions.insert(new.into(), old.into());
+        }
+        Self {
+            substitutions,
+            added_blocks: HashMap::new(),
+            added_properties: HashMap::new(),
+        }
+    }
+}
+
+/// The result of [`Litematic::downgrade`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DowngradeReport {
+    /// The data version the schematic had before downgrading.
+    pub from: i32,
+
+    /// The data version the schematic has after downgrading.
+    pub to: i32,
+
+    /// The lossy changes made to each region, in the order of [`Litematic::regions`].
+    pub regions: Vec<RegionDowngradeReport>,
+}
+
+/// The lossy changes [`Litematic::downgrade`] made to a single region.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RegionDowngradeReport {
+    /// The name of the region.
+    pub region: String,
+
+    /// All lossy changes made to the region.
+    pub changes: Vec<LossyChange>,
+}
+
+/// A lossy change made by [`Litematic::downgrade`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum LossyChange {
+    /// A block which doesn't exist in the target version was replaced.
+    BlockReplaced {
+        /// The original block state.
+        from: GenericBlockState,
+        /// The replacement.
+        to: GenericBlockState,
+    },
+
+    /// A property which doesn't exist in the target version was removed.
+    PropertyRemoved {
+        /// The original block state.
+        block: GenericBlockState,
+        /// The name of the removed property.
+        property: String,
+    },
+
+    /// A block entity was removed, as its block was replaced with air.
+    BlockEntityRemoved {
+        /// The local position of the block entity.
+        pos: BlockPos,
+        /// The id of the block entity.
+        id: String,
+    },
+
+    /// Data of a block entity which can't be represented in the target version was dropped.
+    BlockEntityDataDropped {
+        /// The local position of the block entity.
+        pos: BlockPos,
+        /// The id of the block entity.
+        id: String,
+        /// A description of the change.
+        change: &'static str,
+    },
+
+    /// A block entity is affected by a change which isn't migrated.
+    BlockEntityNotMigrated {
+        /// The local position of the block entity.
+        pos: BlockPos,
+        /// The id of the block entity.
+        id: String,
+        /// A description of the change.
+        change: &'static str,
+    },
+
+    /// An entity is affected by a change which isn't migrated.
+    EntityNotMigrated {
+        /// The UUID of the entity.
+        uuid: u128,
+        /// The id of the entity.
+        id: String,
+        /// A description of the change.
+        change: &'static str,
+    },
+}
+
+/// A single step in downgrading schematics, applied when downgrading to a data version older than
+/// its own.
+struct Revert {
+    data_version: i32,
+    description: &'static str,
+    kind: RevertKind,
+}
+
+enum RevertKind {
+    Blocks(fn(&mut GenericBlockState)),
+    /// Returns whether data was dropped.
+    BlockEntities(fn(&mut GenericBlockEntity) -> bool),
+    /// A change that isn't migrated. Everything matching is reported.
+    Unsupported {
+        block_entity: fn(&GenericBlockEntity) -> bool,
+        entity: fn(&GenericEntity) -> bool,
+    },
+}
+
+/// All known reverts, ordered by their data version from new to old.
+const REVERTS: &[Revert] = &[
+    Revert {
+        data_version: MC_1_21_9,
+        description: "rename iron chains to chains",
+        kind: RevertKind::Blocks(|state| {
+            rename(state, &[("minecraft:iron_chain", "minecraft:chain")]);
+        }),
+    },
+    Revert {
+        data_version: MC_1_21_5,
+        description: "split entity equipment",
+        kind: RevertKind::Unsupported {
+            block_entity: |_| false,
+            entity: |entity| has_tag(&entity.properties, &["equipment"]),
+        },
+    },
+    Revert {
+        data_version: MC_1_21_5,
+        description: "store text components as JSON",
+        kind: RevertKind::Unsupported {
+            block_entity: |block_entity| has_tag(&block_entity.properties, TEXT_TAGS),
+            entity: |entity| has_tag(&entity.properties, TEXT_TAGS),
+        },
+    },
+    Revert {
+        data_version: MC_1_20_5,
+        description: "store item stacks without data components",
+        kind: RevertKind::Unsupported {
+            block_entity: |block_entity| has_tag(&block_entity.properties, ITEM_TAGS),
+            entity: |entity| {
+                has_tag(&entity.properties, ITEM_TAGS)
+                    || has_tag(&entity.properties, EQUIPMENT_TAGS)
+            },
+        },
+    },
+    Revert {
+        data_version: MC_1_20_3,
+        description: "rename short grass to grass",
+        kind: RevertKind::Blocks(|state| {
+            rename(state, &[("minecraft:short_grass", "minecraft:grass")]);
+        }),
+    },
+    Revert {
+        data_version: MC_1_20,
+        description: "make sign text single sided",
+        kind: RevertKind::BlockEntities(single_sided_sign),
+    },
+    Revert {
+        data_version: MC_1_17,
+        description: "rename dirt paths and merge water cauldrons",
+        kind: RevertKind::Blocks(|state| {
+            rename(state, &[("minecraft:dirt_path", "minecraft:grass_path")]);
+            if state.name == "minecraft:water_cauldron" {
+                state.name = "minecraft:cauldron".into();
+            }
+            if state.name == "minecraft:cauldron" {
+                state.properties.entry("level".into()).or_insert("0".into());
+            }
+        }),
+    },
+    Revert {
+        data_version: MC_1_16,
+        description: "replace jigsaw orientation with facing",
+        kind: RevertKind::Blocks(|state| {
+            if state.name == "minecraft:jigsaw" {
+                if let Some(orientation) = state.properties.remove("orientation") {
+                    let facing = orientation.split('_').next().unwrap_or_default().to_owned();
+                    state.properties.insert("facing".into(), facing.into());
+                }
+            }
+        }),
+    },
+    Revert {
+        data_version: MC_1_16,
+        description: "use boolean wall sides",
+        kind: RevertKind::Blocks(|state| {
+            if state.name.ends_with("_wall") {
+                for side in ["north", "east", "south", "west"] {
+                    if let Some(value) = state.properties.get_mut(side) {
+                        match value.as_ref() {
+                            "low" | "tall" => *value = "true".into(),
+                            "none" => *value = "false".into(),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }),
+    },
+    Revert {
+        data_version: MC_1_14,
+        description: "rename oak signs and smooth stone slabs",
+        kind: RevertKind::Blocks(|state| {
+            rename(
+                state,
+                &[
+                    ("minecraft:oak_sign", "minecraft:sign"),
+                    ("minecraft:oak_wall_sign", "minecraft:wall_sign"),
+                    ("minecraft:smooth_stone_slab", "minecraft:stone_slab"),
+                ],
+            );
+        }),
+    },
+];
+
+impl Litematic<GenericBlockState, GenericEntity, GenericBlockEntity> {
+    /// Downgrade the blocks and block entities of this schematic from its
+    /// [Minecraft data version](crate::LitematicMetadata::minecraft_data_version) to an older one.
+    ///
+    /// This is the reverse of [`Self::upgrade`]. Renamed blocks and changed block entity data are
+    /// converted back step by step. Blocks which don't exist in the target version are replaced
+    /// according to [`DowngradeOptions::substitutions`] and properties which don't exist are
+    /// removed. Note that the built-in knowledge of when blocks and properties were added only
+    /// covers Minecraft 1.14 and newer and is not exhaustive.
+    ///
+    /// The returned report lists all lossy changes per region. If the schematic already has the
+    /// given data version or an older one, nothing is changed. Downgrading to a version before
+    /// the flattening in 1.13 is not supported, so the data version won't become older than
+    /// 1.13.
+    pub fn downgrade(&mut self, data_version: i32, options: &DowngradeOptions) -> DowngradeReport {
+        let from = self.metadata.minecraft_data_version;
+        let to = data_version.max(util::FLATTENING_DATA_VERSION).min(from);
+        let mut report = DowngradeReport {
+            from,
+            to,
+            regions: vec![],
+        };
+        if to == from {
+            return report;
+        }
+
+        let reverts = REVERTS
+            .iter()
+            .filter(|revert| to < revert.data_version && revert.data_version <= from)
+            .collect::<Vec<_>>();
+        let air = <GenericBlockState as mcdata::BlockState>::air();
+        for region in self.regions.iter_mut() {
+            let mut changes = vec![];
+
+            for revert in reverts.iter() {
+                match revert.kind {
+                    RevertKind::Blocks(_) => {}
+                    RevertKind::BlockEntities(f) => {
+                        for block_entity in region.block_entities.iter_mut() {
+                            if f(block_entity) {
+                                changes.push(LossyChange::BlockEntityDataDropped {
+                                    pos: block_entity.pos,
+                                    id: block_entity.id.clone().into_owned(),
+                                    change: revert.description,
+                                });
+                            }
+                        }
+                    }
+                    RevertKind::Unsupported {
+                        block_entity,
+                        entity,
+                    } => {
+                        changes.extend(
+                            region
+                                .block_entities
+                                .iter()
+                                .filter(|e| block_entity(e))
+                                .map(|e| LossyChange::BlockEntityNotMigrated {
+                                    pos: e.pos,
+                                    id: e.id.clone().into_owned(),
+                                    change: revert.description,
+                                }),
+                        );
+                        changes.extend(region.entities.iter().filter(|e| entity(e)).map(|e| {
+                            LossyChange::EntityNotMigrated {
+                                uuid: e.uuid,
+                                id: e.id.clone().into_owned(),
+                                change: revert.description,
+                            }
+                        }));
+                    }
+                }
+            }
+
+            region.map_palette(|state| {
+                let original = state.clone();
+                let mut state = state.clone();
+                let mut replaced = false;
+                // replace missing blocks, following chains of replacements
+                for _ in 0..8 {
+                    for revert in reverts.iter() {
+                        if let RevertKind::Blocks(f) = revert.kind {
+                            f(&mut state);
+                        }
+                    }
+                    if state == air || options.added_in(&state.name).is_none_or(|v| v <= to) {
+                        break;
+                    }
+                    replaced = true;
+                    match options.substitutions.get(&state.name) {
+                        Some(name) => state.name = name.clone(),
+                        None => state = air.clone(),
+                    }
+                }
+                if options.added_in(&state.name).is_some_and(|v| v > to) {
+                    state = air.clone();
+                }
+                if replaced {
+                    changes.push(LossyChange::BlockReplaced {
+                        from: original.clone(),
+                        to: state.clone(),
+                    });
+                }
+
+                let name = state.name.clone();
+                state.properties.retain(|property, _| {
+                    let keep = options
+                        .property_added_in(&name, property)
+                        .is_none_or(|v| v <= to);
+                    if !keep {
+                        changes.push(LossyChange::PropertyRemoved {
+                            block: original.clone(),
+                            property: property.clone().into_owned(),
+                        });
+                    }
+                    keep
+                });
+                state
+            });
+
+            let removed = region
+                .block_entities
+                .iter()
+                .filter(|e| {
+                    region
+                        .get_block_opt(e.pos)
+                        .is_none_or(|block| block == &air)
+                })
+                .map(|e| e.pos)
+                .collect::<Vec<_>>();
+            for pos in removed {
+                if let Some(block_entity) = region.remove_block_entity(pos) {
+                    changes.push(LossyChange::BlockEntityRemoved {
+                        pos,
+                        id: block_entity.id.into_owned(),
+                    });
+                }
+            }
+
+            report.regions.push(RegionDowngradeReport {
+                region: region.name.clone().into_owned(),
+                changes,
+            });
+        }
+        self.metadata.minecraft_data_version = to;
+        report
+    }
+}
+
+impl DowngradeOptions {
+    /// The data version a block was added in, if it is known.
+    fn added_in(&self, name: &str) -> Option<i32> {
+        self.added_blocks
+            .get(name)
+            .copied()
+            .or_else(|| vanilla_block_added_in(name.strip_prefix("minecraft:")?))
+    }
+
+    /// The data version a property was added to an existing block in, if it is known.
+    fn property_added_in(&self, name: &str, property: &str) -> Option<i32> {
+        self.added_properties
+            .get(&(Cow::Owned(name.to_owned()), Cow::Owned(property.to_owned())))
+            .copied()
+            .or_else(|| vanilla_property_added_in(name.strip_prefix("minecraft:")?, property))
+    }
+}
+
+/// The data version a vanilla block was added in, for blocks added in 1.14 or later.
+fn vanilla_block_added_in(name: &str) -> Option<i32> {
+    if let Some(plant) = name.strip_prefix("potted_") {
+        return vanilla_block_added_in(plant);
+    }
+    let any_of = |parts: &[&str]| parts.iter().any(|part| name.contains(part));
+    let version = match name {
+        _ if name.ends_with("_shelf")
+            || name.ends_with("lightning_rod") && name != "lightning_rod"
+            || any_of(&[
+                "copper_chest",
+                "copper_golem_statue",
+                "copper_bars",
+                "copper_chain",
+                "copper_lantern",
+                "copper_torch",
+                "copper_wall_torch",
+            ]) =>
+        {
+            MC_1_21_9
+        }
+        "dried_ghast" => MC_1_21_6,
+        "leaf_litter"
+        | "cactus_flower"
+        | "firefly_bush"
+        | "bush"
+        | "short_dry_grass"
+        | "tall_dry_grass"
+        | "wildflowers"
+        | "test_block"
+        | "test_instance_block" => MC_1_21_5,
+        _ if name.starts_with("pale_")
+            || name.starts_with("stripped_pale_oak")
+            || name.contains("resin")
+            || name == "creaking_heart"
+            || name.ends_with("_eyeblossom") =>
+        {
+            MC_1_21_4
+        }
+        "crafter" | "trial_spawner" | "vault" | "heavy_core" => MC_1_21,
+        _ if name.contains("tuff") && name != "tuff"
+            || any_of(&[
+                "chiseled_copper",
+                "copper_grate",
+                "copper_bulb",
+                "copper_door",
+                "copper_trapdoor",
+            ]) =>
+        {
+            MC_1_21
+        }
+        _ if any_of(&["cherry_", "hanging_sign"])
+            || name.contains("bamboo_") && name != "bamboo_sapling" =>
+        {
+            MC_1_20
+        }
+        "pink_petals"
+        | "chiseled_bookshelf"
+        | "decorated_pot"
+        | "suspicious_sand"
+        | "suspicious_gravel"
+        | "sniffer_egg"
+        | "torchflower"
+        | "torchflower_crop"
+        | "pitcher_plant"
+        | "pitcher_crop"
+        | "calibrated_sculk_sensor"
+        | "piglin_head"
+        | "piglin_wall_head" => MC_1_20,
+        _ if name.contains("mangrove")
+            || name.starts_with("mud")
+            || name.ends_with("froglight")
+            || name.starts_with("sculk") && name != "sculk_sensor" =>
+        {
+            MC_1_19
+        }
+        "packed_mud" | "frogspawn" | "reinforced_deepslate" => MC_1_19,
+        _ if any_of(&[
+            "deepslate",
+            "copper",
+            "amethyst",
+            "candle",
+            "azalea",
+            "dripleaf",
+        ]) || name.starts_with("cave_vines")
+            || name.starts_with("raw_") =>
+        {
+            MC_1_17
+        }
+        "tuff"
+        | "calcite"
+        | "tinted_glass"
+        | "lightning_rod"
+        | "pointed_dripstone"
+        | "dripstone_block"
+        | "sculk_sensor"
+        | "glow_lichen"
+        | "moss_block"
+        | "moss_carpet"
+        | "spore_blossom"
+        | "hanging_roots"
+        | "rooted_dirt"
+        | "powder_snow"
+        | "smooth_basalt"
+        | "light"
+        | "lava_cauldron"
+        | "powder_snow_cauldron"
+        | "water_cauldron"
+        | "dirt_path" => MC_1_17,
+        _ if any_of(&["crimson", "warped", "blackstone", "soul_", "twisting_vines"])
+            && name != "soul_sand"
+            || name.starts_with("weeping_vines") =>
+        {
+            MC_1_16
+        }
+        "nether_gold_ore"
+        | "basalt"
+        | "polished_basalt"
+        | "netherite_block"
+        | "ancient_debris"
+        | "crying_obsidian"
+        | "respawn_anchor"
+        | "lodestone"
+        | "target"
+        | "chain"
+        | "nether_sprouts"
+        | "shroomlight"
+        | "quartz_bricks"
+        | "chiseled_nether_bricks"
+        | "cracked_nether_bricks" => MC_1_16,
+        "bee_nest" | "beehive" | "honey_block" | "honeycomb_block" => MC_1_15,
+        _ if name.ends_with("_sign") && name != "wall_sign" => MC_1_14,
+        "bamboo" | "bamboo_sapling" | "barrel" | "bell" | "blast_furnace" | "smoker"
+        | "cartography_table" | "fletching_table" | "grindstone" | "lectern" | "smithing_table"
+        | "stonecutter" | "campfire" | "composter" | "lantern" | "jigsaw" | "scaffolding"
+        | "sweet_berry_bush" | "cornflower" | "lily_of_the_valley" | "wither_rose" => MC_1_14,
+        _ => return None,
+    };
+    Some(version)
+}
+
+/// The data version a property was added to an existing vanilla block in.
+fn vanilla_property_added_in(name: &str, property: &str) -> Option<i32> {
+    match property {
+        "waterlogged" if name.ends_with("_leaves") => Some(MC_1_19),
+        _ => None,
+    }
+}
+
+/// Move the `front_text` of signs back into the `Text1` to `Text4` tags and drop the
+/// `back_text`.
+///
+/// Returns whether non-empty back text or the waxed state was dropped.
+fn single_sided_sign(block_entity: &mut GenericBlockEntity) -> bool {
+    let nbt = &mut block_entity.properties;
+    let Some(Value::Compound(mut front_text)) = nbt.remove("front_text") else {
+        return false;
+    };
+
+    if let Some(Value::List(messages)) = front_text.remove("messages") {
+        for (line, message) in messages.into_iter().enumerate().take(4) {
+            nbt.insert(format!("Text{}", line + 1).into(), message);
+        }
+    }
+    if let Some(Value::List(messages)) = front_text.remove("filtered_messages") {
+        for (line, message) in messages.into_iter().enumerate().take(4) {
+            nbt.insert(format!("FilteredText{}", line + 1).into(), message);
+        }
+    }
+    if let Some(color) = front_text.remove("color") {
+        nbt.insert("Color".into(), color);
+    }
+    if let Some(glowing) = front_text.remove("has_glowing_text") {
+        nbt.insert("GlowingText".into(), glowing);
+    }
+
+    let has_back_text = match nbt.remove("back_text") {
+        Some(Value::Compound(back_text)) => match back_text.get("messages") {
+            Some(Value::List(messages)) => messages.iter().any(|message| {
+                !matches!(message, Value::String(text) if text.is_empty() || text == EMPTY_TEXT)
+            }),
+            _ => false,
+        },
+        _ => false,
+    };
+    let is_waxed = matches!(nbt.remove("is_waxed"), Some(Value::Byte(1..)));
+    has_back_text || is_waxed
+}
@@ -1,7 +1,6 @@
 /// A convenience type alias for [`Result`](std::result::Result) with the error variant as [`Error`].
 pub type Result<T> = std::result::Result<T, Error>;
 
-// TODO: this good?
 /// The rustmatica error type.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -12,4 +11,94 @@ pub enum Error {
     /// Any IO error.
     #[error(transparent)]
     Io(#[from] std::io::Error),
+
+    /// The version of a schematic format is not supported.
+    #[error("unsupported {format} version {version}")]
+    UnsupportedVersion {
+        /// The name of the schematic format.
+        format: &'static str,
+        /// The unsupported version.
+        version: i32,
+    },
+
+    /// The preview image of a litematic does not have 140x140 pixels.
+    #[error("preview image has {pixels} pixels, expected 140x140")]
+    InvalidPreviewSize {
+        /// The number of pixels in the preview image data.
+        pixels: usize,
+    },
+
+    /// A timestamp cannot be represented as a date and time.
+    #[error("timestamp {timestamp} is out of range")]
+    TimestampOutOfRange {
+        /// The timestamp in milliseconds since the unix epoch.
+        timestamp: i64,
+    },
+
+    /// A block references a palette entry that does not exist.
+    #[error("palette index {index} is out of range in region '{region}'")]
+    PaletteIndexOutOfRange {
+        /// The name of the region.
+        region: String,
+        /// The invalid palette index.
+        index: usize,
+    },
+
+    /// The block data of a region does not match its size.
+    #[error("block data of region '{region}' contains {actual} blocks, expected {expected}")]
+    BlockStatesLengthMismatch {
+        /// The name of the region.
+        region: String,
+        /// The number of blocks expected from the region size.
+        expected: usize,
+        /// The number of blocks actually stored.
+        actual: usize,
+    },
+
+    /// The block data of a region contains a malformed varint.
+    #[error("block data of region '{region}' contains an invalid varint")]
+    InvalidVarInt {
+        /// The name of the region.
+        region: String,
+    },
+
+    /// A region is too large to be represented in a schematic format.
+    #[error("region '{region}' is too large for the {format} format")]
+    RegionTooLarge {
+        /// The name of the schematic format.
+        format: &'static str,
+        /// The name of the region.
+        region: String,
+    },
+
+    /// A region contains data that can't be stored in the format version of the schematic.
+    #[error("{data} of region '{region}' can't be stored in {format} version {version}")]
+    UnsupportedByVersion {
+        /// The name of the schematic format.
+        format: &'static str,
+        /// The format version.
+        version: i32,
+        /// The name of the region.
+        region: String,
+        /// A description of the data.
+        data: &'static str,
+    },
+
+    /// Multiple regions of a schematic have the same name.
+    #[error("multiple regions are named '{name}'")]
+    DuplicateRegionName {
+        /// The duplicated name.
+        name: String,
+    },
+
+    /// A pattern or mask could not be parsed.
+    #[error("invalid pattern '{input}' at position {position}, expected {expected}")]
+    InvalidPattern {
+        /// The pattern or mask.
+        input: String,
+        /// The byte position of the error in the input.
+        position: usize,
+        /// A description of what was expected at the position.
+        expected: &'static str,
+    },
 }
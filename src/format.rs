@@ -0,0 +1,39 @@
+use mcdata::GenericBlockState;
+
+mod legacy;
+mod sponge;
+mod structure;
+
+pub use sponge::SpongeVersion;
+
+/// Parse a block state string like `minecraft:oak_stairs[facing=north,half=top]`.
+pub(crate) fn parse_block_state(string: &str) -> GenericBlockState {
+    let (name, properties) = match string.split_once('[') {
+        Some((name, properties)) => (name, properties.trim_end_matches(']')),
+        None => (string, ""),
+    };
+    GenericBlockState {
+        name: name.to_owned().into(),
+        properties: properties
+            .split(',')
+            .filter_map(|property| property.split_once('='))
+            .map(|(key, value)| (key.to_owned().into(), value.to_owned().into()))
+            .collect(),
+    }
+}
+
+/// Format a block state as a string like `minecraft:oak_stairs[facing=north,half=top]`.
+///
+/// Properties are sorted by name.
+pub(crate) fn format_block_state(state: &GenericBlockState) -> String {
+    if state.properties.is_empty() {
+        return state.name.to_string();
+    }
+    let mut properties = state
+        .properties
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>();
+    properties.sort();
+    format!("{}[{}]", state.name, properties.join(","))
+}
@@ -0,0 +1,887 @@
+use std::{collections::HashMap, io::Read};
+
+use fastnbt::{ByteArray, IntArray, Value};
+use flate2::read::GzDecoder;
+use mcdata::{util::BlockPos, GenericBlockState};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    format::{format_block_state, parse_block_state},
+    util, Error, Litematic, Region, Result,
+};
+
+type CowStr = std::borrow::Cow<'static, str>;
+type Compound = HashMap<String, Value>;
+
+const COLORS: [&str; 16] = [
+    "white",
+    "orange",
+    "magenta",
+    "light_blue",
+    "yellow",
+    "lime",
+    "pink",
+    "gray",
+    "light_gray",
+    "cyan",
+    "purple",
+    "blue",
+    "brown",
+    "green",
+    "red",
+    "black",
+];
+const WOODS: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+const SLABS: [&str; 8] = [
+    "stone",
+    "sandstone",
+    "petrified_oak",
+    "cobblestone",
+    "brick",
+    "stone_brick",
+    "nether_brick",
+    "quartz",
+];
+/// The floor and wall block of each legacy skull type.
+const SKULLS: [(&str, &str); 6] = [
+    ("skeleton_skull", "skeleton_wall_skull"),
+    ("wither_skeleton_skull", "wither_skeleton_wall_skull"),
+    ("zombie_head", "zombie_wall_head"),
+    ("player_head", "player_wall_head"),
+    ("creeper_head", "creeper_wall_head"),
+    ("dragon_head", "dragon_wall_head"),
+];
+const RAIL_SHAPES: [&str; 10] = [
+    "north_south",
+    "east_west",
+    "ascending_east",
+    "ascending_west",
+    "ascending_north",
+    "ascending_south",
+    "south_east",
+    "south_west",
+    "north_west",
+    "north_east",
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Schematic {
+    width: i16,
+    height: i16,
+    length: i16,
+    blocks: ByteArray,
+    #[serde(default)]
+    add_blocks: Option<ByteArray>,
+    #[serde(default)]
+    add: Option<ByteArray>,
+    data: ByteArray,
+    #[serde(default)]
+    entities: Vec<Compound>,
+    #[serde(default)]
+    tile_entities: Vec<Compound>,
+    #[serde(default)]
+    schematica_mapping: HashMap<String, i16>,
+}
+
+impl<BlockState, Entity, BlockEntity> Litematic<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Load a schematic from gzip compressed legacy MCEdit or Schematica schematic bytes, i.e. the
+    /// contents of a `.schematic` file.
+    ///
+    /// Numeric block ids and data values are mapped to block states the same way the Minecraft
+    /// 1.13 flattening does, and the [data version](crate::LitematicMetadata::minecraft_data_version) of
+    /// the resulting schematic is set to that of 1.13 accordingly. Modded blocks are only kept
+    /// when the file contains a Schematica name mapping and are otherwise replaced with air.
+    ///
+    /// Tile entities and entities are carried over as is, i.e. their NBT is not upgraded from the
+    /// legacy format. Entity positions are assumed to be relative to the schematic.
+    pub fn from_legacy_schematic(bytes: &[u8], name: impl Into<CowStr>) -> Result<Self> {
+        let mut gz = GzDecoder::new(bytes);
+        let mut extracted = vec![];
+        gz.read_to_end(&mut extracted)?;
+        let raw: Schematic = fastnbt::from_bytes(&extracted)?;
+        let name = name.into();
+
+        let size = BlockPos::new(
+            raw.width as u16 as i32,
+            raw.height as u16 as i32,
+            raw.length as u16 as i32,
+        );
+        let volume = util::volume(size).ok_or_else(|| Error::RegionTooLarge {
+            format: "legacy schematic",
+            region: name.to_string(),
+        })?;
+        // nibble arrays store two blocks per byte
+        let lengths = [
+            Some(raw.blocks.len()),
+            Some(raw.data.len()),
+            raw.add_blocks.as_ref().map(|add| add.len() * 2),
+            raw.add.as_ref().map(|add| add.len()),
+        ];
+        if let Some(actual) = lengths.into_iter().flatten().find(|len| *len < volume) {
+            return Err(Error::BlockStatesLengthMismatch {
+                region: name.to_string(),
+                expected: volume,
+                actual,
+            });
+        }
+        let ids = (0..volume)
+            .map(|index| {
+                let low = raw.blocks[index] as u8 as u16;
+                let high = match (&raw.add_blocks, &raw.add) {
+                    // two ids share one byte, even indices use the lower nibble
+                    (Some(add), _) if index & 1 == 0 => add[index >> 1] as u8 as u16 & 0x0f,
+                    (Some(add), _) => (add[index >> 1] as u8 as u16 & 0xf0) >> 4,
+                    (None, Some(add)) => add[index] as u8 as u16,
+                    (None, None) => 0,
+                };
+                high << 8 | low
+            })
+            .collect::<Vec<_>>();
+        let data_at = |index: usize| raw.data[index] as u8 & 0x0f;
+        let modded = raw
+            .schematica_mapping
+            .iter()
+            .map(|(name, id)| (*id as u16, name.as_str()))
+            .collect::<HashMap<_, _>>();
+        let tile_entities = raw
+            .tile_entities
+            .iter()
+            .filter_map(|nbt| {
+                let coord = |key| nbt.get(key).and_then(Value::as_i64).map(|c| c as i32);
+                Some(((coord("x")?, coord("y")?, coord("z")?), nbt))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut palette = vec![GenericBlockState {
+            name: "minecraft:air".into(),
+            properties: HashMap::new(),
+        }];
+        let mut palette_ids = HashMap::from([(format_block_state(&palette[0]), 0)]);
+        let mut cache = HashMap::new();
+        let mut blocks = Vec::with_capacity(volume);
+        for (index, id) in ids.iter().enumerate() {
+            let data = data_at(index);
+            let state = if needs_context(*id) {
+                let index = index as i32;
+                let pos = (
+                    index % size.x,
+                    index / size.x / size.z,
+                    index / size.x % size.z,
+                );
+                let layer = (size.x * size.z) as usize;
+                let below = (index as usize)
+                    .checked_sub(layer)
+                    .map(|below| (ids[below], data_at(below)));
+                let above = Some(index as usize + layer)
+                    .filter(|above| *above < volume)
+                    .map(|above| (ids[above], data_at(above)));
+                contextual_block(*id, data, below, above, tile_entities.get(&pos).copied())
+            } else if let Some(palette_id) = cache.get(&(*id, data)) {
+                blocks.push(*palette_id);
+                continue;
+            } else {
+                legacy_block(*id, data)
+                    .map(|state| parse_block_state(&format!("minecraft:{state}")))
+                    .or_else(|| {
+                        modded.get(id).map(|name| GenericBlockState {
+                            name: name.to_string().into(),
+                            properties: HashMap::new(),
+                        })
+                    })
+            };
+            let state = state.unwrap_or_else(|| palette[0].clone());
+            let palette_id = *palette_ids
+                .entry(format_block_state(&state))
+                .or_insert_with(|| {
+                    palette.push(state);
+                    palette.len() - 1
+                });
+            if !needs_context(*id) {
+                cache.insert((*id, data), palette_id);
+            }
+            blocks.push(palette_id);
+        }
+
+        let palette = palette
+            .iter()
+            .map(util::convert)
+            .collect::<fastnbt::error::Result<Vec<_>>>()?;
+        let mut region = Region::from_parts(name.clone(), BlockPos::ORIGIN, size, palette, blocks);
+        for nbt in raw.tile_entities {
+            region.block_entities.push(util::convert(&nbt)?);
+        }
+        for mut nbt in raw.entities {
+            // litematica requires a UUID for every entity, legacy versions stored it as two longs
+            let most = nbt.remove("UUIDMost").and_then(|v| v.as_i64());
+            let least = nbt.remove("UUIDLeast").and_then(|v| v.as_i64());
+            let uuid = [most.unwrap_or_default(), least.unwrap_or_default()]
+                .into_iter()
+                .flat_map(|half| [(half >> 32) as i32, half as i32])
+                .collect();
+            nbt.entry("UUID".to_owned())
+                .or_insert(Value::IntArray(IntArray::new(uuid)));
+            region.entities.push(util::convert(&nbt)?);
+        }
+
+        let mut litematic = Litematic::new(name, "", "");
+        litematic.metadata.minecraft_data_version = util::FLATTENING_DATA_VERSION;
+        litematic.regions.push(region);
+        Ok(litematic)
+    }
+}
+
+/// Whether the block state of a legacy id depends on surrounding blocks or tile entities.
+fn needs_context(id: u16) -> bool {
+    matches!(id, 26 | 64 | 71 | 144 | 175 | 176 | 177 | 193..=197)
+}
+
+/// Map a legacy block using its neighbors above and below, and its tile entity.
+fn contextual_block(
+    id: u16,
+    data: u8,
+    below: Option<(u16, u8)>,
+    above: Option<(u16, u8)>,
+    tile_entity: Option<&Compound>,
+) -> Option<GenericBlockState> {
+    let tile_int = |key| tile_entity?.get(key)?.as_i64();
+    let state = match id {
+        // the lower door half stores the facing and open state, the upper half the hinge and
+        // powered state
+        64 | 71 | 193..=197 => {
+            let (lower, upper) = if data & 8 == 0 {
+                (
+                    data,
+                    above.filter(|(above, _)| *above == id).map_or(8, |b| b.1),
+                )
+            } else {
+                (
+                    below.filter(|(below, _)| *below == id).map_or(0, |b| b.1),
+                    data,
+                )
+            };
+            let name = match id {
+                64 => "oak_door",
+                71 => "iron_door",
+                _ => [
+                    "spruce_door",
+                    "birch_door",
+                    "jungle_door",
+                    "acacia_door",
+                    "dark_oak_door",
+                ][id as usize - 193],
+            };
+            format!(
+                "{name}[facing={},half={},hinge={},open={},powered={}]",
+                ["east", "south", "west", "north"][lower as usize & 3],
+                if data & 8 == 0 { "lower" } else { "upper" },
+                if upper & 1 == 0 { "left" } else { "right" },
+                lower & 4 != 0,
+                upper & 2 != 0,
+            )
+        }
+        // the upper half of double plants doesn't store the plant type
+        175 => {
+            let plant = if data & 8 == 0 {
+                data
+            } else {
+                below.filter(|(below, _)| *below == id).map_or(0, |b| b.1)
+            };
+            format!(
+                "{}[half={}]",
+                *[
+                    "sunflower",
+                    "lilac",
+                    "tall_grass",
+                    "large_fern",
+                    "rose_bush",
+                    "peony",
+                ]
+                .get(plant as usize & 7)?,
+                if data & 8 == 0 { "lower" } else { "upper" },
+            )
+        }
+        26 => format!(
+            "{}_bed[facing={},occupied={},part={}]",
+            COLORS[tile_int("color").unwrap_or(14) as usize & 15],
+            ["south", "west", "north", "east"][data as usize & 3],
+            data & 4 != 0,
+            if data & 8 == 0 { "foot" } else { "head" },
+        ),
+        144 => {
+            let (skull, wall_skull) =
+                SKULLS.get(tile_int("SkullType").unwrap_or_default() as usize)?;
+            match data & 7 {
+                1 => format!(
+                    "{skull}[rotation={}]",
+                    tile_int("Rot").unwrap_or_default() & 15
+                ),
+                facing => format!("{wall_skull}[facing={}]", facing_2_to_5(facing)?),
+            }
+        }
+        // banner base colors are stored as dye damage values, which are reversed
+        176 => format!(
+            "{}_banner[rotation={data}]",
+            COLORS[15 - (tile_int("Base").unwrap_or(15) as usize & 15)],
+        ),
+        177 => format!(
+            "{}_wall_banner[facing={}]",
+            COLORS[15 - (tile_int("Base").unwrap_or(15) as usize & 15)],
+            facing_2_to_5(data)?,
+        ),
+        _ => return None,
+    };
+    Some(parse_block_state(&format!("minecraft:{state}")))
+}
+
+/// Map facing data values where 0 to 5 are down, up, north, south, west, and east.
+fn facing_0_to_5(data: u8) -> Option<&'static str> {
+    ["down", "up", "north", "south", "west", "east"]
+        .get(data as usize)
+        .copied()
+}
+
+/// Map facing data values where 2 to 5 are north, south, west, and east.
+fn facing_2_to_5(data: u8) -> Option<&'static str> {
+    facing_0_to_5(data).filter(|_| data >= 2)
+}
+
+/// Map horizontal facing data values where 0 to 3 are south, west, north, and east.
+fn facing_swne(data: u8) -> &'static str {
+    ["south", "west", "north", "east"][data as usize & 3]
+}
+
+/// Map axis data values where 0 to 2 are y, x, and z.
+fn axis(data: u8) -> &'static str {
+    ["y", "x", "z", "y"][data as usize & 3]
+}
+
+/// Map a legacy numeric block id and data value to a flattened block state string without the
+/// `minecraft:` namespace.
+fn legacy_block(id: u16, data: u8) -> Option<String> {
+    let d = data as usize;
+    let top = data & 8 != 0;
+    let simple = |name: &str| Some(name.to_owned());
+    match id {
+        0 => simple("air"),
+        1 => [
+            "stone",
+            "granite",
+            "polished_granite",
+            "diorite",
+            "polished_diorite",
+            "andesite",
+            "polished_andesite",
+        ]
+        .get(d)
+        .map(|name| name.to_string()),
+        2 => simple("grass_block[snowy=false]"),
+        3 => ["dirt", "coarse_dirt", "podzol[snowy=false]"]
+            .get(d)
+            .map(|name| name.to_string()),
+        4 => simple("cobblestone"),
+        5 => WOODS.get(d).map(|wood| format!("{wood}_planks")),
+        6 => WOODS
+            .get(d & 7)
+            .map(|wood| format!("{wood}_sapling[stage={}]", d >> 3)),
+        7 => simple("bedrock"),
+        8 | 9 => Some(format!("water[level={d}]")),
+        10 | 11 => Some(format!("lava[level={d}]")),
+        12 => ["sand", "red_sand"].get(d).map(|name| name.to_string()),
+        13 => simple("gravel"),
+        14 => simple("gold_ore"),
+        15 => simple("iron_ore"),
+        16 => simple("coal_ore"),
+        17 | 162 => {
+            let wood = WOODS.get((d & 3) + if id == 162 { 4 } else { 0 })?;
+            Some(match d >> 2 {
+                3 => format!("{wood}_wood[axis=y]"),
+                axis_data => format!("{wood}_log[axis={}]", axis(axis_data as u8)),
+            })
+        }
+        18 | 161 => WOODS
+            .get((d & 3) + if id == 161 { 4 } else { 0 })
+            .map(|wood| format!("{wood}_leaves[distance=7,persistent={}]", data & 4 != 0)),
+        19 => ["sponge", "wet_sponge"].get(d).map(|name| name.to_string()),
+        20 => simple("glass"),
+        21 => simple("lapis_ore"),
+        22 => simple("lapis_block"),
+        23 | 158 => Some(format!(
+            "{}[facing={},triggered={top}]",
+            if id == 23 { "dispenser" } else { "dropper" },
+            facing_0_to_5(data & 7)?,
+        )),
+        24 | 179 => {
+            let prefix = if id == 179 { "red_" } else { "" };
+            ["", "chiseled_", "cut_"]
+                .get(d)
+                .map(|kind| format!("{kind}{prefix}sandstone"))
+        }
+        25 => simple("note_block"),
+        27 | 28 | 157 => Some(format!(
+            "{}[powered={top},shape={}]",
+            match id {
+                27 => "powered_rail",
+                28 => "detector_rail",
+                _ => "activator_rail",
+            },
+            RAIL_SHAPES.get(d & 7).filter(|_| d & 7 < 6)?,
+        )),
+        29 | 33 => Some(format!(
+            "{}[extended={top},facing={}]",
+            if id == 29 { "sticky_piston" } else { "piston" },
+            facing_0_to_5(data & 7)?,
+        )),
+        30 => simple("cobweb"),
+        31 => ["dead_bush", "grass", "fern"]
+            .get(d)
+            .map(|name| name.to_string()),
+        32 => simple("dead_bush"),
+        34 => Some(format!(
+            "piston_head[facing={},short=false,type={}]",
+            facing_0_to_5(data & 7)?,
+            if top { "sticky" } else { "normal" },
+        )),
+        35 => Some(format!("{}_wool", COLORS[d])),
+        36 => Some(format!(
+            "moving_piston[facing={},type={}]",
+            facing_0_to_5(data & 7)?,
+            if top { "sticky" } else { "normal" },
+        )),
+        37 => simple("dandelion"),
+        38 => [
+            "poppy",
+            "blue_orchid",
+            "allium",
+            "azure_bluet",
+            "red_tulip",
+            "orange_tulip",
+            "white_tulip",
+            "pink_tulip",
+            "oxeye_daisy",
+        ]
+        .get(d)
+        .map(|name| name.to_string()),
+        39 => simple("brown_mushroom"),
+        40 => simple("red_mushroom"),
+        41 => simple("gold_block"),
+        42 => simple("iron_block"),
+        43 => match d {
+            8 => simple("smooth_stone"),
+            9 => simple("smooth_sandstone"),
+            15 => simple("smooth_quartz"),
+            _ => SLABS
+                .get(d)
+                .map(|slab| format!("{slab}_slab[type=double]")),
+        },
+        44 => Some(format!(
+            "{}_slab[type={}]",
+            SLABS[d & 7],
+            if top { "top" } else { "bottom" }
+        )),
+        45 => simple("bricks"),
+        46 => simple("tnt"),
+        47 => simple("bookshelf"),
+        48 => simple("mossy_cobblestone"),
+        49 => simple("obsidian"),
+        50 | 75 | 76 => {
+            let (torch, lit) = match id {
+                50 => ("", ""),
+                75 => ("redstone_", "lit=false,"),
+                _ => ("redstone_", "lit=true,"),
+            };
+            match data {
+                1..=4 => Some(format!(
+                    "{torch}wall_torch[{lit}facing={}]",
+                    ["east", "west", "south", "north"][d - 1],
+                )),
+                _ => Some(format!("{torch}torch[{}]", lit.trim_end_matches(','))),
+            }
+        }
+        51 => Some(format!("fire[age={d}]")),
+        52 => simple("spawner"),
+        53 | 67 | 108 | 109 | 114 | 128 | 134..=136 | 156 | 163 | 164 | 180 | 203 => {
+            let name = match id {
+                53 => "oak",
+                67 => "cobblestone",
+                108 => "brick",
+                109 => "stone_brick",
+                114 => "nether_brick",
+                128 => "sandstone",
+                134 => "spruce",
+                135 => "birch",
+                136 => "jungle",
+                156 => "quartz",
+                163 => "acacia",
+                164 => "dark_oak",
+                180 => "red_sandstone",
+                _ => "purpur",
+            };
+            Some(format!(
+                "{name}_stairs[facing={},half={},shape=straight]",
+                ["east", "west", "south", "north"][d & 3],
+                if data & 4 != 0 { "top" } else { "bottom" },
+            ))
+        }
+        54 | 130 | 146 => Some(format!(
+            "{}[facing={}]",
+            match id {
+                54 => "chest",
+                130 => "ender_chest",
+                _ => "trapped_chest",
+            },
+            facing_2_to_5(data).unwrap_or("north"),
+        )),
+        55 => Some(format!("redstone_wire[power={d}]")),
+        56 => simple("diamond_ore"),
+        57 => simple("diamond_block"),
+        58 => simple("crafting_table"),
+        59 | 141 | 142 => Some(format!(
+            "{}[age={}]",
+            match id {
+                59 => "wheat",
+                141 => "carrots",
+                _ => "potatoes",
+            },
+            d & 7,
+        )),
+        60 => Some(format!("farmland[moisture={}]", d & 7)),
+        61 | 62 => Some(format!(
+            "furnace[facing={},lit={}]",
+            facing_2_to_5(data).unwrap_or("north"),
+            id == 62,
+        )),
+        63 => Some(format!("sign[rotation={d}]")),
+        65 | 68 => Some(format!(
+            "{}[facing={}]",
+            if id == 65 { "ladder" } else { "wall_sign" },
+            facing_2_to_5(data).unwrap_or("north"),
+        )),
+        66 => RAIL_SHAPES
+            .get(d)
+            .map(|shape| format!("rail[shape={shape}]")),
+        69 => {
+            let (face, facing) = match data & 7 {
+                0 => ("ceiling", "west"),
+                1 => ("wall", "east"),
+                2 => ("wall", "west"),
+                3 => ("wall", "south"),
+                4 => ("wall", "north"),
+                5 => ("floor", "north"),
+                6 => ("floor", "west"),
+                _ => ("ceiling", "north"),
+            };
+            Some(format!("lever[face={face},facing={facing},powered={top}]"))
+        }
+        70 | 72 => Some(format!(
+            "{}_pressure_plate[powered={}]",
+            if id == 70 { "stone" } else { "oak" },
+            data & 1 != 0,
+        )),
+        73 | 74 => Some(format!("redstone_ore[lit={}]", id == 74)),
+        77 | 143 => {
+            let (face, facing) = match data & 7 {
+                0 => ("ceiling", "north"),
+                1 => ("wall", "east"),
+                2 => ("wall", "west"),
+                3 => ("wall", "south"),
+                4 => ("wall", "north"),
+                _ => ("floor", "north"),
+            };
+            Some(format!(
+                "{}_button[face={face},facing={facing},powered={top}]",
+                if id == 77 { "stone" } else { "oak" },
+            ))
+        }
+        78 => Some(format!("snow[layers={}]", (d & 7) + 1)),
+        79 => simple("ice"),
+        80 => simple("snow_block"),
+        81 => Some(format!("cactus[age={d}]")),
+        82 => simple("clay"),
+        83 => Some(format!("sugar_cane[age={d}]")),
+        84 => Some(format!("jukebox[has_record={}]", data == 1)),
+        85 => simple("oak_fence"),
+        86 | 91 => Some(format!(
+            "{}[facing={}]",
+            if id == 86 {
+                "carved_pumpkin"
+            } else {
+                "jack_o_lantern"
+            },
+            facing_swne(data),
+        )),
+        87 => simple("netherrack"),
+        88 => simple("soul_sand"),
+        89 => simple("glowstone"),
+        90 => Some(format!(
+            "nether_portal[axis={}]",
+            if data == 2 { "z" } else { "x" }
+        )),
+        92 => Some(format!("cake[bites={}]", d.min(6))),
+        93 | 94 => Some(format!(
+            "repeater[delay={},facing={},locked=false,powered={}]",
+            (d >> 2) + 1,
+            facing_swne(data),
+            id == 94,
+        )),
+        95 => Some(format!("{}_stained_glass", COLORS[d])),
+        96 | 167 => Some(format!(
+            "{}_trapdoor[facing={},half={},open={}]",
+            if id == 96 { "oak" } else { "iron" },
+            ["north", "south", "west", "east"][d & 3],
+            if top { "top" } else { "bottom" },
+            data & 4 != 0,
+        )),
+        97 => [
+            "stone",
+            "cobblestone",
+            "stone_bricks",
+            "mossy_stone_bricks",
+            "cracked_stone_bricks",
+            "chiseled_stone_bricks",
+        ]
+        .get(d)
+        .map(|name| format!("infested_{name}")),
+        98 => [
+            "stone_bricks",
+            "mossy_stone_bricks",
+            "cracked_stone_bricks",
+            "chiseled_stone_bricks",
+        ]
+        .get(d)
+        .map(|name| name.to_string()),
+        99 | 100 => {
+            let name = if id == 99 {
+                "brown_mushroom_block"
+            } else {
+                "red_mushroom_block"
+            };
+            // the faces showing the cap texture as north, east, south, west, up, and down
+            let faces = match data {
+                1 => "tffttf",
+                2 => "tffftf",
+                3 => "ttfftf",
+                4 => "fffttf",
+                5 => "fffftf",
+                6 => "ftfftf",
+                7 => "fftttf",
+                8 => "fftftf",
+                9 => "fttftf",
+                10 => return simple("mushroom_stem[down=false,east=true,north=true,south=true,up=false,west=true]"),
+                14 => "tttttt",
+                15 => return simple("mushroom_stem[down=true,east=true,north=true,south=true,up=true,west=true]"),
+                _ => "ffffff",
+            };
+            let face = |i: usize| faces.as_bytes().get(i) == Some(&b't');
+            Some(format!(
+                "{name}[down={},east={},north={},south={},up={},west={}]",
+                face(5),
+                face(1),
+                face(0),
+                face(2),
+                face(4),
+                face(3),
+            ))
+        }
+        101 => simple("iron_bars"),
+        102 => simple("glass_pane"),
+        103 => simple("melon"),
+        104 | 105 => Some(format!(
+            "{}_stem[age={}]",
+            if id == 104 { "pumpkin" } else { "melon" },
+            d & 7,
+        )),
+        106 => Some(format!(
+            "vine[east={},north={},south={},up=false,west={}]",
+            data & 8 != 0,
+            data & 4 != 0,
+            data & 1 != 0,
+            data & 2 != 0,
+        )),
+        107 | 183..=187 => {
+            let wood = match id {
+                107 => "oak",
+                183 => "spruce",
+                184 => "birch",
+                185 => "jungle",
+                186 => "dark_oak",
+                _ => "acacia",
+            };
+            Some(format!(
+                "{wood}_fence_gate[facing={},in_wall=false,open={},powered={top}]",
+                facing_swne(data),
+                data & 4 != 0,
+            ))
+        }
+        110 => simple("mycelium[snowy=false]"),
+        111 => simple("lily_pad"),
+        112 => simple("nether_bricks"),
+        113 => simple("nether_brick_fence"),
+        115 => Some(format!("nether_wart[age={}]", d & 3)),
+        116 => simple("enchanting_table"),
+        117 => Some(format!(
+            "brewing_stand[has_bottle_0={},has_bottle_1={},has_bottle_2={}]",
+            data & 1 != 0,
+            data & 2 != 0,
+            data & 4 != 0,
+        )),
+        118 => Some(format!("cauldron[level={}]", d & 3)),
+        119 => simple("end_portal"),
+        120 => Some(format!(
+            "end_portal_frame[eye={},facing={}]",
+            data & 4 != 0,
+            facing_swne(data),
+        )),
+        121 => simple("end_stone"),
+        122 => simple("dragon_egg"),
+        123 | 124 => Some(format!("redstone_lamp[lit={}]", id == 124)),
+        125 => WOODS
+            .get(d & 7)
+            .map(|wood| format!("{wood}_slab[type=double]")),
+        126 => WOODS.get(d & 7).map(|wood| {
+            format!(
+                "{wood}_slab[type={}]",
+                if top { "top" } else { "bottom" }
+            )
+        }),
+        127 => Some(format!(
+            "cocoa[age={},facing={}]",
+            (d >> 2).min(2),
+            facing_swne(data),
+        )),
+        129 => simple("emerald_ore"),
+        131 => Some(format!(
+            "tripwire_hook[attached={},facing={},powered={top}]",
+            data & 4 != 0,
+            facing_swne(data),
+        )),
+        132 => Some(format!(
+            "tripwire[attached={},disarmed={top},east=false,north=false,powered={},south=false,west=false]",
+            data & 4 != 0,
+            data & 1 != 0,
+        )),
+        133 => simple("emerald_block"),
+        137 | 210 | 211 => Some(format!(
+            "{}[conditional={top},facing={}]",
+            match id {
+                137 => "command_block",
+                210 => "repeating_command_block",
+                _ => "chain_command_block",
+            },
+            facing_0_to_5(data & 7)?,
+        )),
+        138 => simple("beacon"),
+        139 => ["cobblestone_wall", "mossy_cobblestone_wall"]
+            .get(d)
+            .map(|name| name.to_string()),
+        140 => simple("flower_pot"),
+        145 => ["anvil", "chipped_anvil", "damaged_anvil"]
+            .get(d >> 2)
+            .map(|name| format!("{name}[facing={}]", facing_swne(data))),
+        147 | 148 => Some(format!(
+            "{}_weighted_pressure_plate[power={d}]",
+            if id == 147 { "light" } else { "heavy" },
+        )),
+        149 | 150 => Some(format!(
+            "comparator[facing={},mode={},powered={}]",
+            facing_swne(data),
+            if data & 4 != 0 { "subtract" } else { "compare" },
+            top || id == 150,
+        )),
+        151 | 178 => Some(format!(
+            "daylight_detector[inverted={},power={d}]",
+            id == 178
+        )),
+        152 => simple("redstone_block"),
+        153 => simple("nether_quartz_ore"),
+        154 => Some(format!(
+            "hopper[enabled={},facing={}]",
+            !top,
+            facing_0_to_5(data & 7).filter(|facing| *facing != "up")?,
+        )),
+        155 => match data {
+            0 => simple("quartz_block"),
+            1 => simple("chiseled_quartz_block"),
+            2 => simple("quartz_pillar[axis=y]"),
+            3 => simple("quartz_pillar[axis=x]"),
+            4 => simple("quartz_pillar[axis=z]"),
+            _ => None,
+        },
+        159 => Some(format!("{}_terracotta", COLORS[d])),
+        160 => Some(format!("{}_stained_glass_pane", COLORS[d])),
+        165 => simple("slime_block"),
+        166 => simple("barrier"),
+        168 => ["prismarine", "prismarine_bricks", "dark_prismarine"]
+            .get(d)
+            .map(|name| name.to_string()),
+        169 => simple("sea_lantern"),
+        170 | 202 | 216 => Some(format!(
+            "{}[axis={}]",
+            match id {
+                170 => "hay_block",
+                202 => "purpur_pillar",
+                _ => "bone_block",
+            },
+            axis(data >> 2),
+        )),
+        171 => Some(format!("{}_carpet", COLORS[d])),
+        172 => simple("terracotta"),
+        173 => simple("coal_block"),
+        174 => simple("packed_ice"),
+        181 => match d {
+            8 => simple("smooth_red_sandstone"),
+            _ => simple("red_sandstone_slab[type=double]"),
+        },
+        182 => Some(format!(
+            "red_sandstone_slab[type={}]",
+            if top { "top" } else { "bottom" }
+        )),
+        188..=192 => Some(format!(
+            "{}_fence",
+            ["spruce", "birch", "jungle", "dark_oak", "acacia"][id as usize - 188]
+        )),
+        198 => Some(format!("end_rod[facing={}]", facing_0_to_5(data & 7)?)),
+        199 => simple("chorus_plant"),
+        200 => Some(format!("chorus_flower[age={}]", d.min(5))),
+        201 => simple("purpur_block"),
+        204 => simple("purpur_slab[type=double]"),
+        205 => Some(format!(
+            "purpur_slab[type={}]",
+            if top { "top" } else { "bottom" }
+        )),
+        206 => simple("end_stone_bricks"),
+        207 => Some(format!("beetroots[age={}]", d & 3)),
+        208 => simple("grass_path"),
+        209 => simple("end_gateway"),
+        212 => Some(format!("frosted_ice[age={}]", d & 3)),
+        213 => simple("magma_block"),
+        214 => simple("nether_wart_block"),
+        215 => simple("red_nether_bricks"),
+        217 => simple("structure_void"),
+        218 => Some(format!(
+            "observer[facing={},powered={top}]",
+            facing_0_to_5(data & 7)?
+        )),
+        219..=234 => Some(format!(
+            "{}_shulker_box[facing={}]",
+            COLORS[id as usize - 219],
+            facing_0_to_5(data & 7)?,
+        )),
+        235..=250 => Some(format!(
+            "{}_glazed_terracotta[facing={}]",
+            COLORS[id as usize - 235],
+            facing_swne(data),
+        )),
+        251 => Some(format!("{}_concrete", COLORS[d])),
+        252 => Some(format!("{}_concrete_powder", COLORS[d])),
+        255 => ["save", "load", "corner", "data"]
+            .get(d)
+            .map(|mode| format!("structure_block[mode={mode}]")),
+        _ => None,
+    }
+}
@@ -0,0 +1,559 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use fastnbt::{ByteArray, IntArray, SerOpts, Value};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use mcdata::{util::BlockPos, GenericBlockState};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    format::{format_block_state, parse_block_state},
+    util, Error, Litematic, Region, Result,
+};
+
+type Compound = HashMap<String, Value>;
+
+/// A version of the [Sponge schematic format](https://github.com/SpongePowered/Schematic-Specification),
+/// as used by WorldEdit for `.schem` files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SpongeVersion {
+    /// Version 2, used by WorldEdit for Minecraft 1.13 up to 1.20.4.
+    V2,
+
+    /// Version 3, used by WorldEdit since Minecraft 1.20.5.
+    #[default]
+    V3,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VersionProbe {
+    version: Option<i32>,
+    schematic: Option<Box<VersionProbe>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicV2 {
+    version: i32,
+    data_version: i32,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    metadata: Compound,
+    width: i16,
+    height: i16,
+    length: i16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offset: Option<IntArray>,
+    #[serde(default)]
+    palette_max: i32,
+    palette: HashMap<String, i32>,
+    block_data: ByteArray,
+    #[serde(default)]
+    block_entities: Vec<Compound>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    entities: Vec<Compound>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RootV3 {
+    schematic: SchematicV3,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicV3 {
+    version: i32,
+    data_version: i32,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    metadata: Compound,
+    width: i16,
+    height: i16,
+    length: i16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offset: Option<IntArray>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blocks: Option<BlockContainer>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    entities: Vec<Compound>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BlockContainer {
+    palette: HashMap<String, i32>,
+    data: ByteArray,
+    #[serde(default)]
+    block_entities: Vec<Compound>,
+}
+
+/// The version independent contents of a Sponge schematic.
+struct Sponge {
+    data_version: i32,
+    metadata: Compound,
+    size: BlockPos,
+    offset: BlockPos,
+    palette: HashMap<String, i32>,
+    /// The varint encoded block data, or `None` if the schematic has no blocks at all.
+    data: Option<Vec<i8>>,
+    block_entities: Vec<Compound>,
+    entities: Vec<Compound>,
+}
+
+impl<BlockState, Entity, BlockEntity> Litematic<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Load a schematic from gzip compressed Sponge schematic bytes, i.e. the contents of a
+    /// `.schem` file.
+    ///
+    /// Versions 2 and 3 of the format are supported. The resulting schematic contains a single
+    /// region positioned at the Sponge `Offset`.
+    pub fn from_sponge(bytes: &[u8]) -> Result<Self> {
+        let mut gz = GzDecoder::new(bytes);
+        let mut extracted = vec![];
+        gz.read_to_end(&mut extracted)?;
+
+        let probe: VersionProbe = fastnbt::from_bytes(&extracted)?;
+        let sponge = match probe.schematic.map_or(probe.version, |inner| inner.version) {
+            Some(2) => Sponge::from_v2(fastnbt::from_bytes(&extracted)?),
+            Some(3) => Sponge::from_v3(fastnbt::from_bytes::<RootV3>(&extracted)?.schematic),
+            version => {
+                return Err(Error::UnsupportedVersion {
+                    format: "sponge schematic",
+                    version: version.unwrap_or_default(),
+                })
+            }
+        };
+        sponge.into_litematic()
+    }
+
+    /// Write this schematic to gzip compressed Sponge schematic bytes, i.e. the contents of a
+    /// `.schem` file.
+    ///
+    /// The Sponge format only supports a single box of blocks, so all regions are combined into
+    /// one covering the [enclosing box](Self::enclosing_box). Where regions overlap, non-air
+    /// blocks of later regions take precedence.
+    ///
+    /// Returns [`Error::RegionTooLarge`] if the enclosing box is larger than 65535 blocks along
+    /// any axis.
+    pub fn to_sponge(&self, version: SpongeVersion) -> Result<Vec<u8>> {
+        let sponge = Sponge::from_litematic(self)?;
+        let bytes = match version {
+            SpongeVersion::V2 => fastnbt::to_bytes_with_opts(
+                &sponge.into_v2(),
+                SerOpts::new().root_name("Schematic"),
+            )?,
+            SpongeVersion::V3 => fastnbt::to_bytes(&RootV3 {
+                schematic: sponge.into_v3(),
+            })?,
+        };
+
+        let mut buf = vec![];
+        let mut gz = GzEncoder::new(&mut buf, Compression::default());
+        gz.write_all(&bytes)?;
+        gz.finish()?;
+        Ok(buf)
+    }
+}
+
+impl Sponge {
+    fn from_v2(raw: SchematicV2) -> Self {
+        Self {
+            data_version: raw.data_version,
+            metadata: raw.metadata,
+            size: size_from_raw(raw.width, raw.height, raw.length),
+            offset: offset_from_raw(raw.offset),
+            palette: raw.palette,
+            data: Some(raw.block_data.into_inner()),
+            block_entities: raw.block_entities,
+            entities: raw.entities,
+        }
+    }
+
+    fn from_v3(raw: SchematicV3) -> Self {
+        let (palette, data, block_entities) = match raw.blocks {
+            Some(blocks) => (
+                blocks.palette,
+                Some(blocks.data.into_inner()),
+                blocks.block_entities,
+            ),
+            None => (HashMap::new(), None, vec![]),
+        };
+        Self {
+            data_version: raw.data_version,
+            metadata: raw.metadata,
+            size: size_from_raw(raw.width, raw.height, raw.length),
+            offset: offset_from_raw(raw.offset),
+            palette,
+            data,
+            block_entities: block_entities.into_iter().map(flatten_data).collect(),
+            entities: raw.entities.into_iter().map(flatten_data).collect(),
+        }
+    }
+
+    fn into_v2(self) -> SchematicV2 {
+        SchematicV2 {
+            version: 2,
+            data_version: self.data_version,
+            metadata: self.metadata,
+            // sizes are checked to fit into unsigned shorts by `from_litematic`
+            width: self.size.x as u16 as i16,
+            height: self.size.y as u16 as i16,
+            length: self.size.z as u16 as i16,
+            offset: Some(IntArray::new(vec![
+                self.offset.x,
+                self.offset.y,
+                self.offset.z,
+            ])),
+            palette_max: self.palette.len() as i32,
+            palette: self.palette,
+            // only schematics read from version 3 files can lack block data
+            block_data: ByteArray::new(self.data.unwrap_or_default()),
+            block_entities: self.block_entities,
+            entities: self.entities,
+        }
+    }
+
+    fn into_v3(self) -> SchematicV3 {
+        SchematicV3 {
+            version: 3,
+            data_version: self.data_version,
+            metadata: self.metadata,
+            // sizes are checked to fit into unsigned shorts by `from_litematic`
+            width: self.size.x as u16 as i16,
+            height: self.size.y as u16 as i16,
+            length: self.size.z as u16 as i16,
+            offset: Some(IntArray::new(vec![
+                self.offset.x,
+                self.offset.y,
+                self.offset.z,
+            ])),
+            blocks: self.data.map(|data| BlockContainer {
+                palette: self.palette,
+                data: ByteArray::new(data),
+                block_entities: self.block_entities.into_iter().map(nest_data).collect(),
+            }),
+            entities: self.entities.into_iter().map(nest_data).collect(),
+        }
+    }
+
+    fn into_litematic<BlockState, Entity, BlockEntity>(
+        self,
+    ) -> Result<Litematic<BlockState, Entity, BlockEntity>>
+    where
+        BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+        Entity: mcdata::Entity + Serialize + DeserializeOwned,
+        BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+    {
+        let name = name(&self.metadata);
+        let volume = volume(self.size, &self.metadata)?;
+        let string = |key| match self.metadata.get(key) {
+            Some(Value::String(value)) => value.clone(),
+            _ => String::new(),
+        };
+        let mut litematic = Litematic::new(name.clone(), string("Description"), string("Author"));
+        litematic.metadata.minecraft_data_version = self.data_version;
+        if let Some(time) = self
+            .metadata
+            .get("Date")
+            .and_then(Value::as_i64)
+            .and_then(util::time_from_millis)
+        {
+            litematic.metadata.time_created = time;
+            litematic.metadata.time_modified = time;
+        }
+
+        let mut region = match self.data {
+            Some(data) => {
+                // build a palette with air at index 0 and map the Sponge ids onto it
+                let mut palette = vec![BlockState::air()];
+                let mut id_map = vec![0; self.palette.len()];
+                for (string, id) in &self.palette {
+                    // ids are stored as unsigned varints in the block data
+                    let index = *id as u32 as usize;
+                    if index >= id_map.len() {
+                        return Err(Error::PaletteIndexOutOfRange {
+                            region: name,
+                            index,
+                        });
+                    }
+                    let state: BlockState = util::convert(&parse_block_state(string))?;
+                    if state != BlockState::air() {
+                        id_map[index] = palette.len();
+                        palette.push(state);
+                    }
+                }
+                let blocks = read_varints(&data)
+                    .ok_or_else(|| Error::InvalidVarInt {
+                        region: name.clone(),
+                    })?
+                    .into_iter()
+                    .map(|id| {
+                        id_map
+                            .get(id)
+                            .copied()
+                            .ok_or_else(|| Error::PaletteIndexOutOfRange {
+                                region: name.clone(),
+                                index: id,
+                            })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if blocks.len() != volume {
+                    return Err(Error::BlockStatesLengthMismatch {
+                        region: name,
+                        expected: volume,
+                        actual: blocks.len(),
+                    });
+                }
+
+                Region::from_parts(name, self.offset, self.size, palette, blocks)
+            }
+            // without block data the size isn't backed by anything in the file
+            None if volume <= util::MAX_UNBACKED_VOLUME => {
+                Region::new(name, self.offset, self.size)
+            }
+            None => {
+                return Err(Error::RegionTooLarge {
+                    format: "sponge schematic",
+                    region: name,
+                })
+            }
+        };
+        for mut nbt in self.block_entities {
+            if let Some(Value::IntArray(pos)) = nbt.remove("Pos") {
+                if let [x, y, z] = pos[..] {
+                    nbt.insert("x".to_owned(), Value::Int(x));
+                    nbt.insert("y".to_owned(), Value::Int(y));
+                    nbt.insert("z".to_owned(), Value::Int(z));
+                }
+            }
+            rename(&mut nbt, "Id", "id");
+            region.block_entities.push(util::convert(&nbt)?);
+        }
+        for mut nbt in self.entities {
+            rename(&mut nbt, "Id", "id");
+            // litematica requires a UUID for every entity
+            nbt.entry("UUID".to_owned())
+                .or_insert_with(|| Value::IntArray(IntArray::new(vec![0; 4])));
+            region.entities.push(util::convert(&nbt)?);
+        }
+        litematic.regions.push(region);
+
+        Ok(litematic)
+    }
+
+    fn from_litematic<BlockState, Entity, BlockEntity>(
+        litematic: &Litematic<BlockState, Entity, BlockEntity>,
+    ) -> Result<Self>
+    where
+        BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+        Entity: mcdata::Entity + Serialize + DeserializeOwned,
+        BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+    {
+        let enclosing = litematic.enclosing_box();
+        let size = enclosing.size;
+        let too_large = || Error::RegionTooLarge {
+            format: "sponge schematic",
+            region: litematic.metadata.name.to_string(),
+        };
+        if [size.x, size.y, size.z]
+            .into_iter()
+            .any(|len| len > u16::MAX as i32)
+        {
+            return Err(too_large());
+        }
+        let mut palette = HashMap::from([("minecraft:air".to_owned(), 0)]);
+        let mut ids = vec![0; util::volume(size).ok_or_else(too_large)?];
+        let mut block_entities = vec![];
+        let mut entities = vec![];
+
+        for region in &litematic.regions {
+            let region_min = BlockPos::new(
+                region.min_global_x(),
+                region.min_global_y(),
+                region.min_global_z(),
+            ) - enclosing.origin;
+
+            let id_map = region
+                .block_palette()
+                .iter()
+                .map(|state| {
+                    let string = format_block_state(&util::convert::<_, GenericBlockState>(state)?);
+                    let next_id = palette.len() as i32;
+                    Ok(*palette.entry(string).or_insert(next_id))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            for ((pos, _), id) in region.blocks().zip(region.block_ids()) {
+                if id != 0 {
+                    let pos = pos + region_min;
+                    ids[(pos.x + pos.y * size.x * size.z + pos.z * size.x) as usize] = id_map[id];
+                }
+            }
+
+            for block_entity in &region.block_entities {
+                let Value::Compound(mut nbt) = fastnbt::to_value(block_entity)? else {
+                    continue;
+                };
+                let local = block_entity.position();
+                let pos = local + region_min;
+                for key in ["x", "y", "z"] {
+                    nbt.remove(key);
+                }
+                nbt.insert(
+                    "Pos".to_owned(),
+                    Value::IntArray(IntArray::new(vec![pos.x, pos.y, pos.z])),
+                );
+                match nbt.remove("id") {
+                    Some(id) => nbt.insert("Id".to_owned(), id),
+                    // schematics saved by some litematica versions are missing block entity ids
+                    None => region.get_block_opt(local).and_then(|state| {
+                        let state: GenericBlockState = util::convert(state).ok()?;
+                        nbt.insert("Id".to_owned(), Value::String(state.name.into_owned()))
+                    }),
+                };
+                block_entities.push(nbt);
+            }
+
+            // entity positions are relative to the region position
+            let shift = region.position - enclosing.origin;
+            for entity in &region.entities {
+                let Value::Compound(mut nbt) = fastnbt::to_value(entity)? else {
+                    continue;
+                };
+                if let Some(Value::List(pos)) = nbt.get_mut("Pos") {
+                    if let [Value::Double(x), Value::Double(y), Value::Double(z)] = &mut pos[..] {
+                        *x += shift.x as f64;
+                        *y += shift.y as f64;
+                        *z += shift.z as f64;
+                    }
+                }
+                rename(&mut nbt, "id", "Id");
+                entities.push(nbt);
+            }
+        }
+
+        let mut data = vec![];
+        for id in ids {
+            write_varint(&mut data, id);
+        }
+
+        let meta = &litematic.metadata;
+        let mut metadata = HashMap::from([
+            ("Name".to_owned(), Value::String(meta.name.to_string())),
+            ("Author".to_owned(), Value::String(meta.author.to_string())),
+            (
+                "Date".to_owned(),
+                Value::Long(util::time_to_millis(&meta.time_created)),
+            ),
+        ]);
+        if !meta.description.is_empty() {
+            metadata.insert(
+                "Description".to_owned(),
+                Value::String(meta.description.to_string()),
+            );
+        }
+
+        Ok(Self {
+            data_version: meta.minecraft_data_version,
+            metadata,
+            size,
+            offset: enclosing.origin,
+            palette,
+            data: Some(data),
+            block_entities,
+            entities,
+        })
+    }
+}
+
+fn size_from_raw(width: i16, height: i16, length: i16) -> BlockPos {
+    // dimensions are stored as unsigned shorts
+    BlockPos::new(
+        width as u16 as i32,
+        height as u16 as i32,
+        length as u16 as i32,
+    )
+}
+
+/// The name of a schematic, used as the name of its region.
+fn name(metadata: &Compound) -> String {
+    match metadata.get("Name") {
+        Some(Value::String(name)) => name.clone(),
+        _ => "Unnamed".to_owned(),
+    }
+}
+
+fn volume(size: BlockPos, metadata: &Compound) -> Result<usize> {
+    util::volume(size).ok_or_else(|| Error::RegionTooLarge {
+        format: "sponge schematic",
+        region: name(metadata),
+    })
+}
+
+fn offset_from_raw(offset: Option<IntArray>) -> BlockPos {
+    match offset.as_deref() {
+        Some([x, y, z]) => BlockPos::new(*x, *y, *z),
+        _ => BlockPos::ORIGIN,
+    }
+}
+
+fn rename(nbt: &mut Compound, from: &str, to: &str) {
+    if let Some(value) = nbt.remove(from) {
+        nbt.insert(to.to_owned(), value);
+    }
+}
+
+/// Move all contents of the `Data` compound used by version 3 to the top level.
+fn flatten_data(mut nbt: Compound) -> Compound {
+    if let Some(Value::Compound(data)) = nbt.remove("Data") {
+        for (key, value) in data {
+            nbt.entry(key).or_insert(value);
+        }
+    }
+    nbt
+}
+
+/// Move everything except `Id` and `Pos` into a `Data` compound as used by version 3.
+fn nest_data(nbt: Compound) -> Compound {
+    let (mut outer, data): (Compound, Compound) = nbt
+        .into_iter()
+        .partition(|(key, _)| key == "Id" || key == "Pos");
+    outer.insert("Data".to_owned(), Value::Compound(data));
+    outer
+}
+
+/// Read a sequence of varints.
+///
+/// Returns `None` if a varint is longer than the 5 bytes needed for any 32-bit value.
+fn read_varints(data: &[i8]) -> Option<Vec<usize>> {
+    let mut out = vec![];
+    let mut value = 0;
+    let mut shift = 0;
+    for byte in data {
+        if shift >= 35 {
+            return None;
+        }
+        value |= (*byte as u8 as usize & 0x7f) << shift;
+        if *byte >= 0 {
+            out.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+        }
+    }
+    Some(out)
+}
+
+fn write_varint(out: &mut Vec<i8>, mut value: i32) {
+    while value & !0x7f != 0 {
+        out.push((value & 0x7f | 0x80) as u8 as i8);
+        value = (value as u32 >> 7) as i32;
+    }
+    out.push(value as i8);
+}
@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use fastnbt::Value;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use mcdata::util::BlockPos;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{util, Error, Region, Result};
+
+type CowStr = std::borrow::Cow<'static, str>;
+type Compound = HashMap<String, Value>;
+
+#[derive(Serialize, Deserialize)]
+struct Structure<BlockState> {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+    size: Vec<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palette: Option<Vec<BlockState>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palettes: Option<Vec<Vec<BlockState>>>,
+    blocks: Vec<StructureBlock>,
+    #[serde(default)]
+    entities: Vec<StructureEntity>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StructureBlock {
+    pos: Vec<i32>,
+    state: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbt: Option<Compound>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StructureEntity {
+    pos: Vec<f64>,
+    #[serde(rename = "blockPos")]
+    block_pos: Vec<i32>,
+    nbt: Compound,
+}
+
+impl<BlockState, Entity, BlockEntity> Region<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Load a region with the given name from gzip compressed vanilla structure bytes, i.e. the
+    /// contents of a structure block `.nbt` file.
+    ///
+    /// The region is positioned at the origin. If the structure has multiple `palettes`, the
+    /// first one is used. Positions without a block, e.g. where structure voids were saved, are
+    /// filled with air.
+    pub fn from_structure(bytes: &[u8], name: impl Into<CowStr>) -> Result<Self> {
+        let mut gz = GzDecoder::new(bytes);
+        let mut extracted = vec![];
+        gz.read_to_end(&mut extracted)?;
+        let raw: Structure<BlockState> = fastnbt::from_bytes(&extracted)?;
+        let name = name.into();
+
+        let size = match raw.size[..] {
+            [x, y, z] => BlockPos::new(x, y, z),
+            _ => BlockPos::ORIGIN,
+        };
+        let structure_palette = raw
+            .palette
+            .or_else(|| {
+                raw.palettes
+                    .and_then(|palettes| palettes.into_iter().next())
+            })
+            .unwrap_or_default();
+
+        // build a palette with air at index 0 and map the structure ids onto it
+        let mut palette = vec![BlockState::air()];
+        let id_map = structure_palette
+            .into_iter()
+            .map(|state| {
+                if state == BlockState::air() {
+                    0
+                } else {
+                    palette.push(state);
+                    palette.len() - 1
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // structure voids aren't stored, but the blocks list has to account for most of the size
+        let volume = util::volume(size)
+            .filter(|volume| volume.saturating_sub(raw.blocks.len()) <= util::MAX_UNBACKED_VOLUME)
+            .ok_or_else(|| Error::RegionTooLarge {
+                format: "structure",
+                region: name.to_string(),
+            })?;
+        let mut blocks = vec![0; volume];
+        let mut block_entities = vec![];
+        for block in raw.blocks {
+            let [x, y, z] = block.pos[..] else {
+                continue;
+            };
+            let pos = BlockPos::new(x, y, z);
+            if !(0..size.x).contains(&x) || !(0..size.y).contains(&y) || !(0..size.z).contains(&z) {
+                continue;
+            }
+            blocks[(x + y * size.x * size.z + z * size.x) as usize] = *id_map
+                .get(block.state as usize)
+                .ok_or_else(|| Error::PaletteIndexOutOfRange {
+                    region: name.to_string(),
+                    index: block.state as usize,
+                })?;
+            if let Some(mut nbt) = block.nbt {
+                nbt.insert("x".to_owned(), Value::Int(pos.x));
+                nbt.insert("y".to_owned(), Value::Int(pos.y));
+                nbt.insert("z".to_owned(), Value::Int(pos.z));
+                block_entities.push(util::convert(&nbt)?);
+            }
+        }
+
+        let mut region = Region::from_parts(name, BlockPos::ORIGIN, size, palette, blocks);
+        region.block_entities = block_entities;
+        for entity in raw.entities {
+            let mut nbt = entity.nbt;
+            nbt.insert(
+                "Pos".to_owned(),
+                Value::List(entity.pos.into_iter().map(Value::Double).collect()),
+            );
+            // litematica requires a UUID for every entity
+            nbt.entry("UUID".to_owned())
+                .or_insert_with(|| Value::IntArray(fastnbt::IntArray::new(vec![0; 4])));
+            region.entities.push(util::convert(&nbt)?);
+        }
+
+        Ok(region)
+    }
+
+    /// Write this region to gzip compressed vanilla structure bytes, i.e. the contents of a
+    /// structure block `.nbt` file, using the given Minecraft
+    /// [data version](https://minecraft.wiki/w/Data_version).
+    ///
+    /// All blocks including air are saved, so placing the structure replaces the entire volume.
+    pub fn to_structure(&self, data_version: i32) -> Result<Vec<u8>> {
+        let mut block_entities = HashMap::new();
+        for block_entity in &self.block_entities {
+            let Value::Compound(mut nbt) = fastnbt::to_value(block_entity)? else {
+                continue;
+            };
+            for key in ["x", "y", "z"] {
+                nbt.remove(key);
+            }
+            let pos = block_entity.position();
+            block_entities.insert((pos.x, pos.y, pos.z), nbt);
+        }
+
+        // entity positions are relative to the region position and not to the minimum corner
+        let shift = self.position
+            - BlockPos::new(
+                self.min_global_x(),
+                self.min_global_y(),
+                self.min_global_z(),
+            );
+        let mut entities = vec![];
+        for entity in &self.entities {
+            let Value::Compound(nbt) = fastnbt::to_value(entity)? else {
+                continue;
+            };
+            let pos = match nbt.get("Pos") {
+                Some(Value::List(pos)) => pos
+                    .iter()
+                    .filter_map(Value::as_f64)
+                    .zip([shift.x, shift.y, shift.z])
+                    .map(|(coord, shift)| coord + shift as f64)
+                    .collect(),
+                _ => vec![0.0; 3],
+            };
+            entities.push(StructureEntity {
+                block_pos: pos.iter().map(|coord| coord.floor() as i32).collect(),
+                pos,
+                nbt,
+            });
+        }
+
+        let raw = Structure {
+            data_version,
+            size: vec![self.size.x.abs(), self.size.y.abs(), self.size.z.abs()],
+            palette: Some(self.block_palette().to_vec()),
+            palettes: None,
+            blocks: self
+                .blocks()
+                .zip(self.block_ids())
+                .map(|((pos, _), id)| StructureBlock {
+                    pos: vec![pos.x, pos.y, pos.z],
+                    state: id as i32,
+                    nbt: block_entities.remove(&(pos.x, pos.y, pos.z)),
+                })
+                .collect(),
+            entities,
+        };
+
+        let mut buf = vec![];
+        let mut gz = GzEncoder::new(&mut buf, Compression::default());
+        gz.write_all(&fastnbt::to_bytes(&raw)?)?;
+        gz.finish()?;
+        Ok(buf)
+    }
+}
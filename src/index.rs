@@ -0,0 +1,419 @@
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::OnceLock,
+};
+
+use mcdata::util::BlockPos;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Litematic, Region};
+
+/// The number of bits of each coordinate that are ignored for the chunk of a position.
+const CHUNK_BITS: u32 = 4;
+
+/// A spatial index from global positions to the regions of a schematic containing them.
+///
+/// Instead of checking every region, this index only checks the regions near the given position,
+/// which makes many lookups in schematics with lots of regions a lot faster. Just like with
+/// [`Litematic::get_block`], later regions in [`Litematic::regions`] take priority where regions
+/// overlap.
+///
+/// [`Litematic::get_block`] and [`Litematic::set_block`] already use an index kept by
+/// [`Regions`], so building one yourself is only needed for [`Self::region_at`].
+///
+/// The index doesn't keep a reference to the schematic, so it has to be built again after
+/// regions are added, removed, moved, or resized. Changing blocks doesn't invalidate the index.
+#[derive(Debug, Clone, Default)]
+pub struct RegionIndex {
+    /// The indices of all regions overlapping each chunk, from highest to lowest priority.
+    chunks: HashMap<(i32, i32, i32), Vec<usize>>,
+}
+
+impl RegionIndex {
+    /// Build an index of the regions of the given schematic.
+    pub fn new<BlockState, Entity, BlockEntity>(
+        schem: &Litematic<BlockState, Entity, BlockEntity>,
+    ) -> Self
+    where
+        BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+        Entity: mcdata::Entity + Serialize + DeserializeOwned,
+        BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+    {
+        Self::from_regions(&schem.regions)
+    }
+
+    /// Build an index of the given regions.
+    fn from_regions<BlockState, Entity, BlockEntity>(
+        regions: &[Region<BlockState, Entity, BlockEntity>],
+    ) -> Self
+    where
+        BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+        Entity: mcdata::Entity + Serialize + DeserializeOwned,
+        BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+    {
+        let mut chunks: HashMap<_, Vec<usize>> = HashMap::new();
+        for (index, region) in regions.iter().enumerate().rev() {
+            if region.size.volume() == 0 {
+                continue;
+            }
+            let (min, max) = (
+                chunk(BlockPos::new(
+                    region.min_global_x(),
+                    region.min_global_y(),
+                    region.min_global_z(),
+                )),
+                chunk(BlockPos::new(
+                    region.max_global_x(),
+                    region.max_global_y(),
+                    region.max_global_z(),
+                )),
+            );
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    for x in min.0..=max.0 {
+                        chunks.entry((x, y, z)).or_default().push(index);
+                    }
+                }
+            }
+        }
+        Self { chunks }
+    }
+
+    /// Find the index of the region with the highest priority containing the given global
+    /// position.
+    ///
+    /// Returns `None` if no region contains the position.
+    pub fn region_at<BlockState, Entity, BlockEntity>(
+        &self,
+        schem: &Litematic<BlockState, Entity, BlockEntity>,
+        pos: BlockPos,
+    ) -> Option<usize>
+    where
+        BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+        Entity: mcdata::Entity + Serialize + DeserializeOwned,
+        BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+    {
+        self.region_in(&schem.regions, pos)
+    }
+
+    /// Find the index of the region with the highest priority among the given regions containing
+    /// the given global position.
+    fn region_in<BlockState, Entity, BlockEntity>(
+        &self,
+        regions: &[Region<BlockState, Entity, BlockEntity>],
+        pos: BlockPos,
+    ) -> Option<usize>
+    where
+        BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+        Entity: mcdata::Entity + Serialize + DeserializeOwned,
+        BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+    {
+        self.chunks.get(&chunk(pos))?.iter().copied().find(|index| {
+            regions
+                .get(*index)
+                .is_some_and(|region| contains(region, pos))
+        })
+    }
+
+    /// Get the block state at the given global position.
+    ///
+    /// Returns `None` if no region contains the position. See [`Litematic::get_block`].
+    pub fn get_block<'schem, BlockState, Entity, BlockEntity>(
+        &self,
+        schem: &'schem Litematic<BlockState, Entity, BlockEntity>,
+        pos: BlockPos,
+    ) -> Option<&'schem BlockState>
+    where
+        BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+        Entity: mcdata::Entity + Serialize + DeserializeOwned,
+        BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+    {
+        self.region_at(schem, pos)
+            .map(|index| schem.regions[index].get_block_global(pos))
+    }
+
+    /// Set the block state at the given global position.
+    ///
+    /// Returns `false` and does nothing if no region contains the position. See
+    /// [`Litematic::set_block`].
+    pub fn set_block<BlockState, Entity, BlockEntity>(
+        &self,
+        schem: &mut Litematic<BlockState, Entity, BlockEntity>,
+        pos: BlockPos,
+        block: BlockState,
+    ) -> bool
+    where
+        BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+        Entity: mcdata::Entity + Serialize + DeserializeOwned,
+        BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+    {
+        self.region_at(schem, pos)
+            .map(|index| schem.regions[index].set_block_global(pos, block))
+            .is_some()
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> Litematic<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Get the block state at the given global position.
+    ///
+    /// Where regions overlap, the block of the last region in [`Self::regions`] containing the
+    /// position is returned. Returns `None` if no region contains the position.
+    ///
+    /// The regions are looked up in the index kept by [`Regions`], which is built on the first
+    /// lookup after the regions were changed.
+    pub fn get_block(&self, pos: BlockPos) -> Option<&BlockState> {
+        self.regions
+            .index()
+            .region_in(&self.regions, pos)
+            .map(|index| self.regions[index].get_block_global(pos))
+    }
+
+    /// Set the block state at the given global position.
+    ///
+    /// Where regions overlap, only the block of the last region in [`Self::regions`] containing
+    /// the position is changed, so that [`Self::get_block`] returns the new block. Returns `false`
+    /// and does nothing if no region contains the position.
+    ///
+    /// Like [`Self::get_block`], this uses the index kept by [`Regions`]. Changing blocks doesn't
+    /// invalidate it.
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockState) -> bool {
+        let regions = &mut self.regions;
+        // going through the inner list directly, so the index isn't dropped
+        regions
+            .index()
+            .region_in(&regions.regions, pos)
+            .map(|index| regions.regions[index].set_block_global(pos, block))
+            .is_some()
+    }
+
+    /// Create an iterator over all blocks of all regions.
+    ///
+    /// Each item will be a tuple of the global coordinates of the block and the block state
+    /// itself. The blocks are returned region by region, and every position is only returned
+    /// once, with the same block [`Self::get_block`] returns for it.
+    pub fn blocks(&self) -> impl Iterator<Item = (BlockPos, &BlockState)> + '_ {
+        self.regions
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, region)| {
+                // the regions taking priority over this one at some positions
+                let above = self.regions[index + 1..]
+                    .iter()
+                    .filter(|other| other.size.volume() != 0 && overlaps(region, other))
+                    .collect::<Vec<_>>();
+                region
+                    .blocks()
+                    .map(|(pos, block)| (region.pos_to_global(pos), block))
+                    .filter(move |(pos, _)| !above.iter().any(|other| contains(other, *pos)))
+            })
+    }
+}
+
+/// The list of [`Region`]s of a [`Litematic`], together with a [`RegionIndex`] of them.
+///
+/// This dereferences to a [`Vec`] of the regions, so it can be used just like one. The index is
+/// built on the first lookup with [`Litematic::get_block`] or [`Litematic::set_block`], and
+/// dropped whenever the regions are borrowed mutably, since any region could have been added,
+/// removed, moved, or resized then.
+///
+/// Keeping the index next to the regions is why [`Litematic::regions`] is no longer a [`Vec`]
+/// itself. A [`Vec`] can be turned into [`Regions`] with [`From`] or by collecting an iterator, and
+/// back with [`Self::into_vec`].
+#[derive(Debug, Clone)]
+pub struct Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// The regions themselves.
+    regions: Vec<Region<BlockState, Entity, BlockEntity>>,
+
+    /// The index of the regions, if it was built since they were last borrowed mutably.
+    index: OnceLock<RegionIndex>,
+}
+
+impl<BlockState, Entity, BlockEntity> Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// The index of the regions, built if the regions changed since it was last used.
+    fn index(&self) -> &RegionIndex {
+        self.index
+            .get_or_init(|| RegionIndex::from_regions(&self.regions))
+    }
+
+    /// Unwrap the list of regions.
+    pub fn into_vec(self) -> Vec<Region<BlockState, Entity, BlockEntity>> {
+        self.regions
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> Default for Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    fn default() -> Self {
+        Vec::new().into()
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> From<Vec<Region<BlockState, Entity, BlockEntity>>>
+    for Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    fn from(regions: Vec<Region<BlockState, Entity, BlockEntity>>) -> Self {
+        Self {
+            regions,
+            index: OnceLock::new(),
+        }
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> FromIterator<Region<BlockState, Entity, BlockEntity>>
+    for Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    fn from_iter<I: IntoIterator<Item = Region<BlockState, Entity, BlockEntity>>>(iter: I) -> Self {
+        iter.into_iter().collect::<Vec<_>>().into()
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> Deref for Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    type Target = Vec<Region<BlockState, Entity, BlockEntity>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.regions
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> DerefMut for Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.index.take();
+        &mut self.regions
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> PartialEq for Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned + PartialEq,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned + PartialEq,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.regions == other.regions
+    }
+}
+
+impl<BlockState, Entity, BlockEntity> IntoIterator for Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    type Item = Region<BlockState, Entity, BlockEntity>;
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.regions.into_iter()
+    }
+}
+
+impl<'a, BlockState, Entity, BlockEntity> IntoIterator
+    for &'a Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    type Item = &'a Region<BlockState, Entity, BlockEntity>;
+    type IntoIter = std::slice::Iter<'a, Region<BlockState, Entity, BlockEntity>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.regions.iter()
+    }
+}
+
+impl<'a, BlockState, Entity, BlockEntity> IntoIterator
+    for &'a mut Regions<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    type Item = &'a mut Region<BlockState, Entity, BlockEntity>;
+    type IntoIter = std::slice::IterMut<'a, Region<BlockState, Entity, BlockEntity>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// The chunk containing the given global position.
+fn chunk(pos: BlockPos) -> (i32, i32, i32) {
+    (
+        pos.x >> CHUNK_BITS,
+        pos.y >> CHUNK_BITS,
+        pos.z >> CHUNK_BITS,
+    )
+}
+
+/// Whether a region contains the given global position.
+///
+/// Unlike [`Region::is_in_global_bounds`], this is `false` for all positions if the region has a
+/// size of zero.
+fn contains<BlockState, Entity, BlockEntity>(
+    region: &Region<BlockState, Entity, BlockEntity>,
+    pos: BlockPos,
+) -> bool
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    region.size.volume() != 0 && region.is_in_global_bounds(pos)
+}
+
+/// Whether the bounds of two regions overlap.
+fn overlaps<BlockState, Entity, BlockEntity>(
+    a: &Region<BlockState, Entity, BlockEntity>,
+    b: &Region<BlockState, Entity, BlockEntity>,
+) -> bool
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    a.min_global_x() <= b.max_global_x()
+        && b.min_global_x() <= a.max_global_x()
+        && a.min_global_y() <= b.max_global_y()
+        && b.min_global_y() <= a.max_global_y()
+        && a.min_global_z() <= b.max_global_z()
+        && b.min_global_z() <= a.max_global_z()
+}
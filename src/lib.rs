@@ -6,13 +6,34 @@
 #![cfg_attr(all(doc, CHANNEL_NIGHTLY), feature(doc_auto_cfg))]
 #![warn(rust_2018_idioms, missing_debug_implementations, missing_docs)]
 
+mod any;
+mod bit_array;
+mod clipboard;
+mod convert;
+mod downgrade;
 mod error;
+mod format;
+mod index;
 mod litematic;
+mod pattern;
 mod region;
 mod schema;
+mod transform;
+mod upgrade;
 pub(crate) mod util;
+mod validate;
 
+pub use any::AnyLitematic;
+pub use clipboard::{PasteMode, PasteOptions, ResizeAnchor};
+pub use convert::{ConversionReport, Fallback};
+pub use downgrade::{DowngradeOptions, DowngradeReport, LossyChange, RegionDowngradeReport};
 pub use error::*;
+pub use format::*;
+pub use index::{RegionIndex, Regions};
 pub use litematic::*;
+pub use pattern::{Mask, Pattern};
 pub use region::*;
 pub use schema::{PendingBlockTick, PendingFluidTick};
+pub use transform::{Mirror, Rotation};
+pub use upgrade::{Unmigrated, UpgradeReport};
+pub use validate::{ValidationIssue, ENTITY_MARGIN};
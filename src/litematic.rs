@@ -1,12 +1,14 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
-    io::{Read, Write},
+    io::{BufReader, BufWriter, Read, Write},
+    ops::RangeInclusive,
     path::Path,
 };
 
-use fastnbt::IntArray;
+use fastnbt::{IntArray, Value};
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use indexmap::IndexMap;
 use mcdata::{
     util::{BlockPos, Cuboid},
     GenericBlockEntity, GenericBlockState, GenericEntity,
@@ -14,7 +16,7 @@ use mcdata::{
 use serde::{de::DeserializeOwned, Serialize};
 
 #[cfg(feature = "chrono")]
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 
 #[cfg(feature = "image")]
 use image::{
@@ -22,15 +24,31 @@ use image::{
     DynamicImage, RgbaImage,
 };
 
-use crate::{error::Result, schema, util};
+use crate::{
+    error::{Error, Result},
+    index::Regions,
+    schema, util,
+};
 
-use super::Region;
+use super::{Mask, Mirror, PaletteOrder, Pattern, Region, Rotation};
 
-// TODO: require version 6 when reading?
-// TODO: support multiple versions?
 const SCHEMATIC_VERSION: i32 = 6;
 const SCHEMATIC_VERSION_SUB: i32 = 1;
 
+/// The litematica format versions which can be read and written.
+///
+/// Version 4 is the last version used before Minecraft 1.13 and neither saves the
+/// [Minecraft data version](LitematicMetadata::minecraft_data_version) nor pending fluid ticks.
+/// The later versions share the same layout.
+pub const LITEMATIC_VERSIONS: RangeInclusive<i32> = 4..=7;
+
+/// The first litematica format version which saves the Minecraft data version and pending fluid
+/// ticks.
+const DATA_VERSION_SCHEMATIC_VERSION: i32 = 5;
+
+/// The Minecraft data version of 1.12.2, which is assumed for schematics without a data version.
+const LEGACY_DATA_VERSION: i32 = 1343;
+
 type CowStr = std::borrow::Cow<'static, str>;
 
 /// Metadata for a litematica schematic.
@@ -40,7 +58,7 @@ type CowStr = std::borrow::Cow<'static, str>;
 /// metadata that has already been read, using for example [`Litematic::read_file_with_metadata`].
 /// This might be useful if you want to choose the generic types based on e.g. the
 /// [Minecraft data version](Self::minecraft_data_version).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LitematicMetadata {
     /// The name of this schematic.
     pub name: CowStr,
@@ -52,6 +70,9 @@ pub struct LitematicMetadata {
     pub author: CowStr,
 
     /// The litematica format version this schematic was created with.
+    ///
+    /// This is also the version the schematic is written as, see [`Litematic::set_version`] for
+    /// changing it.
     pub version: i32,
 
     /// An optional litematica format subversion.
@@ -86,6 +107,41 @@ pub struct LitematicMetadata {
     /// The raw ARGB preview image data.
     #[cfg(not(feature = "image"))]
     preview_image: Option<Vec<i32>>,
+
+    /// The summary values stored in the file this schematic was read from, or `None` if the
+    /// schematic wasn't read from a file.
+    ///
+    /// These are always recomputed when writing a [`Litematic`], and only written as they are by
+    /// [`LitematicMetadata::write_to`]. See [`Litematic::validate`] for checking whether they match
+    /// the actual schematic contents.
+    pub stored_totals: Option<MetadataTotals>,
+
+    /// Unknown NBT tags in the `Metadata` compound, e.g. from newer litematica versions or other
+    /// tools.
+    ///
+    /// These are written back unchanged and in the order they were read in.
+    pub extra: IndexMap<String, Value>,
+
+    /// Unknown NBT tags at the root of the schematic.
+    ///
+    /// These are written back unchanged and in the order they were read in.
+    pub root_extra: IndexMap<String, Value>,
+}
+
+/// The summary values stored in the metadata of a litematica schematic file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataTotals {
+    /// The number of regions, see [`Litematic::regions`].
+    pub region_count: i32,
+
+    /// The total volume of all regions, see [`Litematic::total_volume`].
+    pub total_volume: i32,
+
+    /// The number of non-air blocks, see [`Litematic::total_blocks`].
+    pub total_blocks: i64,
+
+    /// The size of the box enclosing all regions, see [`Litematic::enclosing_size`].
+    pub enclosing_size: BlockPos,
 }
 
 /// A litematica schematic.
@@ -123,10 +179,20 @@ pub struct Litematic<
     BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
 {
     /// The list of [`Region`]s in this schematic.
-    pub regions: Vec<Region<BlockState, Entity, BlockEntity>>,
+    ///
+    /// This used to be a plain [`Vec`], which [`Regions`] dereferences to, so reading and changing
+    /// the regions works just like before. Assigning a [`Vec`] now needs `.into()` though, and
+    /// taking the regions by value needs [`Regions::into_vec`].
+    pub regions: Regions<BlockState, Entity, BlockEntity>,
 
     /// The metadata of this schematic.
     pub metadata: LitematicMetadata,
+
+    /// Whether and how to [compact the palettes](Region::compact_palette) of all regions when
+    /// writing this schematic.
+    ///
+    /// The regions themselves are left unchanged. Defaults to `None`.
+    pub compact_palettes: Option<PaletteOrder>,
 }
 
 impl<BlockState, Entity, BlockEntity> Litematic<BlockState, Entity, BlockEntity>
@@ -143,7 +209,7 @@ where
     ) -> Self {
         let now = util::current_time();
         Self {
-            regions: vec![],
+            regions: Regions::default(),
             metadata: LitematicMetadata {
                 name: name.into(),
                 description: description.into(),
@@ -154,86 +220,83 @@ where
                 time_created: now,
                 time_modified: now,
                 preview_image: None,
+                stored_totals: None,
+                extra: IndexMap::new(),
+                root_extra: IndexMap::new(),
             },
+            compact_palettes: None,
         }
     }
 
-    /// Construct a [`Litematic`] from a [raw NBT litematic regions](schema::LitematicRegions) and
-    /// existing metadata.
+    /// Construct a [`Litematic`] from [raw NBT regions](schema::Regions) and existing metadata.
+    ///
+    /// Returns an error if the format version of the metadata is not supported.
     pub(crate) fn from_raw(
-        raw_regions: schema::LitematicRegions<BlockState, Entity, BlockEntity>,
+        raw_regions: schema::Regions<BlockState, Entity, BlockEntity>,
         metadata: LitematicMetadata,
-    ) -> Self {
-        Self {
+    ) -> Result<Self> {
+        check_version(metadata.version)?;
+        Ok(Self {
             regions: raw_regions
-                .regions
                 .into_iter()
                 .map(|(name, region)| Region::from_raw(region, name))
-                .collect(),
+                .collect::<Result<_>>()?,
             metadata,
-        }
+            compact_palettes: None,
+        })
     }
 
-    /// Create a new [raw NBT litematic](schema::Litematic) from this [`Litematic`].
-    pub(crate) fn to_raw(&self) -> schema::Litematic<BlockState, Entity, BlockEntity> {
-        schema::Litematic {
-            regions: {
-                let mut map = HashMap::new();
-                for region in self.regions.iter() {
-                    map.insert(region.name.clone().into_owned(), region.to_raw());
+    /// Create a new [raw NBT litematic](schema::Litematic) from this [`Litematic`] in the layout of
+    /// its [format version](LitematicMetadata::version).
+    ///
+    /// Returns an error if the format version is not supported, multiple regions have the same
+    /// name, or a region has pending fluid ticks which the format version can't store.
+    pub(crate) fn to_raw(
+        &self,
+    ) -> Result<schema::Litematic<schema::Regions<BlockState, Entity, BlockEntity>>> {
+        check_version(self.metadata.version)?;
+        let has_data_version = self.metadata.version >= DATA_VERSION_SCHEMATIC_VERSION;
+
+        let mut regions = IndexMap::with_capacity(self.regions.len());
+        for region in self.regions.iter() {
+            let name = region.name.clone().into_owned();
+            if regions.contains_key(&name) {
+                return Err(Error::DuplicateRegionName { name });
+            }
+            let mut raw = region.to_raw(self.compact_palettes);
+            if !has_data_version {
+                if !region.pending_fluid_ticks.is_empty() {
+                    return Err(Error::UnsupportedByVersion {
+                        format: "litematica",
+                        version: self.metadata.version,
+                        region: name,
+                        data: "pending fluid ticks",
+                    });
                 }
-                schema::LitematicRegions { regions: map }
-            },
-            metadata: schema::LitematicMetadata {
-                version: self.metadata.version,
-                sub_version: self.metadata.sub_version,
-                minecraft_data_version: self.metadata.minecraft_data_version,
-                metadata: schema::Metadata {
-                    name: self.metadata.name.clone().into_owned(),
-                    description: self.metadata.description.clone().into_owned(),
-                    author: self.metadata.author.clone().into_owned(),
-                    region_count: self.regions.len() as i32,
-                    total_blocks: self.total_blocks(),
-                    total_volume: self.total_volume(),
-                    enclosing_size: self.enclosing_size(),
-
-                    #[cfg(feature = "chrono")]
-                    time_created: self.metadata.time_created.timestamp_millis(),
-                    #[cfg(not(feature = "chrono"))]
-                    time_created: self.metadata.time_created,
-                    #[cfg(feature = "chrono")]
-                    time_modified: util::current_time().timestamp_millis(),
-                    #[cfg(not(feature = "chrono"))]
-                    time_modified: util::current_time(),
-
-                    #[cfg(feature = "image")]
-                    preview_image_data: self.metadata.preview_image.as_ref().map(|img| {
-                        IntArray::new(
-                            img.to_rgba8()
-                                .into_vec()
-                                .chunks_exact(4)
-                                .map(|px| {
-                                    let mut px = [px[0], px[1], px[2], px[3]];
-                                    // convert RGBA to ARGB
-                                    px.rotate_right(1);
-                                    i32::from_be_bytes(px)
-                                })
-                                .collect(),
-                        )
-                    }),
-                    #[cfg(not(feature = "image"))]
-                    preview_image_data: self.metadata.preview_image.clone().map(IntArray::new),
-                },
-            },
+                raw.pending_fluid_ticks = None;
+            }
+            regions.insert(name, raw);
         }
+        self.metadata.to_raw(
+            regions,
+            MetadataTotals {
+                region_count: self.regions.len() as i32,
+                total_blocks: self.total_blocks(),
+                total_volume: self.total_volume(),
+                enclosing_size: self.enclosing_size(),
+            },
+        )
     }
 
     /// Load a schematic from uncompressed bytes.
+    ///
+    /// The metadata is read first, so the format version can be checked before deserializing the
+    /// regions.
     pub fn from_uncompressed_bytes(bytes: &[u8]) -> Result<Self> {
-        Ok(Self::from_raw(
-            fastnbt::from_bytes(bytes)?,
+        Self::from_uncompressed_bytes_with_metadata(
+            bytes,
             LitematicMetadata::from_uncompressed_bytes(bytes)?,
-        ))
+        )
     }
 
     /// Load a schematic from uncompressed bytes and use the exising metadata.
@@ -241,45 +304,81 @@ where
         bytes: &[u8],
         metadata: LitematicMetadata,
     ) -> Result<Self> {
-        Ok(Self::from_raw(fastnbt::from_bytes(bytes)?, metadata))
+        check_version(metadata.version)?;
+        let raw: schema::LitematicRegions<_> = fastnbt::from_bytes(bytes)?;
+        Self::from_raw(raw.regions, metadata)
     }
 
     /// Write this schematic to uncompressed bytes.
     pub fn to_uncompressed_bytes(&self) -> Result<Vec<u8>> {
-        Ok(fastnbt::to_bytes(&self.to_raw())?)
+        Ok(fastnbt::to_bytes(&self.to_raw()?)?)
     }
 
     /// Load a schematic from gzip compressed bytes.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        let mut gz = GzDecoder::new(bytes);
-        let mut extracted = vec![];
-        gz.read_to_end(&mut extracted)?;
-        Self::from_uncompressed_bytes(&extracted)
+        Self::from_reader(bytes)
     }
 
     /// Load a schematic from gzip compressed bytes and use the existing metadata.
     pub fn from_bytes_with_metadata(bytes: &[u8], metadata: LitematicMetadata) -> Result<Self> {
-        let mut gz = GzDecoder::new(bytes);
-        let mut extracted = vec![];
-        gz.read_to_end(&mut extracted)?;
-        Self::from_uncompressed_bytes_with_metadata(&extracted, metadata)
+        Self::from_reader_with_metadata(bytes, metadata)
+    }
+
+    /// Load a schematic from a reader of gzip compressed bytes.
+    ///
+    /// The data is decompressed while reading, so the schematic never has to be fully buffered in
+    /// memory.
+    ///
+    /// If the regions are stored before the format version, they are deserialized before the
+    /// version can be checked. Schematics of unsupported versions then fail with whatever error
+    /// their layout causes, and only with [`Error::UnsupportedVersion`] if the regions can be
+    /// deserialized. Use [`Litematic::from_reader_with_metadata`] to always check the version
+    /// first.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        let raw: schema::Litematic<schema::StreamedRegions<_>> =
+            fastnbt::from_reader(GzDecoder::new(reader))?;
+        let (regions, metadata) = raw.split();
+        let metadata = LitematicMetadata::from_raw(metadata)?;
+        check_version(metadata.version)?;
+        match regions {
+            schema::StreamedRegions::Parsed(regions) => Self::from_raw(regions, metadata),
+            schema::StreamedRegions::Skipped { version } => Err(Error::UnsupportedVersion {
+                format: "litematica",
+                version,
+            }),
+        }
+    }
+
+    /// Load a schematic from a reader of gzip compressed bytes and use the existing metadata.
+    pub fn from_reader_with_metadata(
+        reader: impl Read,
+        metadata: LitematicMetadata,
+    ) -> Result<Self> {
+        check_version(metadata.version)?;
+        let raw: schema::LitematicRegions<_> = fastnbt::from_reader(GzDecoder::new(reader))?;
+        Self::from_raw(raw.regions, metadata)
     }
 
     /// Write this schematic to gzip compressed bytes.
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut buf = vec![];
-        let mut gz = GzEncoder::new(&mut buf, Compression::default());
-        gz.write_all(&self.to_uncompressed_bytes()?)?;
-        gz.finish()?;
+        self.write_to(&mut buf)?;
         Ok(buf)
     }
 
+    /// Write this schematic to a writer as gzip compressed bytes.
+    ///
+    /// The data is compressed while writing.
+    pub fn write_to(&self, writer: impl Write) -> Result<()> {
+        let mut gz = GzEncoder::new(writer, Compression::default());
+        fastnbt::to_writer(&mut gz, &self.to_raw()?)?;
+        gz.finish()?.flush()?;
+        Ok(())
+    }
+
     /// Load a schematic from a file.
     pub fn read_file(filename: impl AsRef<Path>) -> Result<Self> {
-        let mut file = File::open(filename)?;
-        let mut bytes = vec![];
-        file.read_to_end(&mut bytes)?;
-        Litematic::from_bytes(&bytes)
+        Self::from_reader(BufReader::new(File::open(filename)?))
     }
 
     /// Load a schematic from a file and use the existing metadata.
@@ -287,19 +386,153 @@ where
         filename: impl AsRef<Path>,
         metadata: LitematicMetadata,
     ) -> Result<Self> {
-        let mut file = File::open(filename)?;
-        let mut bytes = vec![];
-        file.read_to_end(&mut bytes)?;
-        Litematic::from_bytes_with_metadata(&bytes, metadata)
+        Self::from_reader_with_metadata(BufReader::new(File::open(filename)?), metadata)
     }
 
     /// Write this schematic to a file.
     pub fn write_file(&self, filename: impl AsRef<Path>) -> Result<()> {
-        let mut file = File::create(filename)?;
-        file.write_all(&self.to_bytes()?)?;
+        self.write_to(BufWriter::new(File::create(filename)?))
+    }
+
+    /// Set the litematica format version and subversion this schematic is written as.
+    ///
+    /// Returns an error if the version is not in [`LITEMATIC_VERSIONS`]. Note that the blocks and
+    /// entities are not converted, so they should match what litematica expects for the
+    /// [Minecraft data version](LitematicMetadata::minecraft_data_version) of the schematic.
+    /// Version 4 can't store pending fluid ticks, so writing a schematic as version 4 fails with
+    /// [`Error::UnsupportedByVersion`] if any region has some.
+    pub fn set_version(&mut self, version: i32, sub_version: Option<i32>) -> Result<()> {
+        check_version(version)?;
+        self.metadata.version = version;
+        self.metadata.sub_version = sub_version;
         Ok(())
     }
 
+    /// Rename regions with the same name as an earlier region by appending a number, e.g.
+    /// `Region (2)`.
+    ///
+    /// Schematics with duplicate region names can't be written, as litematica identifies regions
+    /// by their name.
+    pub fn make_region_names_unique(&mut self) {
+        let mut names = HashSet::new();
+        for region in self.regions.iter_mut() {
+            if names.contains(&region.name) {
+                let name = (2..)
+                    .map(|n| CowStr::from(format!("{} ({n})", region.name)))
+                    .find(|name| !names.contains(name))
+                    .expect("there are infinitely many suffixes");
+                region.name = name;
+            }
+            names.insert(region.name.clone());
+        }
+    }
+
+    /// Rotate this schematic around its origin.
+    ///
+    /// Every region is rotated using [`Region::rotate`].
+    pub fn rotate(&mut self, rotation: Rotation) {
+        for region in self.regions.iter_mut() {
+            region.rotate(rotation);
+        }
+    }
+
+    /// Mirror this schematic along an axis through its origin.
+    ///
+    /// Every region is mirrored using [`Region::mirror`].
+    pub fn mirror(&mut self, mirror: Mirror) {
+        for region in self.regions.iter_mut() {
+            region.mirror(mirror);
+        }
+    }
+
+    /// Set every block inside the given global area to `block`, in all regions.
+    ///
+    /// See [`Region::fill`]. Returns the number of blocks inside the area summed over all regions.
+    pub fn fill(&mut self, area: Cuboid, block: BlockState) -> usize {
+        self.regions
+            .iter_mut()
+            .map(|region| region.fill(region.area_from_global(area), block.clone()))
+            .sum()
+    }
+
+    /// Replace every occurrence of `from` in all regions with `to`.
+    ///
+    /// See [`Region::replace`]. Returns the number of replaced blocks.
+    pub fn replace(&mut self, from: &BlockState, to: BlockState) -> usize {
+        self.regions
+            .iter_mut()
+            .map(|region| region.replace(from, to.clone()))
+            .sum()
+    }
+
+    /// Set every block inside the given global area that matches `predicate` to `block`, in all
+    /// regions.
+    ///
+    /// See [`Region::replace_in`]. Returns the number of replaced blocks.
+    pub fn replace_in(
+        &mut self,
+        area: Cuboid,
+        mut predicate: impl FnMut(&BlockState) -> bool,
+        block: BlockState,
+    ) -> usize {
+        self.regions
+            .iter_mut()
+            .map(|region| {
+                region.replace_in(region.area_from_global(area), &mut predicate, block.clone())
+            })
+            .sum()
+    }
+
+    /// Set every block inside the given global area to a block chosen by `pattern`, in all
+    /// regions.
+    ///
+    /// See [`Region::fill_pattern`]. Returns the number of blocks inside the area summed over all
+    /// regions.
+    pub fn fill_pattern(&mut self, area: Cuboid, pattern: &mut Pattern) -> Result<usize> {
+        self.regions
+            .iter_mut()
+            .map(|region| region.fill_pattern(region.area_from_global(area), pattern))
+            .sum()
+    }
+
+    /// Set every block inside the given global area that matches `mask` to a block chosen by
+    /// `pattern`, in all regions.
+    ///
+    /// See [`Region::replace_pattern`]. Returns the number of replaced blocks.
+    pub fn replace_pattern(
+        &mut self,
+        area: Cuboid,
+        mask: &Mask,
+        pattern: &mut Pattern,
+    ) -> Result<usize> {
+        self.regions
+            .iter_mut()
+            .map(|region| region.replace_pattern(region.area_from_global(area), mask, pattern))
+            .sum()
+    }
+
+    /// Set every block inside the given global area to air, in all regions.
+    ///
+    /// See [`Region::clear`]. Returns the number of blocks inside the area summed over all
+    /// regions.
+    pub fn clear(&mut self, area: Cuboid) -> usize {
+        self.regions
+            .iter_mut()
+            .map(|region| region.clear(region.area_from_global(area)))
+            .sum()
+    }
+
+    /// Shrink every region to the smallest box containing all of its non-air blocks and remove
+    /// the regions without any.
+    ///
+    /// See [`Region::trim`].
+    pub fn trim(&mut self) {
+        for region in self.regions.iter_mut() {
+            region.trim();
+        }
+        self.regions.retain(|region| region.size.volume() != 0);
+    }
+
     /// The total number of blocks all regions combined contain.
     pub fn total_blocks(&self) -> i64 {
         self.regions.iter().map(|r| r.total_blocks() as i64).sum()
@@ -318,7 +551,11 @@ where
 
     /// Calculate the box enclosing all regions.
     pub fn enclosing_box(&self) -> Cuboid {
-        let mut bounds = [0; 6];
+        let mut bounds = if self.regions.is_empty() {
+            [0; 6]
+        } else {
+            [i32::MAX, i32::MIN, i32::MAX, i32::MIN, i32::MAX, i32::MIN]
+        };
         for region in self.regions.iter() {
             bounds[0] = bounds[0].min(region.min_global_x());
             bounds[1] = bounds[1].max(region.max_global_x());
@@ -344,71 +581,164 @@ where
 
 impl LitematicMetadata {
     /// Construct [`LitematicMetadata`] from [raw NBT litematic metadata](schema::LitematicMetadata).
-    pub(crate) fn from_raw(raw: schema::LitematicMetadata) -> Self {
-        Self {
+    ///
+    /// Returns an error if the format version is not supported, a timestamp can't be represented,
+    /// or the preview image has the wrong size.
+    pub(crate) fn from_raw(raw: schema::LitematicMetadata) -> Result<Self> {
+        check_version(raw.version)?;
+        Ok(Self {
             name: CowStr::Owned(raw.metadata.name),
             description: CowStr::Owned(raw.metadata.description),
             author: CowStr::Owned(raw.metadata.author),
             version: raw.version,
             sub_version: raw.sub_version,
-            minecraft_data_version: raw.minecraft_data_version,
-
-            #[cfg(feature = "chrono")]
-            time_created: Utc.timestamp_millis_opt(raw.metadata.time_created).unwrap(),
-            #[cfg(not(feature = "chrono"))]
-            time_created: raw.metadata.time_created,
-            #[cfg(feature = "chrono")]
-            time_modified: Utc
-                .timestamp_millis_opt(raw.metadata.time_modified)
-                .unwrap(),
-            #[cfg(not(feature = "chrono"))]
-            time_modified: raw.metadata.time_modified,
+            minecraft_data_version: raw.minecraft_data_version.unwrap_or(LEGACY_DATA_VERSION),
+            stored_totals: Some(MetadataTotals {
+                region_count: raw.metadata.region_count,
+                total_volume: raw.metadata.total_volume,
+                total_blocks: raw.metadata.total_blocks,
+                enclosing_size: raw.metadata.enclosing_size,
+            }),
+            extra: raw.metadata.extra,
+            root_extra: raw.extra,
+
+            time_created: util::time_from_millis(raw.metadata.time_created).ok_or(
+                Error::TimestampOutOfRange {
+                    timestamp: raw.metadata.time_created,
+                },
+            )?,
+            time_modified: util::time_from_millis(raw.metadata.time_modified).ok_or(
+                Error::TimestampOutOfRange {
+                    timestamp: raw.metadata.time_modified,
+                },
+            )?,
 
             #[cfg(feature = "image")]
-            preview_image: raw.metadata.preview_image_data.map(|data| {
-                RgbaImage::from_raw(
-                    140,
-                    140,
-                    data.iter()
-                        .flat_map(|int| {
-                            let mut px = int.to_be_bytes();
-                            // data is ARGB, convert it to RGBA
-                            px.rotate_left(1);
-                            px
-                        })
-                        .collect(),
-                )
-                .expect("preview image data should have 140x140 pixels")
-                .into()
-            }),
+            preview_image: raw
+                .metadata
+                .preview_image_data
+                .map(|data| {
+                    RgbaImage::from_raw(
+                        140,
+                        140,
+                        data.iter()
+                            .flat_map(|int| {
+                                let mut px = int.to_be_bytes();
+                                // data is ARGB, convert it to RGBA
+                                px.rotate_left(1);
+                                px
+                            })
+                            .collect(),
+                    )
+                    .map(DynamicImage::from)
+                    .ok_or(Error::InvalidPreviewSize { pixels: data.len() })
+                })
+                .transpose()?,
             #[cfg(not(feature = "image"))]
             preview_image: raw
                 .metadata
                 .preview_image_data
                 .clone()
                 .map(|data| data.into_inner()),
-        }
+        })
+    }
+
+    /// Create new [raw NBT litematic](schema::Litematic) metadata with the given regions and
+    /// summary values.
+    ///
+    /// Returns an error if the format version is not supported.
+    pub(crate) fn to_raw<Regions>(
+        &self,
+        regions: Regions,
+        totals: MetadataTotals,
+    ) -> Result<schema::Litematic<Regions>> {
+        check_version(self.version)?;
+        Ok(schema::Litematic {
+            regions,
+            version: self.version,
+            sub_version: self.sub_version,
+            minecraft_data_version: (self.version >= DATA_VERSION_SCHEMATIC_VERSION)
+                .then_some(self.minecraft_data_version),
+            metadata: schema::Metadata {
+                name: self.name.clone().into_owned(),
+                description: self.description.clone().into_owned(),
+                author: self.author.clone().into_owned(),
+                region_count: totals.region_count,
+                total_blocks: totals.total_blocks,
+                total_volume: totals.total_volume,
+                enclosing_size: totals.enclosing_size,
+
+                #[cfg(feature = "chrono")]
+                time_created: self.time_created.timestamp_millis(),
+                #[cfg(not(feature = "chrono"))]
+                time_created: self.time_created,
+                #[cfg(feature = "chrono")]
+                time_modified: util::current_time().timestamp_millis(),
+                #[cfg(not(feature = "chrono"))]
+                time_modified: util::current_time(),
+
+                #[cfg(feature = "image")]
+                preview_image_data: self.preview_image.as_ref().map(|img| {
+                    IntArray::new(
+                        img.to_rgba8()
+                            .into_vec()
+                            .chunks_exact(4)
+                            .map(|px| {
+                                let mut px = [px[0], px[1], px[2], px[3]];
+                                // convert RGBA to ARGB
+                                px.rotate_right(1);
+                                i32::from_be_bytes(px)
+                            })
+                            .collect(),
+                    )
+                }),
+                #[cfg(not(feature = "image"))]
+                preview_image_data: self.preview_image.clone().map(IntArray::new),
+                extra: self.extra.clone(),
+            },
+            extra: self.root_extra.clone(),
+        })
     }
 
     /// Load schematic metadata from uncompressed bytes.
     pub fn from_uncompressed_bytes(bytes: &[u8]) -> Result<Self> {
-        Ok(Self::from_raw(fastnbt::from_bytes(bytes)?))
+        Self::from_raw(fastnbt::from_bytes(bytes)?)
     }
 
     /// Load schematic metadata from gzip compressed bytes.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        let mut gz = GzDecoder::new(bytes);
-        let mut extracted = vec![];
-        gz.read_to_end(&mut extracted)?;
-        Self::from_uncompressed_bytes(&extracted)
+        Self::from_reader(bytes)
+    }
+
+    /// Load schematic metadata from a reader of gzip compressed bytes.
+    ///
+    /// The data is decompressed while reading, so the schematic never has to be fully buffered in
+    /// memory.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        Self::from_raw(fastnbt::from_reader(GzDecoder::new(reader))?)
+    }
+
+    /// Write this metadata to a writer as a gzip compressed schematic without any regions.
+    ///
+    /// The summary values are taken from [`Self::stored_totals`], or are all zero if there are
+    /// none. Use [`Litematic::write_to`] to write them for the actual regions.
+    pub fn write_to(&self, writer: impl Write) -> Result<()> {
+        let totals = self.stored_totals.unwrap_or(MetadataTotals {
+            region_count: 0,
+            total_volume: 0,
+            total_blocks: 0,
+            enclosing_size: BlockPos::new(0, 0, 0),
+        });
+        let raw = self.to_raw(HashMap::<String, Value>::new(), totals)?;
+        let mut gz = GzEncoder::new(writer, Compression::default());
+        fastnbt::to_writer(&mut gz, &raw)?;
+        gz.finish()?.flush()?;
+        Ok(())
     }
 
     /// Load schematic metadata from a file.
     pub fn read_file(filename: impl AsRef<Path>) -> Result<Self> {
-        let mut file = File::open(filename)?;
-        let mut bytes = vec![];
-        file.read_to_end(&mut bytes)?;
-        LitematicMetadata::from_bytes(&bytes)
+        Self::from_reader(BufReader::new(File::open(filename)?))
     }
 
     /// Get the optional preview image.
@@ -464,3 +794,15 @@ impl LitematicMetadata {
         }
     }
 }
+
+/// Return an error if a litematica format version is not in [`LITEMATIC_VERSIONS`].
+fn check_version(version: i32) -> Result<()> {
+    if LITEMATIC_VERSIONS.contains(&version) {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedVersion {
+            format: "litematica",
+            version,
+        })
+    }
+}
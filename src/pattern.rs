@@ -0,0 +1,510 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hasher},
+};
+
+use mcdata::GenericBlockState;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    util,
+};
+
+/// A weighted random choice of block states, parsed from a WorldEdit-style pattern.
+///
+/// A pattern is a comma separated list of block states, each optionally prefixed with a weight in
+/// percent, e.g. `stone,50%andesite` or `30%oak_stairs[facing=north],70%air`. Block names without
+/// a namespace default to `minecraft:`. Entries without a weight share the percentage left over
+/// by the weighted ones equally. Weights are relative, so they don't have to add up to 100.
+///
+/// Blocks are chosen using a small built-in pseudo random number generator. It is seeded
+/// randomly by default, use [`Self::with_seed`] for reproducible results.
+///
+/// Patterns can be used with [`Region::fill_pattern`](crate::Region::fill_pattern) and
+/// [`Region::replace_pattern`](crate::Region::replace_pattern), or on their own using
+/// [`Self::next_block`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    entries: Vec<(f64, GenericBlockState)>,
+    total_weight: f64,
+    rng: SplitMix64,
+}
+
+impl Pattern {
+    /// Parse a pattern like `stone,50%andesite`.
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut parser = Parser::new(input);
+        let mut entries = vec![];
+        loop {
+            parser.skip_whitespace();
+            let weight = parser.weight()?;
+            entries.push((weight, parser.block()?));
+            if !parser.eat(',') {
+                break;
+            }
+        }
+        parser.end()?;
+
+        let explicit = entries
+            .iter()
+            .filter_map(|(weight, _)| *weight)
+            .sum::<f64>();
+        let implicit = entries
+            .iter()
+            .filter(|(weight, _)| weight.is_none())
+            .count();
+        let share = (100.0 - explicit).max(0.0) / implicit.max(1) as f64;
+        let entries = entries
+            .into_iter()
+            .map(|(weight, block)| (weight.unwrap_or(share), block))
+            .collect::<Vec<_>>();
+        let total_weight = entries.iter().map(|(weight, _)| weight).sum::<f64>();
+        if total_weight <= 0.0 {
+            return Err(parser.error("a positive weight"));
+        }
+
+        Ok(Self {
+            entries,
+            total_weight,
+            rng: SplitMix64::random(),
+        })
+    }
+
+    /// Use a fixed seed for choosing blocks, so the same sequence of blocks is chosen every time.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SplitMix64(seed);
+        self
+    }
+
+    /// The block states of this pattern with their weights in percent.
+    ///
+    /// The weights are normalized to add up to 100.
+    pub fn entries(&self) -> impl Iterator<Item = (f64, &GenericBlockState)> {
+        self.entries
+            .iter()
+            .map(|(weight, block)| (weight / self.total_weight * 100.0, block))
+    }
+
+    /// Choose the next block state.
+    ///
+    /// Returns an error if the block state can't be represented as `BlockState`.
+    pub fn next_block<BlockState: DeserializeOwned>(&mut self) -> Result<BlockState> {
+        let index = self.next_index();
+        Ok(util::convert(&self.entries[index].1)?)
+    }
+
+    /// The block states of this pattern converted to `BlockState`, in the order of
+    /// [`Self::next_index`].
+    pub(crate) fn blocks<BlockState: DeserializeOwned>(&self) -> Result<Vec<BlockState>> {
+        self.entries
+            .iter()
+            .map(|(_, block)| Ok(util::convert(block)?))
+            .collect()
+    }
+
+    /// Choose the index of the next block state.
+    pub(crate) fn next_index(&mut self) -> usize {
+        let mut value = self.rng.next_f64() * self.total_weight;
+        for (index, (weight, _)) in self.entries.iter().enumerate() {
+            if value < *weight {
+                return index;
+            }
+            value -= weight;
+        }
+        // rounding errors can only ever affect the last entry
+        self.entries.len() - 1
+    }
+}
+
+/// A condition on block states, parsed from a WorldEdit-style mask.
+///
+/// A mask consists of block states like `stone` or `oak_stairs[facing=north]` and block tags
+/// like `#minecraft:logs`, which can be combined with `,` or `|` (any of), `&` (all of), `!`
+/// (not), and parentheses, where `&` binds more tightly than `,` and `|`. For example,
+/// `!air & #minecraft:logs` or `(stone,andesite) & !#stone_bricks`. Block names without a
+/// namespace default to `minecraft:`. A block state matches regardless of its properties that
+/// aren't listed in the mask.
+///
+/// Only a built-in selection of vanilla block tags is supported. Some of them are based on block
+/// names and therefore not exact for every Minecraft version. See [`Self::TAGS`] for the list.
+///
+/// Masks can be used with [`Region::replace_pattern`](crate::Region::replace_pattern), or as a
+/// predicate using [`Self::matches`], e.g. for [`Region::replace_in`](crate::Region::replace_in).
+#[derive(Debug, Clone)]
+pub struct Mask(MaskNode);
+
+#[derive(Debug, Clone)]
+enum MaskNode {
+    Block(GenericBlockState),
+    Tag(fn(&str) -> bool),
+    Not(Box<MaskNode>),
+    All(Vec<MaskNode>),
+    Any(Vec<MaskNode>),
+}
+
+impl Mask {
+    /// The names of all supported block tags, without the `minecraft:` namespace.
+    pub const TAGS: &'static [&'static str] = &[
+        "anvil",
+        "banners",
+        "beds",
+        "buttons",
+        "candles",
+        "doors",
+        "fence_gates",
+        "fences",
+        "flower_pots",
+        "ice",
+        "leaves",
+        "logs",
+        "planks",
+        "pressure_plates",
+        "rails",
+        "saplings",
+        "shulker_boxes",
+        "signs",
+        "slabs",
+        "stairs",
+        "stone_bricks",
+        "terracotta",
+        "trapdoors",
+        "wall_signs",
+        "walls",
+        "wool",
+        "wool_carpets",
+    ];
+
+    /// Parse a mask like `!air & #minecraft:logs`.
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut parser = Parser::new(input);
+        let node = parser.mask_any()?;
+        parser.end()?;
+        Ok(Self(node))
+    }
+
+    /// Whether the given block state matches this mask.
+    ///
+    /// Block states which can't be represented as [`GenericBlockState`] never match.
+    pub fn matches<BlockState: Serialize>(&self, block: &BlockState) -> bool {
+        util::convert(block).is_ok_and(|block| self.0.matches(&block))
+    }
+}
+
+impl MaskNode {
+    fn matches(&self, block: &GenericBlockState) -> bool {
+        match self {
+            MaskNode::Block(expected) => {
+                expected.name == block.name
+                    && expected
+                        .properties
+                        .iter()
+                        .all(|(key, value)| block.properties.get(key) == Some(value))
+            }
+            MaskNode::Tag(f) => block.name.strip_prefix("minecraft:").is_some_and(f),
+            MaskNode::Not(node) => !node.matches(block),
+            MaskNode::All(nodes) => nodes.iter().all(|node| node.matches(block)),
+            MaskNode::Any(nodes) => nodes.iter().any(|node| node.matches(block)),
+        }
+    }
+}
+
+/// The members of the `logs` block tag.
+const LOGS: &[&str] = &[
+    "oak_log",
+    "oak_wood",
+    "stripped_oak_log",
+    "stripped_oak_wood",
+    "spruce_log",
+    "spruce_wood",
+    "stripped_spruce_log",
+    "stripped_spruce_wood",
+    "birch_log",
+    "birch_wood",
+    "stripped_birch_log",
+    "stripped_birch_wood",
+    "jungle_log",
+    "jungle_wood",
+    "stripped_jungle_log",
+    "stripped_jungle_wood",
+    "acacia_log",
+    "acacia_wood",
+    "stripped_acacia_log",
+    "stripped_acacia_wood",
+    "dark_oak_log",
+    "dark_oak_wood",
+    "stripped_dark_oak_log",
+    "stripped_dark_oak_wood",
+    "mangrove_log",
+    "mangrove_wood",
+    "stripped_mangrove_log",
+    "stripped_mangrove_wood",
+    "cherry_log",
+    "cherry_wood",
+    "stripped_cherry_log",
+    "stripped_cherry_wood",
+    "pale_oak_log",
+    "pale_oak_wood",
+    "stripped_pale_oak_log",
+    "stripped_pale_oak_wood",
+    "crimson_stem",
+    "crimson_hyphae",
+    "stripped_crimson_stem",
+    "stripped_crimson_hyphae",
+    "warped_stem",
+    "warped_hyphae",
+    "stripped_warped_stem",
+    "stripped_warped_hyphae",
+];
+
+/// The members of the `stone_bricks` block tag.
+const STONE_BRICKS: &[&str] = &[
+    "stone_bricks",
+    "mossy_stone_bricks",
+    "cracked_stone_bricks",
+    "chiseled_stone_bricks",
+];
+
+/// The members of the `wool_carpets` block tag.
+const WOOL_CARPETS: &[&str] = &[
+    "white_carpet",
+    "orange_carpet",
+    "magenta_carpet",
+    "light_blue_carpet",
+    "yellow_carpet",
+    "lime_carpet",
+    "pink_carpet",
+    "gray_carpet",
+    "light_gray_carpet",
+    "cyan_carpet",
+    "purple_carpet",
+    "blue_carpet",
+    "brown_carpet",
+    "green_carpet",
+    "red_carpet",
+    "black_carpet",
+];
+
+/// Get the matcher for a built-in block tag.
+fn tag(name: &str) -> Option<fn(&str) -> bool> {
+    let f: fn(&str) -> bool = match name {
+        "anvil" => |n| n.ends_with("anvil"),
+        "banners" => |n| n.ends_with("_banner"),
+        "beds" => |n| n.ends_with("_bed"),
+        "buttons" => |n| n.ends_with("_button"),
+        "candles" => |n| n.ends_with("candle"),
+        "doors" => |n| n.ends_with("_door") && !n.ends_with("_trapdoor"),
+        "fence_gates" => |n| n.ends_with("_fence_gate"),
+        "fences" => |n| n.ends_with("_fence"),
+        "flower_pots" => |n| n == "flower_pot" || n.starts_with("potted_"),
+        "ice" => |n| matches!(n, "ice" | "packed_ice" | "blue_ice" | "frosted_ice"),
+        "leaves" => |n| n.ends_with("_leaves"),
+        "logs" => |n| LOGS.contains(&n),
+        "planks" => |n| n.ends_with("_planks"),
+        "pressure_plates" => |n| n.ends_with("_pressure_plate"),
+        "rails" => |n| n.ends_with("rail"),
+        "saplings" => |n| n.ends_with("_sapling") || n == "mangrove_propagule",
+        "shulker_boxes" => |n| n.ends_with("shulker_box"),
+        "signs" => |n| n.ends_with("_sign") && !n.contains("hanging"),
+        "slabs" => |n| n.ends_with("_slab"),
+        "stairs" => |n| n.ends_with("_stairs"),
+        "stone_bricks" => |n| STONE_BRICKS.contains(&n),
+        "terracotta" => |n| n.ends_with("terracotta") && !n.ends_with("glazed_terracotta"),
+        "trapdoors" => |n| n.ends_with("_trapdoor"),
+        "wall_signs" => |n| n.ends_with("_wall_sign") && !n.contains("hanging"),
+        "walls" => |n| n.ends_with("_wall"),
+        "wool" => |n| n.ends_with("_wool"),
+        "wool_carpets" => |n| WOOL_CARPETS.contains(&n),
+        _ => return None,
+    };
+    Some(f)
+}
+
+/// A simple recursive descent parser for patterns and masks.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn error(&self, expected: &'static str) -> Error {
+        Error::InvalidPattern {
+            input: self.input.to_owned(),
+            position: self.pos,
+            expected,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos = self.input.len() - self.rest().trim_start().len();
+    }
+
+    /// Skip whitespace and consume `c` if it is next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let eaten = self.rest().starts_with(c);
+        if eaten {
+            self.pos += c.len_utf8();
+        }
+        eaten
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<()> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.error(expected)),
+        }
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        match self.rest().is_empty() {
+            true => Ok(()),
+            false => Err(self.error("end of input")),
+        }
+    }
+
+    /// Consume the longest prefix of characters matching `f`.
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn identifier(&mut self, expected: &'static str) -> Result<&'a str> {
+        self.skip_whitespace();
+        let ident = self
+            .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '.' | '-' | '/'));
+        match ident.is_empty() {
+            true => Err(self.error(expected)),
+            false => Ok(ident),
+        }
+    }
+
+    /// An optional weight in percent like `50%`.
+    fn weight(&mut self) -> Result<Option<f64>> {
+        let start = self.pos;
+        let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+        if number.is_empty() || !self.rest().starts_with('%') {
+            self.pos = start;
+            return Ok(None);
+        }
+        self.pos += 1;
+        number.parse().map(Some).map_err(|_| {
+            self.pos = start;
+            self.error("a weight")
+        })
+    }
+
+    /// A block state like `minecraft:oak_stairs[facing=north,half=top]`.
+    fn block(&mut self) -> Result<GenericBlockState> {
+        let name = self.identifier("a block name")?;
+        let name = match name.contains(':') {
+            true => name.to_owned(),
+            false => format!("minecraft:{name}"),
+        };
+        let mut properties = HashMap::new();
+        if self.eat('[') && !self.eat(']') {
+            loop {
+                let key = self.identifier("a property name")?;
+                self.expect('=', "'='")?;
+                let value = self.identifier("a property value")?;
+                properties.insert(key.to_owned().into(), value.to_owned().into());
+                if self.eat(']') {
+                    break;
+                }
+                self.expect(',', "',' or ']'")?;
+            }
+        }
+        Ok(GenericBlockState {
+            name: name.into(),
+            properties,
+        })
+    }
+
+    fn mask_any(&mut self) -> Result<MaskNode> {
+        let mut nodes = vec![self.mask_all()?];
+        while self.eat('|') || self.eat(',') {
+            nodes.push(self.mask_all()?);
+        }
+        Ok(match nodes.len() {
+            1 => nodes.remove(0),
+            _ => MaskNode::Any(nodes),
+        })
+    }
+
+    fn mask_all(&mut self) -> Result<MaskNode> {
+        let mut nodes = vec![self.mask_unary()?];
+        while self.eat('&') {
+            nodes.push(self.mask_unary()?);
+        }
+        Ok(match nodes.len() {
+            1 => nodes.remove(0),
+            _ => MaskNode::All(nodes),
+        })
+    }
+
+    fn mask_unary(&mut self) -> Result<MaskNode> {
+        if self.eat('!') {
+            return Ok(MaskNode::Not(Box::new(self.mask_unary()?)));
+        }
+        if self.eat('(') {
+            let node = self.mask_any()?;
+            self.expect(')', "')'")?;
+            return Ok(node);
+        }
+        if self.eat('#') {
+            let start = self.pos;
+            let name = self.identifier("a block tag")?;
+            return match tag(name.strip_prefix("minecraft:").unwrap_or(name)) {
+                Some(f) => Ok(MaskNode::Tag(f)),
+                None => {
+                    self.pos = start;
+                    Err(self.error("a supported block tag"))
+                }
+            };
+        }
+        Ok(MaskNode::Block(self.block()?))
+    }
+}
+
+/// The SplitMix64 pseudo random number generator.
+///
+/// It is tiny, fast, and good enough for choosing blocks, but not suitable for anything security
+/// related.
+#[derive(Debug, Clone, PartialEq)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Create a new generator with a random seed.
+    fn random() -> Self {
+        // the standard library randomly seeds every `RandomState`
+        Self(
+            std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish(),
+        )
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniformly distributed number in `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -1,10 +1,23 @@
-use std::ops::RangeInclusive;
+use std::{
+    collections::HashMap,
+    ops::{Range, RangeInclusive},
+};
 
-use fastnbt::LongArray;
-use mcdata::{util::BlockPos, GenericBlockEntity, GenericBlockState, GenericEntity};
+use fastnbt::{LongArray, Value};
+use indexmap::IndexMap;
+use mcdata::{
+    util::{BlockPos, Cuboid},
+    GenericBlockEntity, GenericBlockState, GenericEntity,
+};
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{schema, Litematic, PendingBlockTick, PendingFluidTick};
+use crate::{
+    bit_array::{self, BitArray},
+    error::{Error, Result},
+    schema,
+    transform::{self, Transformation},
+    util, Litematic, Mask, Mirror, Pattern, PendingBlockTick, PendingFluidTick, Rotation,
+};
 
 type CowStr = std::borrow::Cow<'static, str>;
 
@@ -33,6 +46,9 @@ pub struct Region<
     pub name: CowStr,
 
     /// The position of this region within the schematic.
+    ///
+    /// Changing this moves the whole region. To move only this corner of the region while keeping
+    /// the blocks in place, use [`Self::set_origin`].
     pub position: BlockPos,
 
     /// The size of this region.
@@ -42,6 +58,9 @@ pub struct Region<
     /// from [`Self::position`]. Local coordinates in regions aren't directly relative to
     /// [`Self::position`], but to the minimum position in the region, effectively to
     /// `BlockPos::new(region.min_global_x(), region.min_global_y(), region.min_global_z())`.
+    ///
+    /// Changing this directly doesn't change the volume of stored blocks, use [`Self::resize`]
+    /// instead.
     pub size: BlockPos,
 
     /// The list of block entities in this region.
@@ -56,8 +75,15 @@ pub struct Region<
     /// Pending fluid ticks in this region.
     pub pending_fluid_ticks: Vec<PendingFluidTick>,
 
+    /// Unknown NBT tags of this region, e.g. from newer litematica versions or other tools.
+    ///
+    /// These are written back unchanged and in the order they were read in.
+    pub extra: IndexMap<String, Value>,
+
     palette: Vec<BlockState>,
-    blocks: Vec<usize>,
+    /// Palette ids by the [hash](util::hash_serialized) of their block state.
+    palette_index: HashMap<u64, Vec<usize>>,
+    blocks: BitArray,
 }
 
 impl<BlockState, Entity, BlockEntity> Region<BlockState, Entity, BlockEntity>
@@ -68,7 +94,7 @@ where
 {
     /// Create a new, empty region with the given name, position, and size.
     pub fn new(name: impl Into<CowStr>, position: BlockPos, size: BlockPos) -> Self {
-        Self {
+        let mut new = Self {
             name: name.into(),
             position,
             size,
@@ -76,16 +102,23 @@ where
             entities: vec![],
             pending_block_ticks: vec![],
             pending_fluid_ticks: vec![],
+            extra: IndexMap::new(),
             palette: vec![BlockState::air()],
-            blocks: vec![0; size.volume() as usize],
-        }
+            palette_index: HashMap::new(),
+            blocks: BitArray::new(2, size.volume() as usize),
+        };
+        new.rebuild_palette_index();
+        new
     }
 
     /// Construct a [`Region`] from a [raw NBT region](schema::Region) with the given name.
+    ///
+    /// Returns an error if the region size overflows, the block data is too short for the region
+    /// size, or the block data references palette entries that don't exist.
     pub(crate) fn from_raw(
         raw: schema::Region<BlockState, Entity, BlockEntity>,
         name: impl Into<CowStr>,
-    ) -> Self {
+    ) -> Result<Self> {
         fn inner<
             B: mcdata::BlockState + Serialize + DeserializeOwned,
             E: mcdata::Entity + Serialize + DeserializeOwned,
@@ -93,67 +126,110 @@ where
         >(
             raw: schema::Region<B, E, T>,
             name: CowStr,
-        ) -> Region<B, E, T> {
-            let mut new = Region::new(name, raw.position, raw.size);
-            new.palette = raw.block_state_palette.to_owned();
-            new.block_entities = raw.tile_entities.to_owned();
-            new.entities = raw.entities.to_owned();
-            new.pending_block_ticks = raw.pending_block_ticks.clone();
-            new.pending_fluid_ticks = raw.pending_fluid_ticks.clone();
-
-            let num_bits = new.num_bits();
-            new.blocks = raw
-                .block_states
-                .iter()
-                .flat_map(|block| (0..64).map(move |bit| block >> bit & 1))
-                .collect::<Vec<i64>>()
-                .chunks(num_bits)
-                .map(|slice| {
-                    slice
-                        .iter()
-                        .rev()
-                        .fold(0, |acc, bit| acc << 1 | *bit as usize)
-                })
-                .collect::<Vec<usize>>();
-
-            new
+        ) -> Result<Region<B, E, T>> {
+            // check the block data before allocating anything for the region size
+            let Some(volume) = util::volume(raw.size) else {
+                return Err(Error::RegionTooLarge {
+                    format: "litematica",
+                    region: name.into_owned(),
+                });
+            };
+            let bits = bits_for(raw.block_state_palette.len());
+            if raw.block_states.len() < BitArray::words_for(bits, volume) {
+                return Err(Error::BlockStatesLengthMismatch {
+                    region: name.into_owned(),
+                    expected: volume,
+                    actual: raw.block_states.len() * 64 / bits,
+                });
+            }
+
+            let mut new = Region {
+                name,
+                position: raw.position,
+                size: raw.size,
+                block_entities: raw.tile_entities,
+                entities: raw.entities,
+                pending_block_ticks: raw.pending_block_ticks,
+                pending_fluid_ticks: raw.pending_fluid_ticks.unwrap_or_default(),
+                extra: raw.extra,
+                palette: raw.block_state_palette,
+                palette_index: HashMap::new(),
+                blocks: BitArray::from_raw(bits, volume, raw.block_states.iter().copied()),
+            };
+            new.rebuild_palette_index();
+            if let Some(index) = new.blocks.iter().find(|id| *id >= new.palette.len()) {
+                return Err(Error::PaletteIndexOutOfRange {
+                    region: new.name.into_owned(),
+                    index,
+                });
+            }
+
+            Ok(new)
         }
         inner(raw, name.into())
     }
 
     /// Create a new [raw NBT region](schema::Region) from this [`Region`].
-    pub(crate) fn to_raw(&self) -> schema::Region<BlockState, Entity, BlockEntity> {
-        let mut new = schema::Region {
+    ///
+    /// If `compact` is set, the palette is [compacted](Self::compact_palette) in the raw region
+    /// without modifying this region.
+    pub(crate) fn to_raw(
+        &self,
+        compact: Option<PaletteOrder>,
+    ) -> schema::Region<BlockState, Entity, BlockEntity> {
+        let compacted = compact.map(|order| self.compacted(order));
+        let (palette, blocks) = match &compacted {
+            Some((palette, blocks)) => (palette, blocks),
+            None => (&self.palette, &self.blocks),
+        };
+        schema::Region {
             position: self.position,
             size: self.size,
-            block_state_palette: self.palette.to_owned(),
+            block_state_palette: palette.to_owned(),
             tile_entities: self.block_entities.to_owned(),
             entities: self.entities.to_owned(),
             pending_block_ticks: self.pending_block_ticks.clone(),
-            pending_fluid_ticks: self.pending_fluid_ticks.clone(),
-            block_states: LongArray::new(vec![]),
-        };
-
-        let num_bits = self.num_bits();
-        new.block_states = LongArray::new(
-            self.blocks
-                .iter()
-                .flat_map(|id| (0..num_bits).map(move |bit| id >> bit & 1))
-                .collect::<Vec<usize>>()
-                .chunks(64)
-                .map(|bits| bits.iter().rev().fold(0, |acc, bit| acc << 1 | *bit as i64))
-                .collect(),
-        );
+            pending_fluid_ticks: Some(self.pending_fluid_ticks.clone()),
+            block_states: LongArray::new(blocks.to_raw()),
+            extra: self.extra.clone(),
+        }
+    }
 
+    /// Construct a [`Region`] from a palette and a list of palette indices in the order of
+    /// [`Self::blocks`].
+    ///
+    /// The first palette entry is expected to be air.
+    pub(crate) fn from_parts(
+        name: impl Into<CowStr>,
+        position: BlockPos,
+        size: BlockPos,
+        palette: Vec<BlockState>,
+        blocks: Vec<usize>,
+    ) -> Self {
+        let mut new = Self::new(name, position, size);
+        new.palette = palette;
+        new.rebuild_palette_index();
+        new.blocks = BitArray::from_values(new.num_bits(), blocks.into_iter());
         new
     }
 
-    fn num_bits(&self) -> usize {
-        let mut num_bits = 2;
-        while 1 << num_bits < self.palette.len() {
-            num_bits += 1;
+    /// Get the palette indices of all blocks in the order of [`Self::blocks`].
+    pub(crate) fn block_ids(&self) -> bit_array::Iter<'_> {
+        self.blocks.iter()
+    }
+
+    fn rebuild_palette_index(&mut self) {
+        self.palette_index.clear();
+        for (id, state) in self.palette.iter().enumerate() {
+            self.palette_index
+                .entry(util::hash_serialized(state))
+                .or_default()
+                .push(id);
         }
-        num_bits
+    }
+
+    fn num_bits(&self) -> usize {
+        bits_for(self.palette.len())
     }
 
     fn pos_to_index(&self, pos: BlockPos) -> usize {
@@ -200,7 +276,7 @@ where
     /// For global coordinates, use [`Self::get_block_global`] instead.
     pub fn get_block(&self, pos: BlockPos) -> &BlockState {
         self.assert_bounds(pos);
-        &self.palette[self.blocks[self.pos_to_index(pos)]]
+        &self.palette[self.blocks.get(self.pos_to_index(pos))]
     }
 
     /// Set the block state at the given position within this region.
@@ -211,15 +287,341 @@ where
     /// For global coordinates, use [`Self::set_block_global`] instead.
     pub fn set_block(&mut self, pos: BlockPos, block: BlockState) {
         self.assert_bounds(pos);
-        let block = block;
-        let id = if let Some(pos) = self.palette.iter().position(|b| b == &block) {
-            pos
-        } else {
-            self.palette.push(block);
-            self.palette.len() - 1
-        };
+        let id = self.add_to_palette(block);
+        let pos = self.pos_to_index(pos);
+        self.blocks.set(pos, id);
+    }
+
+    /// Get the palette id of the block at the given position within this region.
+    ///
+    /// Panics if the position is not inside this region.
+    pub fn get_block_id(&self, pos: BlockPos) -> usize {
+        self.assert_bounds(pos);
+        self.blocks.get(self.pos_to_index(pos))
+    }
+
+    /// Set the block at the given position within this region to the palette entry with the
+    /// given id.
+    ///
+    /// This skips looking up the block state in the palette, so it is the fastest way to set many
+    /// blocks at once. Palette ids can be obtained with [`Self::palette_id`] and
+    /// [`Self::add_to_palette`].
+    ///
+    /// Panics if the position is not inside this region or if the id is not in the palette.
+    pub fn set_block_id(&mut self, pos: BlockPos, id: usize) {
+        self.assert_bounds(pos);
+        assert!(
+            id < self.palette.len(),
+            "palette id '{id}' out of bounds for palette of length {}",
+            self.palette.len(),
+        );
         let pos = self.pos_to_index(pos);
-        self.blocks[pos] = id;
+        self.blocks.set(pos, id);
+    }
+
+    /// Set every block inside the given local area to `block`.
+    ///
+    /// Parts of the area outside this region are ignored. Block entities at positions where the
+    /// block changes are removed. Returns the number of blocks inside the area.
+    ///
+    /// For global coordinates, use [`Litematic::fill`] instead.
+    pub fn fill(&mut self, area: Cuboid, block: BlockState) -> usize {
+        let id = self.add_to_palette(block);
+        self.replace_ids_in(area, |_| true, || id)
+    }
+
+    /// Replace every occurrence of `from` in this region with `to`.
+    ///
+    /// This only changes the palette if `to` isn't used in this region yet, so it is very fast.
+    /// Block entities at replaced positions are removed. Returns the number of replaced blocks.
+    pub fn replace(&mut self, from: &BlockState, to: BlockState) -> usize {
+        let Some(from_id) = self.palette_id(from) else {
+            return 0;
+        };
+        let count = self.blocks.iter().filter(|id| *id == from_id).count();
+        if count == 0 || from == &to {
+            return count;
+        }
+        self.remove_block_entities_where(|region, pos| region.get_block_id(pos) == from_id);
+        match self.palette_id(&to) {
+            // air has to stay at id 0
+            None if from_id != 0 => {
+                self.palette[from_id] = to;
+                self.rebuild_palette_index();
+            }
+            _ => {
+                let to_id = self.add_to_palette(to);
+                for index in 0..self.blocks.len() {
+                    if self.blocks.get(index) == from_id {
+                        self.blocks.set(index, to_id);
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// Set every block inside the given local area that matches `predicate` to `block`.
+    ///
+    /// The predicate is only called once per palette entry. Parts of the area outside this region
+    /// are ignored and block entities at replaced positions are removed. Returns the number of
+    /// replaced blocks, which doesn't include blocks that already are `block`.
+    ///
+    /// For global coordinates, use [`Litematic::replace_in`] instead.
+    pub fn replace_in(
+        &mut self,
+        area: Cuboid,
+        predicate: impl FnMut(&BlockState) -> bool,
+        block: BlockState,
+    ) -> usize {
+        let matches = self.palette.iter().map(predicate).collect::<Vec<_>>();
+        let id = self.add_to_palette(block);
+        self.replace_ids_in(area, |old| old != id && matches[old], || id)
+    }
+
+    /// Set every block inside the given local area to a block chosen by `pattern`.
+    ///
+    /// Parts of the area outside this region are ignored and block entities at changed positions
+    /// are removed. Returns the number of blocks inside the area, or an error if a block state of
+    /// the pattern can't be represented as `BlockState`.
+    ///
+    /// For global coordinates, use [`Litematic::fill_pattern`] instead.
+    pub fn fill_pattern(&mut self, area: Cuboid, pattern: &mut Pattern) -> Result<usize> {
+        let ids = self.add_pattern_to_palette(pattern)?;
+        Ok(self.replace_ids_in(area, |_| true, || ids[pattern.next_index()]))
+    }
+
+    /// Set every block inside the given local area that matches `mask` to a block chosen by
+    /// `pattern`.
+    ///
+    /// The mask is only checked once per palette entry. Parts of the area outside this region
+    /// are ignored and block entities at changed positions are removed. Returns the number of
+    /// replaced blocks, or an error if a block state of the pattern can't be represented as
+    /// `BlockState`.
+    ///
+    /// For global coordinates, use [`Litematic::replace_pattern`] instead.
+    pub fn replace_pattern(
+        &mut self,
+        area: Cuboid,
+        mask: &Mask,
+        pattern: &mut Pattern,
+    ) -> Result<usize> {
+        let matches = self
+            .palette
+            .iter()
+            .map(|block| mask.matches(block))
+            .collect::<Vec<_>>();
+        let ids = self.add_pattern_to_palette(pattern)?;
+        Ok(self.replace_ids_in(
+            area,
+            |old| matches.get(old).copied().unwrap_or_default(),
+            || ids[pattern.next_index()],
+        ))
+    }
+
+    fn add_pattern_to_palette(&mut self, pattern: &Pattern) -> Result<Vec<usize>> {
+        Ok(pattern
+            .blocks()?
+            .into_iter()
+            .map(|block| self.add_to_palette(block))
+            .collect())
+    }
+
+    /// Set every block inside the given local area to air.
+    ///
+    /// Parts of the area outside this region are ignored and block entities at cleared positions
+    /// are removed. Returns the number of blocks inside the area.
+    ///
+    /// For global coordinates, use [`Litematic::clear`] instead.
+    pub fn clear(&mut self, area: Cuboid) -> usize {
+        self.fill(area, BlockState::air())
+    }
+
+    /// Set every block inside the given local area for which `predicate` returns `true` for its
+    /// old palette id to the palette id returned by `next`.
+    ///
+    /// Block entities at changed positions are removed. Returns the number of matching blocks.
+    fn replace_ids_in(
+        &mut self,
+        area: Cuboid,
+        mut predicate: impl FnMut(usize) -> bool,
+        mut next: impl FnMut() -> usize,
+    ) -> usize {
+        let size = self.size.abs();
+        let clamp = |range: Range<i32>, len: i32| range.start.max(0)..range.end.min(len);
+        let (xs, ys, zs) = (
+            clamp(area.x_range(), size.x),
+            clamp(area.y_range(), size.y),
+            clamp(area.z_range(), size.z),
+        );
+        let in_area =
+            |pos: BlockPos| xs.contains(&pos.x) && ys.contains(&pos.y) && zs.contains(&pos.z);
+        let block_entity_ids = self
+            .block_entities
+            .iter()
+            .map(|e| e.position())
+            .filter(|pos| self.is_in_bounds(*pos) && in_area(*pos))
+            .map(|pos| (pos, self.get_block_id(pos)))
+            .collect::<Vec<_>>();
+
+        let mut count = 0;
+        for y in ys.clone() {
+            for z in zs.clone() {
+                let row = (y * size.x * size.z + z * size.x) as usize;
+                for x in xs.clone() {
+                    let index = row + x as usize;
+                    if predicate(self.blocks.get(index)) {
+                        self.blocks.set(index, next());
+                        count += 1;
+                    }
+                }
+            }
+        }
+
+        if !block_entity_ids.is_empty() {
+            self.remove_block_entities_where(|region, pos| {
+                block_entity_ids
+                    .iter()
+                    .any(|(old_pos, old)| *old_pos == pos && region.get_block_id(pos) != *old)
+            });
+        }
+        count
+    }
+
+    fn remove_block_entities_where(&mut self, mut f: impl FnMut(&Self, BlockPos) -> bool) {
+        let remove = self
+            .block_entities
+            .iter()
+            .map(|e| self.is_in_bounds(e.position()) && f(self, e.position()))
+            .collect::<Vec<_>>();
+        let mut remove = remove.into_iter();
+        self.block_entities
+            .retain(|_| !remove.next().unwrap_or_default());
+    }
+
+    /// Remove all unused entries from the palette.
+    ///
+    /// Air always stays at palette id `0`. The order of the remaining entries is determined by
+    /// `order`. This also reduces the number of bits used per block if possible, which makes
+    /// written schematics smaller.
+    ///
+    /// To compact the palettes of all regions whenever a schematic is written, see
+    /// [`Litematic::compact_palettes`].
+    pub fn compact_palette(&mut self, order: PaletteOrder) {
+        (self.palette, self.blocks) = self.compacted(order);
+        self.rebuild_palette_index();
+    }
+
+    fn compacted(&self, order: PaletteOrder) -> (Vec<BlockState>, BitArray) {
+        // collect the used ids in the order of their first occurrence
+        let mut used = vec![false; self.palette.len()];
+        used[0] = true;
+        let mut ids = vec![0];
+        for id in self.blocks.iter() {
+            if let Some(used @ false) = used.get_mut(id) {
+                *used = true;
+                ids.push(id);
+            }
+        }
+        if order == PaletteOrder::Unchanged {
+            ids.sort_unstable();
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        for (new, old) in ids.iter().enumerate() {
+            remap[*old] = new;
+        }
+        let palette = ids
+            .iter()
+            .map(|id| self.palette[*id].clone())
+            .collect::<Vec<_>>();
+        let blocks = BitArray::from_values(
+            bits_for(palette.len()),
+            self.blocks
+                .iter()
+                .map(|id| remap.get(id).copied().unwrap_or_default()),
+        );
+        (palette, blocks)
+    }
+
+    /// Move air to palette id `0`, adding it to the palette if it is missing.
+    pub(crate) fn move_air_to_front(&mut self) {
+        let air = BlockState::air();
+        let remap = match self.palette.iter().position(|state| state == &air) {
+            Some(0) => return,
+            Some(air_id) => {
+                self.palette.swap(0, air_id);
+                let mut remap = (0..self.palette.len()).collect::<Vec<_>>();
+                remap.swap(0, air_id);
+                remap
+            }
+            None => {
+                self.palette.insert(0, air);
+                (1..self.palette.len()).collect()
+            }
+        };
+        self.blocks =
+            BitArray::from_values(self.num_bits(), self.blocks.iter().map(|id| remap[id]));
+        self.rebuild_palette_index();
+    }
+
+    /// Replace every palette entry with the result of `f`, merging entries which become equal.
+    pub(crate) fn map_palette(&mut self, mut f: impl FnMut(&BlockState) -> BlockState) {
+        let mut palette: Vec<BlockState> = Vec::with_capacity(self.palette.len());
+        let mut palette_index: HashMap<u64, Vec<usize>> = HashMap::new();
+        let remap = self
+            .palette
+            .iter()
+            .map(|state| {
+                let state = f(state);
+                let ids = palette_index
+                    .entry(util::hash_serialized(&state))
+                    .or_default();
+                match ids.iter().copied().find(|id| palette[*id] == state) {
+                    Some(id) => id,
+                    None => {
+                        palette.push(state);
+                        ids.push(palette.len() - 1);
+                        palette.len() - 1
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        self.palette = palette;
+        self.palette_index = palette_index;
+        if remap.iter().enumerate().any(|(old, new)| old != *new) {
+            self.blocks =
+                BitArray::from_values(self.num_bits(), self.blocks.iter().map(|id| remap[id]));
+        }
+    }
+
+    /// Get the palette id of a block state.
+    ///
+    /// Returns `None` if the block state is not in the palette.
+    pub fn palette_id(&self, block: &BlockState) -> Option<usize> {
+        self.palette_index
+            .get(&util::hash_serialized(block))?
+            .iter()
+            .copied()
+            .find(|id| &self.palette[*id] == block)
+    }
+
+    /// Get the palette id of a block state and add it to the palette if it isn't in it yet.
+    pub fn add_to_palette(&mut self, block: BlockState) -> usize {
+        let hash = util::hash_serialized(&block);
+        if let Some(id) = self
+            .palette_index
+            .get(&hash)
+            .and_then(|ids| ids.iter().copied().find(|id| self.palette[*id] == block))
+        {
+            return id;
+        }
+        self.palette.push(block);
+        let id = self.palette.len() - 1;
+        self.palette_index.entry(hash).or_default().push(id);
+        // grow the storage once the palette no longer fits into the current number of bits
+        self.blocks.resize_bits(self.num_bits());
+        id
     }
 
     /// Get the block state at the given position within this region.
@@ -230,7 +632,7 @@ where
     /// For global coordinates, use [`Self::get_block_global_opt`] instead.
     pub fn get_block_opt(&self, pos: BlockPos) -> Option<&BlockState> {
         self.is_in_bounds(pos)
-            .then(|| &self.palette[self.blocks[self.pos_to_index(pos)]])
+            .then(|| &self.palette[self.blocks.get(self.pos_to_index(pos))])
     }
 
     /// Set the block state at the given position within this region.
@@ -269,6 +671,21 @@ where
         }
     }
 
+    /// Converts an area from the global to the region-local coordinate system.
+    ///
+    /// The area doesn't have to be inside this region.
+    pub(crate) fn area_from_global(&self, area: Cuboid) -> Cuboid {
+        Cuboid::new(
+            area.origin
+                - BlockPos::new(
+                    self.min_global_x(),
+                    self.min_global_y(),
+                    self.min_global_z(),
+                ),
+            area.size,
+        )
+    }
+
     fn assert_global_bounds(&self, pos: BlockPos) {
         assert!(
             self.global_x_range().contains(&pos.x),
@@ -307,7 +724,9 @@ where
     /// For local coordinates, use [`Self::get_block`] instead.
     pub fn get_block_global(&self, pos: BlockPos) -> &BlockState {
         self.assert_global_bounds(pos);
-        &self.palette[self.blocks[self.pos_to_index(self.pos_from_global(pos))]]
+        &self.palette[self
+            .blocks
+            .get(self.pos_to_index(self.pos_from_global(pos)))]
     }
 
     /// Set the block state at the given global position.
@@ -328,8 +747,11 @@ where
     ///
     /// For local coordinates, use [`Self::get_block_opt`] instead.
     pub fn get_block_global_opt(&self, pos: BlockPos) -> Option<&BlockState> {
-        self.is_in_global_bounds(pos)
-            .then(|| &self.palette[self.blocks[self.pos_to_index(self.pos_from_global(pos))]])
+        self.is_in_global_bounds(pos).then(|| {
+            &self.palette[self
+                .blocks
+                .get(self.pos_to_index(self.pos_from_global(pos)))]
+        })
     }
 
     /// Set the block state at the given global position.
@@ -472,7 +894,7 @@ where
 
     /// Count the number of non-air blocks in this region.
     pub fn total_blocks(&self) -> usize {
-        self.blocks.iter().filter(|b| b != &&0).count()
+        self.blocks.iter().filter(|b| *b != 0).count()
     }
 
     /// Create an iterator over all blocks in this region.
@@ -482,12 +904,109 @@ where
     pub fn blocks(&self) -> Blocks<'_, BlockState> {
         Blocks {
             palette: &self.palette,
-            blocks: &self.blocks,
+            blocks: self.blocks.iter(),
             index: 0,
             size: self.size.abs(),
         }
     }
 
+    /// Rotate this region around the origin of the schematic.
+    ///
+    /// This moves the [position](Self::position) and rotates the [size](Self::size) of this
+    /// region, so that rotating every region of a schematic by the same amount rotates the
+    /// schematic as a whole. See [`Litematic::rotate`] for that. Orientation dependent block
+    /// state properties like `facing` or `axis` are rotated as well, see
+    /// [`Rotation::rotate_block_state`]. Block entities, entities, and pending ticks are moved to
+    /// their new positions and entity yaws are rotated.
+    pub fn rotate(&mut self, rotation: Rotation) {
+        if rotation != Rotation::None {
+            self.transform(rotation);
+        }
+    }
+
+    /// Mirror this region along an axis through the origin of the schematic.
+    ///
+    /// Just like [`Self::rotate`], this moves the [position](Self::position) and flips the
+    /// [size](Self::size) of this region, so that mirroring every region of a schematic mirrors
+    /// the schematic as a whole. See [`Litematic::mirror`] for that. Orientation dependent block
+    /// state properties are mirrored as well, see [`Mirror::mirror_block_state`]. Block entities,
+    /// entities, and pending ticks are moved to their new positions and entity yaws are
+    /// reflected.
+    pub fn mirror(&mut self, mirror: Mirror) {
+        if mirror != Mirror::None {
+            self.transform(mirror);
+        }
+    }
+
+    fn transform(&mut self, t: impl Transformation) {
+        let size = self.size.abs();
+        let apply = |pos: BlockPos| {
+            let (x, z) = t.apply(pos.x, pos.z);
+            BlockPos::new(x, pos.y, z)
+        };
+        // local coordinates are relative to the minimum corner, so the transformed positions have
+        // to be moved back into the positive range
+        let offset = apply(size - BlockPos::new(1, 1, 1));
+        let offset = BlockPos::new(offset.x.min(0), 0, offset.z.min(0));
+        let map_pos = |pos: BlockPos| apply(pos) - offset;
+
+        self.position = apply(self.position);
+        self.size = apply(self.size);
+        let new_size = self.size.abs();
+
+        let mut blocks = BitArray::new(self.blocks.bits(), self.blocks.len());
+        for (index, id) in self.blocks.iter().enumerate() {
+            let index = index as i32;
+            let pos = map_pos(BlockPos::new(
+                index % size.x,
+                index / size.x / size.z % size.y,
+                index / size.x % size.z,
+            ));
+            blocks.set(
+                (pos.x + pos.y * new_size.x * new_size.z + pos.z * new_size.x) as usize,
+                id,
+            );
+        }
+        self.blocks = blocks;
+        for state in self.palette.iter_mut() {
+            *state = transform::map_block_state(state, |state| t.block_state(state));
+        }
+        self.rebuild_palette_index();
+
+        for block_entity in self.block_entities.iter_mut() {
+            let pos = map_pos(block_entity.position());
+            *block_entity = transform::map_nbt(block_entity, |nbt| {
+                nbt.insert("x".into(), Value::Int(pos.x));
+                nbt.insert("y".into(), Value::Int(pos.y));
+                nbt.insert("z".into(), Value::Int(pos.z));
+            });
+        }
+        for entity in self.entities.iter_mut() {
+            *entity = transform::map_nbt(entity, |nbt| {
+                if let Some(Value::List(pos)) = nbt.get_mut("Pos") {
+                    if let [Value::Double(x), _, Value::Double(z)] = &mut pos[..] {
+                        // entity positions are relative to the region position, which is moved
+                        // by the integer transformation just like blocks are
+                        (*x, *z) = t.apply_continuous(*x, *z);
+                    }
+                }
+                if let Some(Value::List(rotation)) = nbt.get_mut("Rotation") {
+                    if let Some(Value::Float(yaw)) = rotation.first_mut() {
+                        *yaw = t.yaw(*yaw);
+                    }
+                }
+            });
+        }
+        for tick in self.pending_block_ticks.iter_mut() {
+            let pos = map_pos(BlockPos::new(tick.x, tick.y, tick.z));
+            (tick.x, tick.y, tick.z) = (pos.x, pos.y, pos.z);
+        }
+        for tick in self.pending_fluid_ticks.iter_mut() {
+            let pos = map_pos(BlockPos::new(tick.x, tick.y, tick.z));
+            (tick.x, tick.y, tick.z) = (pos.x, pos.y, pos.z);
+        }
+    }
+
     /// Create a new [`Litematic`] from this [`Region`] with a given description and author.
     ///
     /// The created schematic will have the same name as this region and will include this region
@@ -503,6 +1022,29 @@ where
     }
 }
 
+/// The order of palette entries after [compacting a palette](Region::compact_palette).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PaletteOrder {
+    /// Keep the remaining entries in their current order.
+    #[default]
+    Unchanged,
+
+    /// Order the entries by their first occurrence in [`Region::blocks`].
+    ///
+    /// This results in the same palette for regions with the same blocks, regardless of how they
+    /// were edited.
+    FirstOccurrence,
+}
+
+/// The number of bits litematica uses per block for a palette of the given length.
+fn bits_for(palette_len: usize) -> usize {
+    let mut num_bits = 2;
+    while 1 << num_bits < palette_len {
+        num_bits += 1;
+    }
+    num_bits
+}
+
 /// An iterator over all blocks in a [`Region`].
 ///
 /// Each item will be a tuple of the local coordinates of this block, and the block state itself.
@@ -512,7 +1054,7 @@ where
     BlockState: mcdata::BlockState,
 {
     palette: &'b Vec<BlockState>,
-    blocks: &'b Vec<usize>,
+    blocks: bit_array::Iter<'b>,
     index: usize,
     size: BlockPos,
 }
@@ -524,10 +1066,7 @@ where
     type Item = (BlockPos, &'b BlockState);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.size.volume() as usize {
-            return None;
-        }
-        let block = self.palette.get(*self.blocks.get(self.index)?)?;
+        let block = self.palette.get(self.blocks.next()?)?;
         let x = self.index as i32 % self.size.x;
         let y = self.index as i32 / self.size.x / self.size.z % self.size.y;
         let z = self.index as i32 / self.size.x % self.size.z;
@@ -1,52 +1,237 @@
-use fastnbt::{IntArray, LongArray};
+use fastnbt::{IntArray, LongArray, Value};
+use indexmap::IndexMap;
 use mcdata::{util::BlockPos, GenericBlockEntity, GenericBlockState, GenericEntity};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{de, ser, Deserialize, Serialize};
+use std::{fmt, marker::PhantomData};
 
 type CowStr = std::borrow::Cow<'static, str>;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Implement [`Deserialize`] for a struct by visiting its compound entries.
+///
+/// All keys that aren't listed are collected into the `extra` field of the struct, so they can be
+/// written back unchanged. The derived implementation with a flattened `extra` map can't be used
+/// for this, as flattening buffers the data in a way that loses NBT specific types like the `u128`
+/// UUIDs of entities.
+macro_rules! deserialize_with_extra {
+    (
+        $name:ident $(<$($gen:ident: $bound:path),*>)? {
+            $($key:literal => $field:ident $(= $default:expr)?),* $(,)?
+        }
+        $(ignore { $($ignored:literal),* $(,)? })?
+    ) => {
+        impl<'de $($(, $gen: $bound + Deserialize<'de>)*)?> Deserialize<'de>
+            for $name$(<$($gen),*>)?
+        {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct Visitor$(<$($gen),*>)?(PhantomData<fn() -> ($($($gen,)*)?)>);
+
+                impl<'de $($(, $gen: $bound + Deserialize<'de>)*)?> de::Visitor<'de>
+                    for Visitor$(<$($gen),*>)?
+                {
+                    type Value = $name$(<$($gen),*>)?;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        write!(f, "a {} compound", stringify!($name))
+                    }
+
+                    fn visit_map<A: de::MapAccess<'de>>(
+                        self,
+                        mut map: A,
+                    ) -> Result<Self::Value, A::Error> {
+                        $(let mut $field = None;)*
+                        let mut extra = IndexMap::new();
+                        while let Some(key) = map.next_key::<String>()? {
+                            match key.as_str() {
+                                $($key => $field = Some(map.next_value()?),)*
+                                $($($ignored => {
+                                    map.next_value::<de::IgnoredAny>()?;
+                                })*)?
+                                _ => {
+                                    extra.insert(key, map.next_value::<Value>()?);
+                                }
+                            }
+                        }
+                        Ok($name {
+                            $($field: match $field {
+                                Some(value) => value,
+                                None => deserialize_with_extra!(@default $key $($default)?),
+                            },)*
+                            extra,
+                        })
+                    }
+                }
+
+                deserializer.deserialize_map(Visitor(PhantomData))
+            }
+        }
+    };
+    (@default $key:literal) => {
+        return Err(de::Error::missing_field($key))
+    };
+    (@default $key:literal $default:expr) => {
+        $default
+    };
+}
+
+/// Regions ready to be serialized.
+pub type Regions<BlockState, Entity, BlockEntity> =
+    IndexMap<String, Region<BlockState, Entity, BlockEntity>>;
+
+/// The regions of a litematic that is read in a single pass.
+///
+/// Litematica doesn't write the compound entries in a fixed order, so the format version may come
+/// before or after the regions. If it comes before them and is not supported, the regions are
+/// skipped instead of deserialized.
+#[derive(Debug, Clone)]
+pub enum StreamedRegions<Regions> {
+    /// The deserialized regions.
+    Parsed(Regions),
+    /// The regions were skipped, as they came after an unsupported format version.
+    Skipped {
+        /// The unsupported format version.
+        version: i32,
+    },
+}
+
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "PascalCase")]
-pub struct Litematic<
-    BlockState = GenericBlockState,
-    Entity = GenericEntity,
-    BlockEntity = GenericBlockEntity,
-> where
-    BlockState: mcdata::BlockState,
-    Entity: mcdata::Entity,
-    BlockEntity: mcdata::BlockEntity,
-{
-    #[serde(flatten)]
-    pub regions: LitematicRegions<BlockState, Entity, BlockEntity>,
+pub struct Litematic<Regions> {
+    // the fields are repeated instead of flattened, as flattening buffers the data in a way that
+    // loses NBT specific types like the `u128` UUIDs of entities
+    pub regions: Regions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minecraft_data_version: Option<i32>,
+    pub version: i32,
+    pub sub_version: Option<i32>,
+    pub metadata: Metadata,
     #[serde(flatten)]
-    pub metadata: LitematicMetadata,
+    pub extra: IndexMap<String, Value>,
+}
+
+// implemented by hand instead of with `deserialize_with_extra!`, as how the regions are read
+// depends on the format version read before them
+impl<'de, Regions: Deserialize<'de>> Deserialize<'de> for Litematic<StreamedRegions<Regions>> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<Regions>(PhantomData<fn() -> Regions>);
+
+        impl<'de, Regions: Deserialize<'de>> de::Visitor<'de> for Visitor<Regions> {
+            type Value = Litematic<StreamedRegions<Regions>>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a Litematic compound")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut regions = None;
+                let mut minecraft_data_version = None;
+                let mut version = None;
+                let mut sub_version = None;
+                let mut metadata = None;
+                let mut extra = IndexMap::new();
+                // a repeated version could otherwise replace the one the regions were read with
+                fn once<T, E: de::Error>(field: &Option<T>, name: &'static str) -> Result<(), E> {
+                    match field {
+                        Some(_) => Err(E::duplicate_field(name)),
+                        None => Ok(()),
+                    }
+                }
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "Regions" => {
+                            once(&regions, "Regions")?;
+                            regions = Some(match version {
+                                Some(version) if !crate::LITEMATIC_VERSIONS.contains(&version) => {
+                                    map.next_value::<de::IgnoredAny>()?;
+                                    StreamedRegions::Skipped { version }
+                                }
+                                _ => StreamedRegions::Parsed(map.next_value()?),
+                            })
+                        }
+                        "MinecraftDataVersion" => {
+                            once(&minecraft_data_version, "MinecraftDataVersion")?;
+                            minecraft_data_version = Some(map.next_value()?);
+                        }
+                        "Version" => {
+                            once(&version, "Version")?;
+                            version = Some(map.next_value()?);
+                        }
+                        "SubVersion" => {
+                            once(&sub_version, "SubVersion")?;
+                            sub_version = Some(map.next_value()?);
+                        }
+                        "Metadata" => {
+                            once(&metadata, "Metadata")?;
+                            metadata = Some(map.next_value()?);
+                        }
+                        _ => {
+                            extra.insert(key, map.next_value::<Value>()?);
+                        }
+                    }
+                }
+                Ok(Litematic {
+                    regions: regions.ok_or_else(|| de::Error::missing_field("Regions"))?,
+                    minecraft_data_version,
+                    version: version.ok_or_else(|| de::Error::missing_field("Version"))?,
+                    sub_version,
+                    metadata: metadata.ok_or_else(|| de::Error::missing_field("Metadata"))?,
+                    extra,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(Visitor(PhantomData))
+    }
+}
+
+impl<Regions> Litematic<Regions> {
+    pub fn split(self) -> (Regions, LitematicMetadata) {
+        (
+            self.regions,
+            LitematicMetadata {
+                minecraft_data_version: self.minecraft_data_version,
+                version: self.version,
+                sub_version: self.sub_version,
+                metadata: self.metadata,
+                extra: self.extra,
+            },
+        )
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "PascalCase")]
-pub struct LitematicRegions<
-    BlockState = GenericBlockState,
-    Entity = GenericEntity,
-    BlockEntity = GenericBlockEntity,
-> where
-    BlockState: mcdata::BlockState,
-    Entity: mcdata::Entity,
-    BlockEntity: mcdata::BlockEntity,
-{
-    pub regions: HashMap<String, Region<BlockState, Entity, BlockEntity>>,
+pub struct LitematicRegions<Regions> {
+    pub regions: Regions,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct LitematicMetadata {
-    pub minecraft_data_version: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minecraft_data_version: Option<i32>,
     pub version: i32,
     pub sub_version: Option<i32>,
     pub metadata: Metadata,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "PascalCase")]
+deserialize_with_extra! {
+    LitematicMetadata {
+        "MinecraftDataVersion" => minecraft_data_version = None,
+        "Version" => version,
+        "SubVersion" => sub_version = None,
+        "Metadata" => metadata,
+    }
+    ignore { "Regions" }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(
+    rename_all = "PascalCase",
+    bound(serialize = "BlockState: Serialize, Entity: Serialize, BlockEntity: Serialize")
+)]
 pub struct Region<
     BlockState = GenericBlockState,
     Entity = GenericEntity,
@@ -58,18 +243,36 @@ pub struct Region<
 {
     pub position: BlockPos,
     pub size: BlockPos,
+    #[serde(serialize_with = "serialize_palette")]
     pub block_state_palette: Vec<BlockState>,
     pub block_states: LongArray,
     pub tile_entities: Vec<BlockEntity>,
-    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
     pub entities: Vec<Entity>,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub pending_block_ticks: Vec<PendingBlockTick>,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub pending_fluid_ticks: Vec<PendingFluidTick>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_fluid_ticks: Option<Vec<PendingFluidTick>>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+deserialize_with_extra! {
+    Region<
+        BlockState: mcdata::BlockState,
+        Entity: mcdata::Entity,
+        BlockEntity: mcdata::BlockEntity
+    > {
+        "Position" => position,
+        "Size" => size,
+        "BlockStatePalette" => block_state_palette,
+        "BlockStates" => block_states,
+        "TileEntities" => tile_entities,
+        "Entities" => entities = Vec::new(),
+        "PendingBlockTicks" => pending_block_ticks = Vec::new(),
+        "PendingFluidTicks" => pending_fluid_ticks = None,
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct Metadata {
     pub name: String,
@@ -77,11 +280,29 @@ pub struct Metadata {
     pub description: String,
     pub region_count: i32,
     pub total_volume: i32,
+    #[serde(serialize_with = "serialize_int_if_fits")]
     pub total_blocks: i64,
     pub time_created: i64,
     pub time_modified: i64,
     pub enclosing_size: BlockPos,
     pub preview_image_data: Option<IntArray>,
+    #[serde(flatten)]
+    pub extra: IndexMap<String, Value>,
+}
+
+deserialize_with_extra! {
+    Metadata {
+        "Name" => name,
+        "Author" => author,
+        "Description" => description,
+        "RegionCount" => region_count,
+        "TotalVolume" => total_volume,
+        "TotalBlocks" => total_blocks,
+        "TimeCreated" => time_created,
+        "TimeModified" => time_modified,
+        "EnclosingSize" => enclosing_size,
+        "PreviewImageData" => preview_image_data = None,
+    }
 }
 
 /// A pending block tick.
@@ -90,7 +311,10 @@ pub struct Metadata {
 #[allow(missing_docs)]
 pub struct PendingBlockTick {
     pub block: CowStr,
+    // may be missing in schematics of older format versions
+    #[serde(default)]
     pub priority: i32,
+    #[serde(default)]
     pub sub_tick: i64,
     pub time: i32,
     #[serde(rename = "x")]
@@ -117,3 +341,36 @@ pub struct PendingFluidTick {
     #[serde(rename = "z")]
     pub z: i32,
 }
+
+/// Serialize a block state palette without empty `Properties` compounds, just like litematica
+/// does.
+fn serialize_palette<BlockState: Serialize, S: ser::Serializer>(
+    palette: &[BlockState],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    palette
+        .iter()
+        .map(|state| {
+            let mut state = fastnbt::to_value(state).map_err(ser::Error::custom)?;
+            if let Value::Compound(map) = &mut state {
+                if matches!(map.get("Properties"), Some(Value::Compound(props)) if props.is_empty())
+                {
+                    map.remove("Properties");
+                }
+            }
+            Ok(state)
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .serialize(serializer)
+}
+
+/// Serialize a long as an int if it fits, just like litematica does for some values.
+fn serialize_int_if_fits<S: ser::Serializer>(
+    value: &i64,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match i32::try_from(*value) {
+        Ok(value) => serializer.serialize_i32(value),
+        Err(_) => serializer.serialize_i64(*value),
+    }
+}
@@ -0,0 +1,354 @@
+use std::{borrow::Cow, collections::HashMap, ops::Neg};
+
+use fastnbt::Value;
+use mcdata::GenericBlockState;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::util;
+
+type CowStr = Cow<'static, str>;
+
+/// The horizontal directions in clockwise order, starting at north.
+const HORIZONTAL: [&str; 4] = ["north", "east", "south", "west"];
+
+/// A rotation around the vertical axis, as used by Minecraft structures and litematica placements.
+///
+/// Rotations are always clockwise or counterclockwise when viewed from above, i.e. a
+/// [`Clockwise90`](Self::Clockwise90) rotation turns north into east.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Rotation {
+    /// No rotation.
+    #[default]
+    None,
+
+    /// A clockwise rotation by 90 degrees.
+    Clockwise90,
+
+    /// A rotation by 180 degrees.
+    Clockwise180,
+
+    /// A counterclockwise rotation by 90 degrees.
+    CounterClockwise90,
+}
+
+impl Rotation {
+    /// The number of clockwise quarter turns of this rotation.
+    fn quarter_turns(self) -> usize {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 1,
+            Rotation::Clockwise180 => 2,
+            Rotation::CounterClockwise90 => 3,
+        }
+    }
+
+    /// Rotate a horizontal direction name like `north`.
+    ///
+    /// Returns `None` if the name is not a horizontal direction.
+    pub fn rotate_direction(self, direction: &str) -> Option<&'static str> {
+        let idx = HORIZONTAL.iter().position(|dir| *dir == direction)?;
+        Some(HORIZONTAL[(idx + self.quarter_turns()) % 4])
+    }
+
+    /// Rotate all properties of a block state that depend on its orientation.
+    ///
+    /// This includes `facing`, `axis`, `rotation`, `orientation`, rail `shape`s, and the `north`,
+    /// `east`, `south`, and `west` connection properties.
+    ///
+    /// ```rust
+    /// use mcdata::GenericBlockState;
+    /// use rustmatica::Rotation;
+    ///
+    /// let mut rail = GenericBlockState {
+    ///     name: "minecraft:rail".into(),
+    ///     properties: [("shape".into(), "south_east".into())].into(),
+    /// };
+    /// Rotation::Clockwise90.rotate_block_state(&mut rail);
+    /// assert_eq!(rail.properties["shape"], "south_west");
+    /// ```
+    pub fn rotate_block_state(self, state: &mut GenericBlockState) {
+        if self == Rotation::None {
+            return;
+        }
+        transform_properties(&mut state.properties, |key, value| match key {
+            "facing" | "shape" | "orientation" => {
+                map_directions(value, |dir| self.rotate_direction(dir))
+            }
+            "axis" if self.quarter_turns() % 2 == 1 => match value {
+                "x" => Some("z".into()),
+                "z" => Some("x".into()),
+                _ => None,
+            },
+            "rotation" => {
+                let rotation = value.parse::<usize>().ok()?;
+                Some(
+                    ((rotation + 4 * self.quarter_turns()) % 16)
+                        .to_string()
+                        .into(),
+                )
+            }
+            _ => None,
+        });
+        transform_connections(&mut state.properties, |dir| self.rotate_direction(dir));
+    }
+
+    /// Rotate an entity yaw in degrees.
+    pub fn rotate_yaw(self, yaw: f32) -> f32 {
+        (yaw + 90.0 * self.quarter_turns() as f32).rem_euclid(360.0)
+    }
+}
+
+/// A mirror operation along a horizontal axis, as used by Minecraft structures and litematica
+/// placements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Mirror {
+    /// No mirroring.
+    #[default]
+    None,
+
+    /// Mirror along the z axis, i.e. swap north and south.
+    LeftRight,
+
+    /// Mirror along the x axis, i.e. swap east and west.
+    FrontBack,
+}
+
+impl Mirror {
+    /// Mirror a horizontal direction name like `north`.
+    ///
+    /// Returns `None` if the name is not a horizontal direction.
+    pub fn mirror_direction(self, direction: &str) -> Option<&'static str> {
+        let idx = HORIZONTAL.iter().position(|dir| *dir == direction)?;
+        Some(match (self, idx % 2) {
+            (Mirror::LeftRight, 0) | (Mirror::FrontBack, 1) => HORIZONTAL[(idx + 2) % 4],
+            _ => HORIZONTAL[idx],
+        })
+    }
+
+    /// Mirror all properties of a block state that depend on its orientation.
+    ///
+    /// This includes `facing`, `rotation`, `orientation`, rail `shape`s, and the `north`, `east`,
+    /// `south`, and `west` connection properties. Additionally, `left` and `right` are swapped in
+    /// door `hinge`s, stair `shape`s, and chest `type`s. Vertical properties like `half` are left
+    /// as is.
+    pub fn mirror_block_state(self, state: &mut GenericBlockState) {
+        if self == Mirror::None {
+            return;
+        }
+        transform_properties(&mut state.properties, |key, value| match key {
+            "facing" | "orientation" => map_directions(value, |dir| self.mirror_direction(dir)),
+            "shape" => map_directions(value, |dir| self.mirror_direction(dir))
+                .or_else(|| swap_sides(value)),
+            "hinge" | "type" => swap_sides(value),
+            "rotation" => {
+                let rotation = value.parse::<usize>().ok()?;
+                Some(
+                    match self {
+                        Mirror::LeftRight => (24 - rotation) % 16,
+                        _ => (16 - rotation) % 16,
+                    }
+                    .to_string()
+                    .into(),
+                )
+            }
+            _ => None,
+        });
+        transform_connections(&mut state.properties, |dir| self.mirror_direction(dir));
+    }
+
+    /// Mirror an entity yaw in degrees.
+    pub fn mirror_yaw(self, yaw: f32) -> f32 {
+        match self {
+            Mirror::None => yaw,
+            Mirror::LeftRight => (180.0 - yaw).rem_euclid(360.0),
+            Mirror::FrontBack => (-yaw).rem_euclid(360.0),
+        }
+    }
+}
+
+/// A linear transformation of the horizontal plane.
+pub(crate) trait Transformation: Copy {
+    /// Apply this transformation to horizontal coordinates around the origin.
+    fn apply<T: Neg<Output = T>>(self, x: T, z: T) -> (T, T);
+
+    /// Apply this transformation to continuous horizontal coordinates, like entity positions.
+    ///
+    /// Unlike [`Self::apply`], this transforms around the center of the block at the origin, so
+    /// that everything inside a block ends up inside the block its position is moved to by
+    /// [`Self::apply`]. This matches how litematica transforms entities.
+    fn apply_continuous(self, x: f64, z: f64) -> (f64, f64);
+
+    /// Transform the properties of a block state.
+    fn block_state(self, state: &mut GenericBlockState);
+
+    /// Transform an entity yaw in degrees.
+    fn yaw(self, yaw: f32) -> f32;
+}
+
+impl Transformation for Rotation {
+    fn apply<T: Neg<Output = T>>(self, x: T, z: T) -> (T, T) {
+        match self {
+            Rotation::None => (x, z),
+            Rotation::Clockwise90 => (-z, x),
+            Rotation::Clockwise180 => (-x, -z),
+            Rotation::CounterClockwise90 => (z, -x),
+        }
+    }
+
+    fn apply_continuous(self, x: f64, z: f64) -> (f64, f64) {
+        match self {
+            Rotation::None => (x, z),
+            Rotation::Clockwise90 => (1.0 - z, x),
+            Rotation::Clockwise180 => (1.0 - x, 1.0 - z),
+            Rotation::CounterClockwise90 => (z, 1.0 - x),
+        }
+    }
+
+    fn block_state(self, state: &mut GenericBlockState) {
+        self.rotate_block_state(state)
+    }
+
+    fn yaw(self, yaw: f32) -> f32 {
+        self.rotate_yaw(yaw)
+    }
+}
+
+impl Transformation for Mirror {
+    fn apply<T: Neg<Output = T>>(self, x: T, z: T) -> (T, T) {
+        match self {
+            Mirror::None => (x, z),
+            Mirror::LeftRight => (x, -z),
+            Mirror::FrontBack => (-x, z),
+        }
+    }
+
+    fn apply_continuous(self, x: f64, z: f64) -> (f64, f64) {
+        match self {
+            Mirror::None => (x, z),
+            Mirror::LeftRight => (x, 1.0 - z),
+            Mirror::FrontBack => (1.0 - x, z),
+        }
+    }
+
+    fn block_state(self, state: &mut GenericBlockState) {
+        self.mirror_block_state(state)
+    }
+
+    fn yaw(self, yaw: f32) -> f32 {
+        self.mirror_yaw(yaw)
+    }
+}
+
+/// Replace the values of all properties for which `f` returns `Some`.
+fn transform_properties(
+    properties: &mut HashMap<CowStr, CowStr>,
+    f: impl Fn(&str, &str) -> Option<CowStr>,
+) {
+    for (key, value) in properties.iter_mut() {
+        if let Some(new) = f(key, value) {
+            *value = new;
+        }
+    }
+}
+
+/// Move the values of the `north`, `east`, `south`, and `west` properties according to `f`.
+fn transform_connections(
+    properties: &mut HashMap<CowStr, CowStr>,
+    f: impl Fn(&str) -> Option<&'static str>,
+) {
+    let connections = HORIZONTAL
+        .iter()
+        .filter_map(|dir| Some((*dir, properties.remove(*dir)?)))
+        .collect::<Vec<_>>();
+    for (dir, value) in connections {
+        properties.insert(f(dir).unwrap_or(dir).into(), value);
+    }
+}
+
+/// Map all `_` separated direction names in a property value like `ascending_east` or
+/// `south_west`.
+///
+/// Returns `None` if the value does not contain any direction. Rail shapes are kept in their
+/// canonical order, e.g. `west_south` becomes `south_west`.
+fn map_directions(value: &str, f: impl Fn(&str) -> Option<&'static str>) -> Option<CowStr> {
+    let mut parts = value
+        .split('_')
+        .map(|part| f(part).ok_or(part))
+        .collect::<Vec<_>>();
+    if parts.iter().all(|part| part.is_err()) {
+        return None;
+    }
+    if let [Ok(first), Ok(second)] = parts[..] {
+        let order = |dir| {
+            ["north", "south", "east", "west"]
+                .iter()
+                .position(|d| *d == dir)
+        };
+        if order(first) > order(second) {
+            parts.swap(0, 1);
+        }
+    }
+    Some(
+        parts
+            .into_iter()
+            .map(|part| part.unwrap_or_else(|part| part))
+            .collect::<Vec<_>>()
+            .join("_")
+            .into(),
+    )
+}
+
+/// Swap all `_` separated `left` and `right` parts in a property value like `inner_left`.
+///
+/// Returns `None` if the value does not contain any side.
+fn swap_sides(value: &str) -> Option<CowStr> {
+    let parts = value.split('_').collect::<Vec<_>>();
+    if !parts.iter().any(|part| *part == "left" || *part == "right") {
+        return None;
+    }
+    Some(
+        parts
+            .into_iter()
+            .map(|part| match part {
+                "left" => "right",
+                "right" => "left",
+                part => part,
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+            .into(),
+    )
+}
+
+/// Map a value through its [`GenericBlockState`] representation.
+///
+/// If the value cannot be represented as a [`GenericBlockState`] or the mapped state cannot be
+/// represented by `B`, the value is returned unchanged.
+pub(crate) fn map_block_state<B: Serialize + DeserializeOwned + Clone>(
+    state: &B,
+    f: impl FnOnce(&mut GenericBlockState),
+) -> B {
+    util::convert::<_, GenericBlockState>(state)
+        .and_then(|mut generic| {
+            f(&mut generic);
+            util::convert(&generic)
+        })
+        .unwrap_or_else(|_| state.clone())
+}
+
+/// Map a value through its raw NBT compound representation.
+///
+/// If the value is not an NBT compound or the mapped compound cannot be represented by `T`, the
+/// value is returned unchanged.
+pub(crate) fn map_nbt<T: Serialize + DeserializeOwned + Clone>(
+    value: &T,
+    f: impl FnOnce(&mut HashMap<String, Value>),
+) -> T {
+    match fastnbt::to_value(value) {
+        Ok(Value::Compound(mut map)) => {
+            f(&mut map);
+            fastnbt::from_value(&Value::Compound(map)).unwrap_or_else(|_| value.clone())
+        }
+        _ => value.clone(),
+    }
+}
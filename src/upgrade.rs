@@ -0,0 +1,401 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use fastnbt::Value;
+use mcdata::{
+    data_version::{MC_1_14, MC_1_16, MC_1_17, MC_1_20, MC_1_20_3, MC_1_20_5},
+    util::BlockPos,
+    GenericBlockEntity, GenericBlockState, GenericEntity,
+};
+
+use crate::{util, Litematic};
+
+/// Data version for Minecraft 1.21.
+pub(crate) const MC_1_21: i32 = 3953;
+/// Data version for Minecraft 1.21.4.
+pub(crate) const MC_1_21_4: i32 = 4189;
+/// Data version for Minecraft 1.21.5.
+pub(crate) const MC_1_21_5: i32 = 4325;
+/// Data version for Minecraft 1.21.6.
+pub(crate) const MC_1_21_6: i32 = 4435;
+/// Data version for Minecraft 1.21.9.
+pub(crate) const MC_1_21_9: i32 = 4554;
+
+/// The empty JSON text component used for sign messages.
+pub(crate) const EMPTY_TEXT: &str = r#""""#;
+
+/// Block entity and entity tags which contain item stacks.
+pub(crate) const ITEM_TAGS: &[&str] = &[
+    "Items",
+    "Item",
+    "item",
+    "RecordItem",
+    "Book",
+    "HandItems",
+    "ArmorItems",
+    "Inventory",
+    "SaddleItem",
+    "ArmorItem",
+    "DecorItem",
+    "FireworksItem",
+    "Offers",
+];
+
+/// Block entity and entity tags which contain JSON text components.
+pub(crate) const TEXT_TAGS: &[&str] = &["CustomName", "front_text", "back_text"];
+
+/// Entity tags which were merged into the `equipment` tag.
+pub(crate) const EQUIPMENT_TAGS: &[&str] =
+    &["HandItems", "ArmorItems", "body_armor_item", "SaddleItem"];
+
+/// The result of [`Litematic::upgrade`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct UpgradeReport {
+    /// The data version the schematic had before upgrading.
+    pub from: i32,
+
+    /// The data version the schematic has after upgrading.
+    pub to: i32,
+
+    /// Descriptions of all applied fixes in the order they were applied.
+    pub applied: Vec<&'static str>,
+
+    /// Everything that was affected by a change which couldn't be migrated.
+    pub unmigrated: Vec<Unmigrated>,
+}
+
+/// Something [`Litematic::upgrade`] couldn't migrate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unmigrated {
+    /// A palette entry of a region.
+    Block {
+        /// The name of the region.
+        region: String,
+        /// The block state.
+        block: GenericBlockState,
+        /// A description of the change that wasn't migrated.
+        change: &'static str,
+    },
+
+    /// A block entity.
+    BlockEntity {
+        /// The name of the region.
+        region: String,
+        /// The local position of the block entity.
+        pos: BlockPos,
+        /// The id of the block entity.
+        id: String,
+        /// A description of the change that wasn't migrated.
+        change: &'static str,
+    },
+
+    /// An entity.
+    Entity {
+        /// The name of the region.
+        region: String,
+        /// The UUID of the entity.
+        uuid: u128,
+        /// The id of the entity.
+        id: String,
+        /// A description of the change that wasn't migrated.
+        change: &'static str,
+    },
+}
+
+/// A single step in upgrading schematics, applied to schematics older than its data version.
+struct Fix {
+    data_version: i32,
+    description: &'static str,
+    kind: FixKind,
+}
+
+enum FixKind {
+    Blocks(fn(&mut GenericBlockState)),
+    BlockEntities(fn(&mut GenericBlockEntity)),
+    /// A change that isn't migrated. Everything matching is reported.
+    Unsupported {
+        block: fn(&GenericBlockState) -> bool,
+        block_entity: fn(&GenericBlockEntity) -> bool,
+        entity: fn(&GenericEntity) -> bool,
+    },
+}
+
+/// All known fixes, ordered by their data version.
+const FIXES: &[Fix] = &[
+    Fix {
+        data_version: util::FLATTENING_DATA_VERSION,
+        description: "flatten block ids and states",
+        kind: FixKind::Unsupported {
+            block: |state| state.name != "minecraft:air",
+            block_entity: |_| false,
+            entity: |_| false,
+        },
+    },
+    Fix {
+        data_version: MC_1_14,
+        description: "rename signs and smooth stone slabs",
+        kind: FixKind::Blocks(|state| {
+            rename(
+                state,
+                &[
+                    ("minecraft:sign", "minecraft:oak_sign"),
+                    ("minecraft:wall_sign", "minecraft:oak_wall_sign"),
+                    ("minecraft:stone_slab", "minecraft:smooth_stone_slab"),
+                ],
+            )
+        }),
+    },
+    Fix {
+        data_version: MC_1_16,
+        description: "use low and tall wall sides",
+        kind: FixKind::Blocks(|state| {
+            if state.name.ends_with("_wall") {
+                for side in ["north", "east", "south", "west"] {
+                    if let Some(value) = state.properties.get_mut(side) {
+                        match value.as_ref() {
+                            "true" => *value = "low".into(),
+                            "false" => *value = "none".into(),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }),
+    },
+    Fix {
+        data_version: MC_1_16,
+        description: "replace jigsaw facing with orientation",
+        kind: FixKind::Blocks(|state| {
+            if state.name == "minecraft:jigsaw" {
+                if let Some(facing) = state.properties.remove("facing") {
+                    let orientation = match facing.as_ref() {
+                        "down" => "down_south",
+                        "up" => "up_north",
+                        "north" => "north_up",
+                        "south" => "south_up",
+                        "west" => "west_up",
+                        _ => "east_up",
+                    };
+                    state
+                        .properties
+                        .insert("orientation".into(), orientation.into());
+                }
+            }
+        }),
+    },
+    Fix {
+        data_version: MC_1_17,
+        description: "rename grass paths and split filled cauldrons",
+        kind: FixKind::Blocks(|state| {
+            rename(state, &[("minecraft:grass_path", "minecraft:dirt_path")]);
+            if state.name == "minecraft:cauldron" {
+                match state.properties.remove("level").as_deref() {
+                    None | Some("0") => {}
+                    Some(level) => {
+                        let level = level.to_owned();
+                        state.name = "minecraft:water_cauldron".into();
+                        state.properties.insert("level".into(), level.into());
+                    }
+                }
+            }
+        }),
+    },
+    Fix {
+        data_version: MC_1_20,
+        description: "make sign text double sided",
+        kind: FixKind::BlockEntities(double_sided_sign),
+    },
+    Fix {
+        data_version: MC_1_20_3,
+        description: "rename grass to short grass",
+        kind: FixKind::Blocks(|state| {
+            rename(state, &[("minecraft:grass", "minecraft:short_grass")]);
+        }),
+    },
+    Fix {
+        data_version: MC_1_20_5,
+        description: "store item stacks with data components",
+        kind: FixKind::Unsupported {
+            block: |_| false,
+            block_entity: |block_entity| has_tag(&block_entity.properties, ITEM_TAGS),
+            entity: |entity| has_tag(&entity.properties, ITEM_TAGS),
+        },
+    },
+    Fix {
+        data_version: MC_1_21_5,
+        description: "store text components as NBT",
+        kind: FixKind::Unsupported {
+            block: |_| false,
+            block_entity: |block_entity| has_tag(&block_entity.properties, TEXT_TAGS),
+            entity: |entity| has_tag(&entity.properties, TEXT_TAGS),
+        },
+    },
+    Fix {
+        data_version: MC_1_21_5,
+        description: "merge entity equipment",
+        kind: FixKind::Unsupported {
+            block: |_| false,
+            block_entity: |_| false,
+            entity: |entity| has_tag(&entity.properties, EQUIPMENT_TAGS),
+        },
+    },
+    Fix {
+        data_version: MC_1_21_9,
+        description: "rename chains to iron chains",
+        kind: FixKind::Blocks(|state| {
+            rename(state, &[("minecraft:chain", "minecraft:iron_chain")]);
+        }),
+    },
+];
+
+impl Litematic<GenericBlockState, GenericEntity, GenericBlockEntity> {
+    /// Upgrade the blocks, block entities, and entities of this schematic from its
+    /// [Minecraft data version](crate::LitematicMetadata::minecraft_data_version) to a newer one,
+    /// similar to Minecraft's DataFixer.
+    ///
+    /// The fixes for all changes in between are applied step by step and the data version of the
+    /// schematic is updated. Changes which can't be migrated, like the item stack format of 1.20.5,
+    /// are listed in the returned report together with everything they affect. If the schematic
+    /// already has the given data version or a newer one, nothing is changed.
+    pub fn upgrade(&mut self, data_version: i32) -> UpgradeReport {
+        let from = self.metadata.minecraft_data_version;
+        let mut report = UpgradeReport {
+            from,
+            to: from.max(data_version),
+            ..Default::default()
+        };
+        for fix in FIXES
+            .iter()
+            .filter(|fix| from < fix.data_version && fix.data_version <= data_version)
+        {
+            match fix.kind {
+                FixKind::Blocks(f) => {
+                    report.applied.push(fix.description);
+                    for region in self.regions.iter_mut() {
+                        region.map_palette(|state| {
+                            let mut state = state.clone();
+                            f(&mut state);
+                            state
+                        });
+                    }
+                }
+                FixKind::BlockEntities(f) => {
+                    report.applied.push(fix.description);
+                    for region in self.regions.iter_mut() {
+                        region.block_entities.iter_mut().for_each(f);
+                    }
+                }
+                FixKind::Unsupported {
+                    block,
+                    block_entity,
+                    entity,
+                } => {
+                    for region in self.regions.iter() {
+                        let name = || region.name.clone().into_owned();
+                        report.unmigrated.extend(
+                            region
+                                .block_palette()
+                                .iter()
+                                .filter(|state| block(state))
+                                .map(|state| Unmigrated::Block {
+                                    region: name(),
+                                    block: state.clone(),
+                                    change: fix.description,
+                                }),
+                        );
+                        report.unmigrated.extend(
+                            region
+                                .block_entities
+                                .iter()
+                                .filter(|e| block_entity(e))
+                                .map(|e| Unmigrated::BlockEntity {
+                                    region: name(),
+                                    pos: e.pos,
+                                    id: e.id.clone().into_owned(),
+                                    change: fix.description,
+                                }),
+                        );
+                        report
+                            .unmigrated
+                            .extend(region.entities.iter().filter(|e| entity(e)).map(|e| {
+                                Unmigrated::Entity {
+                                    region: name(),
+                                    uuid: e.uuid,
+                                    id: e.id.clone().into_owned(),
+                                    change: fix.description,
+                                }
+                            }));
+                    }
+                }
+            }
+        }
+        self.metadata.minecraft_data_version = report.to;
+        report
+    }
+}
+
+pub(crate) fn rename(state: &mut GenericBlockState, renames: &[(&str, &'static str)]) {
+    if let Some((_, new)) = renames.iter().find(|(old, _)| state.name == *old) {
+        state.name = Cow::Borrowed(new);
+    }
+}
+
+pub(crate) fn has_tag(nbt: &HashMap<Cow<'static, str>, Value>, tags: &[&str]) -> bool {
+    tags.iter().any(|tag| match nbt.get(*tag) {
+        Some(Value::List(list)) => !list.is_empty(),
+        Some(Value::Compound(compound)) => !compound.is_empty(),
+        Some(_) => true,
+        None => false,
+    })
+}
+
+/// Move the `Text1` to `Text4` tags of signs into `front_text` and add an empty `back_text`.
+fn double_sided_sign(block_entity: &mut GenericBlockEntity) {
+    let nbt = &mut block_entity.properties;
+    if !(1..=4).any(|line| nbt.contains_key(format!("Text{line}").as_str())) {
+        return;
+    }
+
+    let empty_text = || Value::String(EMPTY_TEXT.to_owned());
+    let messages = (1..=4)
+        .map(|line| {
+            nbt.remove(format!("Text{line}").as_str())
+                .unwrap_or_else(empty_text)
+        })
+        .collect::<Vec<_>>();
+    let filtered_messages = (1..=4)
+        .map(|line| nbt.remove(format!("FilteredText{line}").as_str()))
+        .collect::<Vec<_>>();
+    let mut front_text = HashMap::from([
+        ("messages".to_owned(), Value::List(messages.clone())),
+        (
+            "color".to_owned(),
+            nbt.remove("Color")
+                .unwrap_or_else(|| Value::String("black".to_owned())),
+        ),
+        (
+            "has_glowing_text".to_owned(),
+            nbt.remove("GlowingText").unwrap_or(Value::Byte(0)),
+        ),
+    ]);
+    if filtered_messages.iter().any(Option::is_some) {
+        front_text.insert(
+            "filtered_messages".to_owned(),
+            Value::List(
+                filtered_messages
+                    .into_iter()
+                    .zip(messages)
+                    .map(|(filtered, message)| filtered.unwrap_or(message))
+                    .collect(),
+            ),
+        );
+    }
+    let back_text = HashMap::from([
+        ("messages".to_owned(), Value::List(vec![empty_text(); 4])),
+        ("color".to_owned(), Value::String("black".to_owned())),
+        ("has_glowing_text".to_owned(), Value::Byte(0)),
+    ]);
+
+    nbt.insert("front_text".into(), Value::Compound(front_text));
+    nbt.insert("back_text".into(), Value::Compound(back_text));
+    nbt.insert("is_waxed".into(), Value::Byte(0));
+}
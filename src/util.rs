@@ -1,3 +1,6 @@
+/// The Minecraft data version of 1.13, which flattened block ids and states.
+pub(crate) const FLATTENING_DATA_VERSION: i32 = 1519;
+
 #[cfg(feature = "chrono")]
 pub(crate) fn current_time() -> chrono::DateTime<chrono::Utc> {
     chrono::Utc::now()
@@ -15,3 +18,469 @@ pub(crate) fn current_time() -> i64 {
 pub(crate) fn current_time() -> i64 {
     js_sys::Date::now() as i64
 }
+
+/// Convert between two serializable representations of the same NBT data.
+pub(crate) fn convert<T: serde::Serialize, U: serde::de::DeserializeOwned>(
+    value: &T,
+) -> fastnbt::error::Result<U> {
+    fastnbt::from_value(&fastnbt::to_value(value)?)
+}
+
+#[cfg(feature = "chrono")]
+pub(crate) fn time_from_millis(millis: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+    chrono::Utc.timestamp_millis_opt(millis).single()
+}
+
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn time_from_millis(millis: i64) -> Option<i64> {
+    Some(millis)
+}
+
+#[cfg(feature = "chrono")]
+pub(crate) fn time_to_millis(time: &chrono::DateTime<chrono::Utc>) -> i64 {
+    time.timestamp_millis()
+}
+
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn time_to_millis(time: &i64) -> i64 {
+    *time
+}
+
+/// Hash the serialized representation of a value, e.g. the name and properties of a block state.
+///
+/// The value is fed to the hasher while serializing, without building an NBT
+/// [`Value`](fastnbt::Value) first. Map entries are combined independently of their order, so
+/// values that compare equal produce the same hash even if they are backed by a
+/// [`HashMap`](std::collections::HashMap). Values that cannot be serialized all hash to `0`.
+pub(crate) fn hash_serialized<T: serde::Serialize>(value: &T) -> u64 {
+    use std::hash::{DefaultHasher, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    match value.serialize(hash::HashSerializer(&mut hasher)) {
+        Ok(()) => hasher.finish(),
+        Err(_) => 0,
+    }
+}
+
+mod hash {
+    use std::{
+        fmt,
+        hash::{DefaultHasher, Hash, Hasher},
+    };
+
+    use serde::{ser, Serialize};
+
+    /// A serializer which feeds everything it is given to a hasher.
+    pub(super) struct HashSerializer<'a>(pub(super) &'a mut DefaultHasher);
+
+    #[derive(Debug)]
+    pub(super) struct Error;
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("value cannot be hashed")
+        }
+    }
+
+    impl std::error::Error for Error {}
+
+    impl ser::Error for Error {
+        fn custom<T: fmt::Display>(_msg: T) -> Self {
+            Error
+        }
+    }
+
+    /// The kinds of serialized data, hashed before the data itself so that e.g. a byte and an int
+    /// with the same value hash differently.
+    #[derive(Hash)]
+    enum Kind {
+        Bool,
+        I8,
+        I16,
+        I32,
+        I64,
+        I128,
+        U8,
+        U16,
+        U32,
+        U64,
+        U128,
+        F32,
+        F64,
+        Char,
+        Str,
+        Bytes,
+        None,
+        Some,
+        Unit,
+        Variant,
+        Newtype,
+        Seq,
+        Map,
+    }
+
+    impl HashSerializer<'_> {
+        fn write(self, kind: Kind, value: impl Hash) -> Result<(), Error> {
+            kind.hash(self.0);
+            value.hash(self.0);
+            Ok(())
+        }
+    }
+
+    impl<'a> ser::Serializer for HashSerializer<'a> {
+        type Ok = ();
+        type Error = Error;
+        type SerializeSeq = Seq<'a>;
+        type SerializeTuple = Seq<'a>;
+        type SerializeTupleStruct = Seq<'a>;
+        type SerializeTupleVariant = Seq<'a>;
+        type SerializeMap = Map<'a>;
+        type SerializeStruct = Map<'a>;
+        type SerializeStructVariant = Map<'a>;
+
+        fn serialize_bool(self, v: bool) -> Result<(), Error> {
+            self.write(Kind::Bool, v)
+        }
+
+        fn serialize_i8(self, v: i8) -> Result<(), Error> {
+            self.write(Kind::I8, v)
+        }
+
+        fn serialize_i16(self, v: i16) -> Result<(), Error> {
+            self.write(Kind::I16, v)
+        }
+
+        fn serialize_i32(self, v: i32) -> Result<(), Error> {
+            self.write(Kind::I32, v)
+        }
+
+        fn serialize_i64(self, v: i64) -> Result<(), Error> {
+            self.write(Kind::I64, v)
+        }
+
+        fn serialize_i128(self, v: i128) -> Result<(), Error> {
+            self.write(Kind::I128, v)
+        }
+
+        fn serialize_u8(self, v: u8) -> Result<(), Error> {
+            self.write(Kind::U8, v)
+        }
+
+        fn serialize_u16(self, v: u16) -> Result<(), Error> {
+            self.write(Kind::U16, v)
+        }
+
+        fn serialize_u32(self, v: u32) -> Result<(), Error> {
+            self.write(Kind::U32, v)
+        }
+
+        fn serialize_u64(self, v: u64) -> Result<(), Error> {
+            self.write(Kind::U64, v)
+        }
+
+        fn serialize_u128(self, v: u128) -> Result<(), Error> {
+            self.write(Kind::U128, v)
+        }
+
+        fn serialize_f32(self, v: f32) -> Result<(), Error> {
+            self.write(Kind::F32, v.to_bits())
+        }
+
+        fn serialize_f64(self, v: f64) -> Result<(), Error> {
+            self.write(Kind::F64, v.to_bits())
+        }
+
+        fn serialize_char(self, v: char) -> Result<(), Error> {
+            self.write(Kind::Char, v)
+        }
+
+        fn serialize_str(self, v: &str) -> Result<(), Error> {
+            self.write(Kind::Str, v)
+        }
+
+        fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+            self.write(Kind::Bytes, v)
+        }
+
+        fn serialize_none(self) -> Result<(), Error> {
+            self.write(Kind::None, ())
+        }
+
+        fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+            Kind::Some.hash(self.0);
+            value.serialize(self)
+        }
+
+        fn serialize_unit(self) -> Result<(), Error> {
+            self.write(Kind::Unit, ())
+        }
+
+        fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+            self.write(Kind::Unit, name)
+        }
+
+        fn serialize_unit_variant(
+            self,
+            _name: &'static str,
+            variant_index: u32,
+            _variant: &'static str,
+        ) -> Result<(), Error> {
+            self.write(Kind::Variant, variant_index)
+        }
+
+        fn serialize_newtype_struct<T: ?Sized + Serialize>(
+            self,
+            name: &'static str,
+            value: &T,
+        ) -> Result<(), Error> {
+            Kind::Newtype.hash(self.0);
+            name.hash(self.0);
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(
+            self,
+            _name: &'static str,
+            variant_index: u32,
+            _variant: &'static str,
+            value: &T,
+        ) -> Result<(), Error> {
+            Kind::Variant.hash(self.0);
+            variant_index.hash(self.0);
+            value.serialize(self)
+        }
+
+        fn serialize_seq(self, _len: Option<usize>) -> Result<Seq<'a>, Error> {
+            Kind::Seq.hash(self.0);
+            Ok(Seq {
+                hasher: self.0,
+                len: 0,
+            })
+        }
+
+        fn serialize_tuple(self, len: usize) -> Result<Seq<'a>, Error> {
+            self.serialize_seq(Some(len))
+        }
+
+        fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Seq<'a>, Error> {
+            name.hash(self.0);
+            self.serialize_seq(Some(len))
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _name: &'static str,
+            variant_index: u32,
+            _variant: &'static str,
+            len: usize,
+        ) -> Result<Seq<'a>, Error> {
+            Kind::Variant.hash(self.0);
+            variant_index.hash(self.0);
+            self.serialize_seq(Some(len))
+        }
+
+        fn serialize_map(self, _len: Option<usize>) -> Result<Map<'a>, Error> {
+            Kind::Map.hash(self.0);
+            Ok(Map {
+                hasher: self.0,
+                combined: 0,
+                entry: DefaultHasher::new(),
+            })
+        }
+
+        fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Map<'a>, Error> {
+            self.serialize_map(Some(len))
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _name: &'static str,
+            variant_index: u32,
+            _variant: &'static str,
+            len: usize,
+        ) -> Result<Map<'a>, Error> {
+            Kind::Variant.hash(self.0);
+            variant_index.hash(self.0);
+            self.serialize_map(Some(len))
+        }
+    }
+
+    /// Hashes elements in order.
+    pub(super) struct Seq<'a> {
+        hasher: &'a mut DefaultHasher,
+        len: usize,
+    }
+
+    impl Seq<'_> {
+        fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+            self.len += 1;
+            value.serialize(HashSerializer(self.hasher))
+        }
+
+        fn finish(self) -> Result<(), Error> {
+            self.len.hash(self.hasher);
+            Ok(())
+        }
+    }
+
+    impl ser::SerializeSeq for Seq<'_> {
+        type Ok = ();
+        type Error = Error;
+
+        fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+            self.element(value)
+        }
+
+        fn end(self) -> Result<(), Error> {
+            self.finish()
+        }
+    }
+
+    impl ser::SerializeTuple for Seq<'_> {
+        type Ok = ();
+        type Error = Error;
+
+        fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+            self.element(value)
+        }
+
+        fn end(self) -> Result<(), Error> {
+            self.finish()
+        }
+    }
+
+    impl ser::SerializeTupleStruct for Seq<'_> {
+        type Ok = ();
+        type Error = Error;
+
+        fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+            self.element(value)
+        }
+
+        fn end(self) -> Result<(), Error> {
+            self.finish()
+        }
+    }
+
+    impl ser::SerializeTupleVariant for Seq<'_> {
+        type Ok = ();
+        type Error = Error;
+
+        fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+            self.element(value)
+        }
+
+        fn end(self) -> Result<(), Error> {
+            self.finish()
+        }
+    }
+
+    /// Hashes every entry on its own and combines the entry hashes independently of their order.
+    pub(super) struct Map<'a> {
+        hasher: &'a mut DefaultHasher,
+        combined: u64,
+        entry: DefaultHasher,
+    }
+
+    impl Map<'_> {
+        fn field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+            ser::SerializeMap::serialize_key(self, key)?;
+            ser::SerializeMap::serialize_value(self, value)
+        }
+
+        fn finish(self) -> Result<(), Error> {
+            self.combined.hash(self.hasher);
+            Ok(())
+        }
+    }
+
+    impl ser::SerializeMap for Map<'_> {
+        type Ok = ();
+        type Error = Error;
+
+        fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+            self.entry = DefaultHasher::new();
+            key.serialize(HashSerializer(&mut self.entry))
+        }
+
+        fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+            value.serialize(HashSerializer(&mut self.entry))?;
+            self.combined = self.combined.wrapping_add(self.entry.finish());
+            Ok(())
+        }
+
+        fn end(self) -> Result<(), Error> {
+            self.finish()
+        }
+    }
+
+    impl ser::SerializeStruct for Map<'_> {
+        type Ok = ();
+        type Error = Error;
+
+        fn serialize_field<T: ?Sized + Serialize>(
+            &mut self,
+            key: &'static str,
+            value: &T,
+        ) -> Result<(), Error> {
+            self.field(key, value)
+        }
+
+        fn end(self) -> Result<(), Error> {
+            self.finish()
+        }
+    }
+
+    impl ser::SerializeStructVariant for Map<'_> {
+        type Ok = ();
+        type Error = Error;
+
+        fn serialize_field<T: ?Sized + Serialize>(
+            &mut self,
+            key: &'static str,
+            value: &T,
+        ) -> Result<(), Error> {
+            self.field(key, value)
+        }
+
+        fn end(self) -> Result<(), Error> {
+            self.finish()
+        }
+    }
+}
+
+/// Read the `Pos` of an entity.
+pub(crate) fn entity_pos(entity: &impl serde::Serialize) -> Option<[f64; 3]> {
+    use fastnbt::Value;
+
+    match fastnbt::to_value(entity).ok()? {
+        Value::Compound(mut nbt) => match nbt.remove("Pos")? {
+            Value::List(pos) => match pos[..] {
+                [Value::Double(x), Value::Double(y), Value::Double(z)] => Some([x, y, z]),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The largest volume of a region whose blocks aren't stored in the file it is read from, e.g. a
+/// Sponge schematic without block data or the structure voids of a structure.
+///
+/// The blocks still have to be allocated, so without a limit a tiny file could claim a size that
+/// takes gigabytes.
+pub(crate) const MAX_UNBACKED_VOLUME: usize = 1 << 24;
+
+/// Compute the number of blocks in a box of the given size.
+///
+/// Unlike [`BlockPos::volume`](mcdata::util::BlockPos::volume), this returns `None` instead of
+/// overflowing.
+pub(crate) fn volume(size: mcdata::util::BlockPos) -> Option<usize> {
+    size.x
+        .unsigned_abs()
+        .checked_mul(size.y.unsigned_abs())?
+        .checked_mul(size.z.unsigned_abs())
+        .map(|volume| volume as usize)
+}
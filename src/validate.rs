@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+
+use fastnbt::Value;
+use mcdata::util::BlockPos;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{util, Litematic, MetadataTotals, Region};
+
+/// A problem with a schematic found by [`Litematic::validate`].
+///
+/// Every issue can be fixed automatically using [`Litematic::repair`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ValidationIssue {
+    /// Multiple regions have the same name.
+    ///
+    /// Repaired by [making the names unique](Litematic::make_region_names_unique).
+    #[error("multiple regions are named '{name}'")]
+    DuplicateRegionName {
+        /// The duplicated name.
+        name: String,
+    },
+
+    /// The first palette entry of a region is not air.
+    ///
+    /// Repaired by moving air to the front of the palette.
+    #[error("first palette entry of region '{region}' is not air")]
+    AirNotFirstInPalette {
+        /// The name of the region.
+        region: String,
+    },
+
+    /// A block entity is outside of its region.
+    ///
+    /// Repaired by removing the block entity.
+    #[error("block entity at {pos:?} is outside of region '{region}'")]
+    BlockEntityOutOfBounds {
+        /// The name of the region.
+        region: String,
+        /// The local position of the block entity.
+        pos: BlockPos,
+    },
+
+    /// A block entity is at a position without a block.
+    ///
+    /// Repaired by removing the block entity.
+    #[error("block entity at {pos:?} in region '{region}' is on air")]
+    BlockEntityOnAir {
+        /// The name of the region.
+        region: String,
+        /// The local position of the block entity.
+        pos: BlockPos,
+    },
+
+    /// A block entity does not belong to the block at its position.
+    ///
+    /// Repaired by removing the block entity.
+    #[error("block entity '{id}' at {pos:?} in region '{region}' is on '{block}'")]
+    BlockEntityMismatch {
+        /// The name of the region.
+        region: String,
+        /// The local position of the block entity.
+        pos: BlockPos,
+        /// The id of the block entity.
+        id: String,
+        /// The name of the block at the position.
+        block: String,
+    },
+
+    /// An entity is outside of its region.
+    ///
+    /// Litematica saves all entities whose hitbox intersects a region, so entities may be up to
+    /// [`ENTITY_MARGIN`] blocks outside of the region before this is reported.
+    ///
+    /// Repaired by removing the entity.
+    #[error("entity at {pos:?} is outside of region '{region}'")]
+    EntityOutOfBounds {
+        /// The name of the region.
+        region: String,
+        /// The position of the entity relative to the [region position](Region::position).
+        pos: [f64; 3],
+    },
+
+    /// A pending block tick is outside of its region.
+    ///
+    /// Repaired by removing the tick.
+    #[error("pending block tick at {pos:?} is outside of region '{region}'")]
+    PendingBlockTickOutOfBounds {
+        /// The name of the region.
+        region: String,
+        /// The local position of the tick.
+        pos: BlockPos,
+    },
+
+    /// A pending fluid tick is outside of its region.
+    ///
+    /// Repaired by removing the tick.
+    #[error("pending fluid tick at {pos:?} is outside of region '{region}'")]
+    PendingFluidTickOutOfBounds {
+        /// The name of the region.
+        region: String,
+        /// The local position of the tick.
+        pos: BlockPos,
+    },
+
+    /// The [stored metadata totals](crate::LitematicMetadata::stored_totals) don't match the
+    /// schematic contents.
+    ///
+    /// Repaired by replacing them with the recomputed values.
+    #[error("stored metadata totals {stored:?} don't match the computed totals {computed:?}")]
+    TotalsMismatch {
+        /// The totals stored in the metadata.
+        stored: MetadataTotals,
+        /// The totals computed from the regions.
+        computed: MetadataTotals,
+    },
+}
+
+impl<BlockState, Entity, BlockEntity> Litematic<BlockState, Entity, BlockEntity>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    /// Check this schematic for inconsistencies.
+    ///
+    /// Returns a list of all found issues, which is empty if the schematic is valid. Whether block
+    /// entities belong to the blocks at their positions is only checked for Minecraft 1.13 and
+    /// newer and only for vanilla block entities.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = vec![];
+        let mut names = HashSet::new();
+        let mut duplicates = HashSet::new();
+        for region in self.regions.iter() {
+            if !names.insert(&region.name) && duplicates.insert(&region.name) {
+                issues.push(ValidationIssue::DuplicateRegionName {
+                    name: region.name.clone().into_owned(),
+                });
+            }
+        }
+        for region in self.regions.iter() {
+            issues.extend(region_issues(
+                region,
+                self.metadata.minecraft_data_version >= util::FLATTENING_DATA_VERSION,
+            ));
+        }
+        if let Some(stored) = self.metadata.stored_totals {
+            let computed = self.totals();
+            if stored != computed {
+                issues.push(ValidationIssue::TotalsMismatch { stored, computed });
+            }
+        }
+        issues
+    }
+
+    /// Fix all issues found by [`Self::validate`].
+    ///
+    /// Invalid block entities, entities, and pending ticks are removed. Returns the list of
+    /// repaired issues.
+    pub fn repair(&mut self) -> Vec<ValidationIssue> {
+        let issues = self.validate();
+        if issues.is_empty() {
+            return issues;
+        }
+
+        self.make_region_names_unique();
+        let check_ids = self.metadata.minecraft_data_version >= util::FLATTENING_DATA_VERSION;
+        for region in self.regions.iter_mut() {
+            region.move_air_to_front();
+            let air = BlockState::air();
+            region.block_entities = std::mem::take(&mut region.block_entities)
+                .into_iter()
+                .filter(|block_entity| {
+                    let pos = block_entity.position();
+                    in_block_bounds(region.size, pos) && {
+                        let block = region.get_block(pos);
+                        block != &air
+                            && !(check_ids && block_entity_mismatch(block_entity, block).is_some())
+                    }
+                })
+                .collect();
+            let bounds = entity_bounds(region);
+            region.entities.retain(|entity| {
+                util::entity_pos(entity).is_none_or(|pos| in_entity_bounds(pos, bounds))
+            });
+            let size = region.size;
+            region
+                .pending_block_ticks
+                .retain(|tick| in_block_bounds(size, BlockPos::new(tick.x, tick.y, tick.z)));
+            region
+                .pending_fluid_ticks
+                .retain(|tick| in_block_bounds(size, BlockPos::new(tick.x, tick.y, tick.z)));
+        }
+        if self.metadata.stored_totals.is_some() {
+            self.metadata.stored_totals = Some(self.totals());
+        }
+        issues
+    }
+
+    /// Compute the metadata totals of this schematic.
+    fn totals(&self) -> MetadataTotals {
+        MetadataTotals {
+            region_count: self.regions.len() as i32,
+            total_volume: self.total_volume(),
+            total_blocks: self.total_blocks(),
+            enclosing_size: self.enclosing_size(),
+        }
+    }
+}
+
+fn region_issues<BlockState, Entity, BlockEntity>(
+    region: &Region<BlockState, Entity, BlockEntity>,
+    check_ids: bool,
+) -> Vec<ValidationIssue>
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    let name = || region.name.clone().into_owned();
+    let mut issues = vec![];
+    let air = BlockState::air();
+
+    if region.block_palette().first() != Some(&air) {
+        issues.push(ValidationIssue::AirNotFirstInPalette { region: name() });
+    }
+
+    for block_entity in region.block_entities.iter() {
+        let pos = block_entity.position();
+        if !in_block_bounds(region.size, pos) {
+            issues.push(ValidationIssue::BlockEntityOutOfBounds {
+                region: name(),
+                pos,
+            });
+            continue;
+        }
+        let block = region.get_block(pos);
+        if block == &air {
+            issues.push(ValidationIssue::BlockEntityOnAir {
+                region: name(),
+                pos,
+            });
+        } else if let Some((id, block)) = check_ids
+            .then(|| block_entity_mismatch(block_entity, block))
+            .flatten()
+        {
+            issues.push(ValidationIssue::BlockEntityMismatch {
+                region: name(),
+                pos,
+                id,
+                block,
+            });
+        }
+    }
+
+    let bounds = entity_bounds(region);
+    for entity in region.entities.iter() {
+        if let Some(pos) = util::entity_pos(entity).filter(|pos| !in_entity_bounds(*pos, bounds)) {
+            issues.push(ValidationIssue::EntityOutOfBounds {
+                region: name(),
+                pos,
+            });
+        }
+    }
+
+    for tick in region.pending_block_ticks.iter() {
+        let pos = BlockPos::new(tick.x, tick.y, tick.z);
+        if !in_block_bounds(region.size, pos) {
+            issues.push(ValidationIssue::PendingBlockTickOutOfBounds {
+                region: name(),
+                pos,
+            });
+        }
+    }
+    for tick in region.pending_fluid_ticks.iter() {
+        let pos = BlockPos::new(tick.x, tick.y, tick.z);
+        if !in_block_bounds(region.size, pos) {
+            issues.push(ValidationIssue::PendingFluidTickOutOfBounds {
+                region: name(),
+                pos,
+            });
+        }
+    }
+
+    issues
+}
+
+/// Like [`Region::is_in_bounds`], but also `false` for regions without any blocks.
+fn in_block_bounds(size: BlockPos, pos: BlockPos) -> bool {
+    let size = size.abs();
+    (0..size.x).contains(&pos.x) && (0..size.y).contains(&pos.y) && (0..size.z).contains(&pos.z)
+}
+
+/// How far entities may be outside of their region, see [`ValidationIssue::EntityOutOfBounds`].
+pub const ENTITY_MARGIN: f64 = 2.0;
+
+/// The minimum and maximum entity coordinates relative to the region position.
+fn entity_bounds<BlockState, Entity, BlockEntity>(
+    region: &Region<BlockState, Entity, BlockEntity>,
+) -> ([f64; 3], [f64; 3])
+where
+    BlockState: mcdata::BlockState + Serialize + DeserializeOwned,
+    Entity: mcdata::Entity + Serialize + DeserializeOwned,
+    BlockEntity: mcdata::BlockEntity + Serialize + DeserializeOwned,
+{
+    let pos = region.position;
+    (
+        [
+            (region.min_global_x() - pos.x) as f64 - ENTITY_MARGIN,
+            (region.min_global_y() - pos.y) as f64 - ENTITY_MARGIN,
+            (region.min_global_z() - pos.z) as f64 - ENTITY_MARGIN,
+        ],
+        [
+            (region.max_global_x() + 1 - pos.x) as f64 + ENTITY_MARGIN,
+            (region.max_global_y() + 1 - pos.y) as f64 + ENTITY_MARGIN,
+            (region.max_global_z() + 1 - pos.z) as f64 + ENTITY_MARGIN,
+        ],
+    )
+}
+
+fn in_entity_bounds(pos: [f64; 3], (min, max): ([f64; 3], [f64; 3])) -> bool {
+    (0..3).all(|axis| (min[axis]..=max[axis]).contains(&pos[axis]))
+}
+
+/// Read a string tag of a compound.
+fn string_tag(value: &impl Serialize, key: &str) -> Option<String> {
+    match fastnbt::to_value(value).ok()? {
+        Value::Compound(mut nbt) => match nbt.remove(key)? {
+            Value::String(string) => Some(string),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the block entity id and block name if a vanilla block entity doesn't belong to a block.
+fn block_entity_mismatch(
+    block_entity: &impl Serialize,
+    block: &impl Serialize,
+) -> Option<(String, String)> {
+    // some litematica versions didn't save block entity ids
+    let id = string_tag(block_entity, "id").filter(|id| !id.is_empty())?;
+    let name = string_tag(block, "Name")?;
+    let (Some(short_id), Some(short_name)) = (vanilla_name(&id), vanilla_name(&name)) else {
+        return None;
+    };
+    let matches = short_name == short_id
+        || short_name.ends_with(&format!("_{short_id}"))
+        || match short_id {
+            "mob_spawner" => short_name == "spawner",
+            "skull" => short_name.ends_with("_head") || short_name.ends_with("_skull"),
+            "beehive" => short_name == "bee_nest",
+            "piston" => short_name == "moving_piston",
+            "brushable_block" => short_name.starts_with("suspicious_"),
+            _ => false,
+        };
+    (!matches).then_some((id, name))
+}
+
+/// Strip the `minecraft:` namespace, returning `None` for other namespaces.
+fn vanilla_name(name: &str) -> Option<&str> {
+    match name.split_once(':') {
+        Some(("minecraft", name)) => Some(name),
+        Some(_) => None,
+        None => Some(name),
+    }
+}
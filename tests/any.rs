@@ -0,0 +1,28 @@
+use mcdata::util::BlockPos;
+use rustmatica::{AnyLitematic, Litematic, Region};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[test]
+fn read_typed() -> Result<()> {
+    let schem = AnyLitematic::read_file("test_files/axolotl.litematic")?;
+    assert_eq!(schem.metadata().minecraft_data_version, 2975);
+    #[cfg(feature = "mc-1.18")]
+    assert!(matches!(schem, AnyLitematic::Mc1_18(_)));
+    #[cfg(not(feature = "mc-1.18"))]
+    assert!(matches!(schem, AnyLitematic::Generic(_)));
+
+    let generic: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    assert_eq!(generic.metadata.name, schem.metadata().name);
+    Ok(())
+}
+
+#[test]
+fn unknown_version_is_generic() -> Result<()> {
+    let mut schem: Litematic =
+        Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(1, 1, 1)).as_litematic("", "");
+    schem.metadata.minecraft_data_version = 4554;
+    let schem = AnyLitematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    assert!(matches!(schem, AnyLitematic::Generic(_)));
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use fastnbt::{LongArray, Value};
+use mcdata::{util::BlockPos, GenericBlockState};
+use rustmatica::{Litematic, Region};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const SIZE: BlockPos = BlockPos::new(7, 5, 3);
+
+/// Unpack block states one bit at a time, like rustmatica did before using packed arrays.
+fn decode_bitwise(words: &[i64], bits: usize, len: usize) -> Vec<usize> {
+    words
+        .iter()
+        .flat_map(|word| (0..64).map(move |bit| word >> bit & 1))
+        .collect::<Vec<i64>>()
+        .chunks(bits)
+        .map(|slice| {
+            slice
+                .iter()
+                .rev()
+                .fold(0, |acc, bit| acc << 1 | *bit as usize)
+        })
+        .take(len)
+        .collect()
+}
+
+/// Pack block states one bit at a time, like rustmatica did before using packed arrays.
+fn encode_bitwise(ids: &[usize], bits: usize) -> Vec<i64> {
+    ids.iter()
+        .flat_map(|id| (0..bits).map(move |bit| id >> bit & 1))
+        .collect::<Vec<usize>>()
+        .chunks(64)
+        .map(|bits| bits.iter().rev().fold(0, |acc, bit| acc << 1 | *bit as i64))
+        .collect()
+}
+
+/// A deterministic sequence of pseudo random words.
+fn random_words(count: usize, seed: u64) -> Vec<i64> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            state as i64
+        })
+        .collect()
+}
+
+fn palette_name(id: usize) -> String {
+    match id {
+        0 => "minecraft:air".to_owned(),
+        id => format!("test:block_{id}"),
+    }
+}
+
+fn region_nbt(nbt: &mut Value) -> &mut HashMap<String, Value> {
+    let Value::Compound(root) = nbt else {
+        panic!("expected a compound");
+    };
+    let Some(Value::Compound(regions)) = root.get_mut("Regions") else {
+        panic!("expected regions");
+    };
+    let Some(Value::Compound(region)) = regions.get_mut("Region") else {
+        panic!("expected a region");
+    };
+    region
+}
+
+/// Read a region with the given number of palette entries and packed block states.
+fn read(palette_len: usize, words: &[i64]) -> Result<Litematic> {
+    let mut schem: Litematic = Litematic::new("Bits", "", "");
+    schem
+        .regions
+        .push(Region::new("Region", BlockPos::new(0, 0, 0), SIZE));
+    let mut nbt: Value = fastnbt::from_bytes(&schem.to_uncompressed_bytes()?)?;
+    let region = region_nbt(&mut nbt);
+    region.insert(
+        "BlockStatePalette".to_owned(),
+        Value::List(
+            (0..palette_len)
+                .map(|id| {
+                    Value::Compound(HashMap::from([(
+                        "Name".to_owned(),
+                        Value::String(palette_name(id)),
+                    )]))
+                })
+                .collect(),
+        ),
+    );
+    region.insert(
+        "BlockStates".to_owned(),
+        Value::LongArray(LongArray::new(words.to_vec())),
+    );
+    Ok(Litematic::from_uncompressed_bytes(&fastnbt::to_bytes(
+        &nbt,
+    )?)?)
+}
+
+/// Get the packed block states of the only region of a schematic as written to NBT.
+fn written_words(schem: &Litematic) -> Result<Vec<i64>> {
+    let mut nbt: Value = fastnbt::from_bytes(&schem.to_uncompressed_bytes()?)?;
+    match region_nbt(&mut nbt).remove("BlockStates") {
+        Some(Value::LongArray(words)) => Ok(words.into_inner()),
+        _ => panic!("expected block states"),
+    }
+}
+
+fn pos(index: usize) -> BlockPos {
+    let index = index as i32;
+    BlockPos::new(
+        index % SIZE.x,
+        index / (SIZE.x * SIZE.z),
+        index / SIZE.x % SIZE.z,
+    )
+}
+
+#[test]
+fn matches_bitwise_decoder() -> Result<()> {
+    let volume = SIZE.volume() as usize;
+    // 3, 5, 6, 7, and 9 bit entries span two words, 2 bit entries never do
+    for bits in [2, 3, 5, 6, 7, 9] {
+        let mut words = random_words((volume * bits).div_ceil(64), bits as u64);
+        // unused bits in the last word are kept as is, so clear them to compare with the encoder
+        let used = volume * bits % 64;
+        if let Some(last) = words.last_mut().filter(|_| used != 0) {
+            *last &= (1 << used) - 1;
+        }
+        let expected = decode_bitwise(&words, bits, volume);
+        let schem = read(1 << bits, &words)?;
+        let region = &schem.regions[0];
+        for (index, id) in expected.iter().enumerate() {
+            assert_eq!(
+                region.get_block(pos(index)).name,
+                palette_name(*id),
+                "{bits} bits, index {index}"
+            );
+        }
+        assert_eq!(
+            written_words(&schem)?,
+            encode_bitwise(&expected, bits),
+            "{bits} bits"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn entries_spanning_words() -> Result<()> {
+    // with 5 bits, the entry at index 12 uses the last 4 bits of the first word and the first bit
+    // of the second word
+    let mut ids = vec![0; SIZE.volume() as usize];
+    ids[12] = 0b10111;
+    ids[25] = 0b11001;
+    ids[38] = 0b01101;
+    let words = encode_bitwise(&ids, 5);
+    assert_eq!(words[0] as u64 >> 60, 0b0111);
+    assert_eq!(words[1] & 1, 1);
+
+    let mut schem = read(32, &words)?;
+    let region = &mut schem.regions[0];
+    for (index, id) in ids.iter().enumerate() {
+        assert_eq!(region.get_block(pos(index)).name, palette_name(*id));
+    }
+
+    // changing a spanning entry must not touch its neighbors
+    region.set_block(pos(12), block("test:block_8"));
+    region.set_block(pos(25), block("minecraft:air"));
+    ids[12] = 8;
+    ids[25] = 0;
+    for (index, id) in ids.iter().enumerate() {
+        assert_eq!(region.get_block(pos(index)).name, palette_name(*id));
+    }
+    assert_eq!(written_words(&schem)?, encode_bitwise(&ids, 5));
+    Ok(())
+}
+
+#[test]
+fn resize_bits() -> Result<()> {
+    let mut schem: Litematic = Litematic::new("Bits", "", "");
+    schem
+        .regions
+        .push(Region::new("Region", BlockPos::new(0, 0, 0), SIZE));
+    let volume = SIZE.volume() as usize;
+
+    // every new block grows the palette, which repacks the blocks at 3, 4, 5, and 6 bits
+    let names = (1..=40).map(palette_name).collect::<Vec<_>>();
+    let mut expected = vec![palette_name(0); volume];
+    for (step, name) in names.iter().enumerate() {
+        let index = step * 37 % volume;
+        schem.regions[0].set_block(
+            pos(index),
+            mcdata::GenericBlockState {
+                name: name.clone().into(),
+                properties: HashMap::new(),
+            },
+        );
+        expected[index] = name.clone();
+        for (index, name) in expected.iter().enumerate() {
+            assert_eq!(&schem.regions[0].get_block(pos(index)).name, name);
+        }
+    }
+
+    let words = written_words(&schem)?;
+    assert_eq!(words.len(), (volume * 6).div_ceil(64));
+    let read: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    for (index, name) in expected.iter().enumerate() {
+        assert_eq!(&read.regions[0].get_block(pos(index)).name, name);
+    }
+
+    // shrinking the palette repacks the blocks with fewer bits again
+    let region = &mut schem.regions[0];
+    for index in 0..volume {
+        if index % 2 == 0 {
+            region.set_block(pos(index), block("minecraft:stone"));
+        } else {
+            region.set_block(pos(index), block("minecraft:air"));
+        }
+    }
+    region.compact_palette(Default::default());
+    let words = written_words(&schem)?;
+    assert_eq!(words.len(), (volume * 2).div_ceil(64));
+    let ids = decode_bitwise(&words, 2, volume);
+    for (index, id) in ids.iter().enumerate() {
+        assert_eq!(*id, if index % 2 == 0 { 1 } else { 0 }, "{index}");
+    }
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use fastnbt::Value;
+use mcdata::{
+    util::{BlockPos, Cuboid},
+    GenericBlockEntity, GenericBlockState, GenericEntity,
+};
+use rustmatica::{Litematic, PasteMode, PasteOptions, PendingBlockTick, Region, ResizeAnchor};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+fn chest(pos: BlockPos) -> GenericBlockEntity {
+    GenericBlockEntity {
+        id: "minecraft:chest".into(),
+        pos,
+        properties: HashMap::new(),
+    }
+}
+
+fn tick(x: i32, y: i32, z: i32) -> PendingBlockTick {
+    PendingBlockTick {
+        block: "minecraft:observer".into(),
+        priority: 0,
+        sub_tick: 0,
+        time: 2,
+        x,
+        y,
+        z,
+    }
+}
+
+fn entity_pos(entity: &GenericEntity) -> Vec<f64> {
+    match &entity.properties["Pos"] {
+        Value::List(pos) => pos
+            .iter()
+            .map(|value| match value {
+                Value::Double(value) => *value,
+                _ => panic!("invalid entity position"),
+            })
+            .collect(),
+        _ => panic!("invalid entity position"),
+    }
+}
+
+fn source() -> Region {
+    // negative size, covering x and z from -3 to 0 and y from 10 to 13
+    let mut region: Region =
+        Region::new("Source", BlockPos::new(0, 10, 0), BlockPos::new(-4, 4, -4));
+    region.set_block(BlockPos::new(1, 1, 1), block("minecraft:chest"));
+    region.set_block(BlockPos::new(2, 1, 1), block("minecraft:observer"));
+    region.set_block(BlockPos::new(3, 3, 3), block("minecraft:stone"));
+    region.block_entities.push(chest(BlockPos::new(1, 1, 1)));
+    region.pending_block_ticks.push(tick(2, 1, 1));
+    region.entities.push(GenericEntity {
+        id: "minecraft:pig".into(),
+        uuid: 1,
+        // relative to the region position, so in the block at local (1, 1, 2)
+        properties: HashMap::from([(
+            "Pos".into(),
+            Value::List(vec![
+                Value::Double(-1.5),
+                Value::Double(1.0),
+                Value::Double(-0.5),
+            ]),
+        )]),
+    });
+    region
+}
+
+#[test]
+fn extract() {
+    let source = source();
+    let extracted = source.extract(Cuboid::new(BlockPos::new(1, 1, 1), BlockPos::new(2, 2, 2)));
+
+    assert_eq!(extracted.size, BlockPos::new(2, 2, 2));
+    assert_eq!(extracted.position, BlockPos::new(-2, 11, -2));
+    assert_eq!(
+        extracted.get_block(BlockPos::new(0, 0, 0)),
+        &block("minecraft:chest")
+    );
+    assert_eq!(
+        extracted.get_block(BlockPos::new(1, 0, 0)),
+        &block("minecraft:observer")
+    );
+    assert_eq!(extracted.total_blocks(), 2);
+    assert_eq!(extracted.block_palette().len(), 3);
+    // blocks keep their global coordinates
+    for (pos, block) in extracted.blocks() {
+        assert_eq!(block, source.get_block_global(extracted.pos_to_global(pos)));
+    }
+
+    assert_eq!(
+        extracted.block_entities,
+        vec![chest(BlockPos::new(0, 0, 0))]
+    );
+    assert_eq!(extracted.pending_block_ticks, vec![tick(1, 0, 0)]);
+    assert_eq!(extracted.entities.len(), 1);
+    assert_eq!(entity_pos(&extracted.entities[0]), vec![0.5, 0.0, 1.5]);
+
+    // clipped to the region
+    let clipped = source.extract(Cuboid::new(BlockPos::new(3, -2, 3), BlockPos::new(5, 8, 5)));
+    assert_eq!(clipped.size, BlockPos::new(1, 4, 1));
+    assert_eq!(
+        clipped.get_block(BlockPos::new(0, 3, 0)),
+        &block("minecraft:stone")
+    );
+    assert!(clipped.block_entities.is_empty());
+    assert!(clipped.entities.is_empty());
+
+    let empty = source.extract(Cuboid::new(BlockPos::new(5, 0, 0), BlockPos::new(1, 1, 1)));
+    assert_eq!(empty.size, BlockPos::new(0, 0, 0));
+}
+
+#[test]
+fn paste() {
+    let source = source();
+    let mut target: Region = Region::new("Target", BlockPos::new(0, 0, 0), BlockPos::new(5, 5, 5));
+    target.set_block(BlockPos::new(2, 2, 2), block("minecraft:barrel"));
+    target.block_entities.push(GenericBlockEntity {
+        id: "minecraft:barrel".into(),
+        pos: BlockPos::new(2, 2, 2),
+        properties: HashMap::new(),
+    });
+
+    let mut skipped = target.clone();
+    let pasted = skipped.paste(
+        &source,
+        BlockPos::new(1, 1, 1),
+        PasteOptions {
+            mode: PasteMode::SkipAir,
+            ..Default::default()
+        },
+    );
+    assert_eq!(pasted, 3);
+    assert_eq!(
+        skipped.get_block(BlockPos::new(4, 4, 4)),
+        &block("minecraft:stone")
+    );
+    assert_eq!(
+        skipped.get_block(BlockPos::new(2, 2, 2)),
+        &block("minecraft:chest")
+    );
+    assert_eq!(
+        skipped.get_block(BlockPos::new(3, 2, 2)),
+        &block("minecraft:observer")
+    );
+    // the barrel was replaced by the chest
+    assert_eq!(skipped.block_entities, vec![chest(BlockPos::new(2, 2, 2))]);
+    assert_eq!(skipped.pending_block_ticks, vec![tick(3, 2, 2)]);
+    assert_eq!(skipped.entities.len(), 1);
+    assert_eq!(entity_pos(&skipped.entities[0]), vec![2.5, 2.0, 3.5]);
+
+    let mut only_air = target.clone();
+    let pasted = only_air.paste(
+        &source,
+        BlockPos::new(1, 1, 1),
+        PasteOptions {
+            mode: PasteMode::ReplaceAir,
+            entities: false,
+        },
+    );
+    assert_eq!(pasted, 63);
+    assert_eq!(
+        only_air.get_block(BlockPos::new(2, 2, 2)),
+        &block("minecraft:barrel")
+    );
+    assert_eq!(only_air.block_entities, target.block_entities);
+    assert_eq!(only_air.pending_block_ticks, vec![tick(3, 2, 2)]);
+    assert!(only_air.entities.is_empty());
+
+    let mut replaced = target.clone();
+    let pasted = replaced.paste(&source, BlockPos::new(1, 1, 1), PasteOptions::default());
+    assert_eq!(pasted, 64);
+    assert_eq!(replaced.total_blocks(), 3);
+
+    // pasting an extracted part back at its original position changes nothing
+    let mut copy = source.clone();
+    let part = source.extract(Cuboid::new(BlockPos::new(1, 1, 1), BlockPos::new(3, 3, 3)));
+    copy.paste(&part, BlockPos::new(1, 1, 1), PasteOptions::default());
+    assert_eq!(
+        copy.blocks().collect::<Vec<_>>(),
+        source.blocks().collect::<Vec<_>>()
+    );
+    assert_eq!(copy.block_entities, source.block_entities);
+    assert_eq!(copy.pending_block_ticks, source.pending_block_ticks);
+    assert_eq!(copy.entities.len(), 2);
+    assert_eq!(
+        entity_pos(&copy.entities[1]),
+        entity_pos(&source.entities[0])
+    );
+}
+
+#[test]
+fn trim() {
+    let mut region = source();
+    region.trim();
+    assert_eq!(region.position, BlockPos::new(-2, 11, -2));
+    assert_eq!(region.size, BlockPos::new(3, 3, 3));
+    assert_eq!(
+        region.get_block_global(BlockPos::new(0, 13, 0)),
+        &block("minecraft:stone")
+    );
+    assert_eq!(region.block_entities, vec![chest(BlockPos::new(0, 0, 0))]);
+    assert_eq!(region.pending_block_ticks, vec![tick(1, 0, 0)]);
+    assert_eq!(entity_pos(&region.entities[0]), vec![0.5, 0.0, 1.5]);
+
+    let mut schem = source().as_litematic("", "");
+    schem.regions.push(Region::new(
+        "Empty",
+        BlockPos::new(5, 5, 5),
+        BlockPos::new(3, 3, 3),
+    ));
+    schem.trim();
+    assert_eq!(schem.regions.len(), 1);
+    assert_eq!(schem.regions[0], region);
+}
+
+#[test]
+fn resize() {
+    let source = source();
+    let globals = [
+        (BlockPos::new(-2, 11, -2), block("minecraft:chest")),
+        (BlockPos::new(-1, 11, -2), block("minecraft:observer")),
+        (BlockPos::new(0, 13, 0), block("minecraft:stone")),
+    ];
+
+    let mut grown = source.clone();
+    grown.resize(BlockPos::new(-6, 4, -4), ResizeAnchor::Position);
+    assert_eq!(grown.position, source.position);
+    assert_eq!(grown.min_global_x(), -5);
+    for (pos, block) in &globals {
+        assert_eq!(grown.get_block_global(*pos), block);
+    }
+    assert_eq!(grown.block_entities, vec![chest(BlockPos::new(3, 1, 1))]);
+    assert_eq!(grown.pending_block_ticks, vec![tick(4, 1, 1)]);
+    assert_eq!(grown.entities, source.entities);
+
+    let mut min = source.clone();
+    min.resize(BlockPos::new(2, 2, 2), ResizeAnchor::Min);
+    assert_eq!(min.position, BlockPos::new(-3, 10, -3));
+    assert_eq!(min.total_blocks(), 1);
+    assert_eq!(min.get_block_global(globals[0].0), &globals[0].1);
+    assert_eq!(min.block_entities, vec![chest(BlockPos::new(1, 1, 1))]);
+    assert!(min.pending_block_ticks.is_empty());
+    assert!(min.entities.is_empty());
+
+    let mut max = source.clone();
+    max.resize(BlockPos::new(-2, 2, -2), ResizeAnchor::Max);
+    assert_eq!(max.position, BlockPos::new(0, 12, 0));
+    assert_eq!(max.total_blocks(), 1);
+    assert_eq!(max.get_block_global(globals[2].0), &globals[2].1);
+    assert!(max.block_entities.is_empty());
+
+    let mut moved = source.clone();
+    moved.set_origin(BlockPos::new(2, 9, 2));
+    assert_eq!(moved.position, BlockPos::new(2, 9, 2));
+    assert_eq!(moved.size, BlockPos::new(-6, 5, -6));
+    assert_eq!(moved.min_global_x(), source.min_global_x());
+    for (pos, block) in &globals {
+        assert_eq!(moved.get_block_global(*pos), block);
+    }
+    assert_eq!(moved.block_entities, vec![chest(BlockPos::new(1, 2, 1))]);
+    assert_eq!(entity_pos(&moved.entities[0]), vec![-3.5, 2.0, -2.5]);
+}
+
+#[test]
+fn split_and_merge() {
+    let source = source();
+    let parts = source.split_by(BlockPos::new(3, 4, 4));
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].name, "Source (0, 0, 0)");
+    assert_eq!(parts[0].size, BlockPos::new(3, 4, 4));
+    assert_eq!(parts[1].name, "Source (1, 0, 0)");
+    assert_eq!(parts[1].position, BlockPos::new(0, 10, -3));
+    assert_eq!(parts[1].size, BlockPos::new(1, 4, 4));
+    assert_eq!(
+        parts.iter().map(|part| part.total_blocks()).sum::<usize>(),
+        source.total_blocks()
+    );
+    assert_eq!(parts[0].block_entities.len(), 1);
+    assert_eq!(parts[0].entities.len(), 1);
+    assert!(parts[1].entities.is_empty());
+
+    let mut schem = source.clone().as_litematic("", "");
+    schem.regions = parts.into();
+    schem.merge_regions(PasteMode::ReplaceAll);
+    assert_eq!(schem.regions.len(), 1);
+    let merged = &schem.regions[0];
+    assert_eq!(merged.name, "Source");
+    assert_eq!(merged.position, BlockPos::new(-3, 10, -3));
+    assert_eq!(merged.size, BlockPos::new(4, 4, 4));
+    for (pos, block) in source.blocks() {
+        assert_eq!(merged.get_block_global(source.pos_to_global(pos)), block);
+    }
+    assert_eq!(merged.block_entities, source.block_entities);
+    assert_eq!(merged.pending_block_ticks, source.pending_block_ticks);
+    assert_eq!(entity_pos(&merged.entities[0]), vec![1.5, 1.0, 2.5]);
+}
+
+#[test]
+fn merge_overlap() {
+    let mut a: Region = Region::new("A", BlockPos::new(0, 0, 0), BlockPos::new(3, 1, 1));
+    a.fill(
+        Cuboid::new(BlockPos::new(0, 0, 0), BlockPos::new(3, 1, 1)),
+        block("minecraft:stone"),
+    );
+    let mut b = Region::new("B", BlockPos::new(1, 0, 0), BlockPos::new(3, 1, 1));
+    b.set_block(BlockPos::new(0, 0, 0), block("minecraft:dirt"));
+    b.set_block(BlockPos::new(2, 0, 0), block("minecraft:dirt"));
+
+    for (mode, expected) in [
+        (PasteMode::ReplaceAll, ["stone", "dirt", "air", "dirt"]),
+        (PasteMode::SkipAir, ["stone", "dirt", "stone", "dirt"]),
+        (PasteMode::ReplaceAir, ["stone", "stone", "stone", "dirt"]),
+    ] {
+        let mut merged: Litematic = Litematic::new("Merged", "", "");
+        merged.regions = vec![a.clone(), b.clone()].into();
+        merged.merge_regions(mode);
+        assert_eq!(merged.regions.len(), 1);
+        assert_eq!(merged.regions[0].name, "Merged");
+        let blocks = merged.regions[0]
+            .blocks()
+            .map(|(_, block)| block.name.trim_start_matches("minecraft:").to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(blocks, expected, "{mode:?}");
+    }
+}
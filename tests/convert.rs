@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use bounded_integer::BoundedU8;
+use fastnbt::Value;
+use mcdata::{
+    latest::BlockState, util::BlockPos, GenericBlockEntity, GenericBlockState, GenericEntity,
+};
+use rustmatica::{Fallback, Litematic, Region};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+fn block_with(
+    name: &'static str,
+    properties: &[(&'static str, &'static str)],
+) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: properties
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect(),
+    }
+}
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[test]
+fn generic_to_typed_and_back() -> Result<()> {
+    let mut schem: Litematic = Litematic::read_file("test_files/axolotl.litematic")?;
+    let modded = GenericBlockState {
+        name: "mod:machine".into(),
+        properties: HashMap::from([("powered".into(), "true".into())]),
+    };
+    schem.regions[0].set_block(BlockPos::new(0, 0, 0), modded.clone());
+
+    let (typed, report): (Litematic<BlockState>, _) = schem.convert()?;
+    assert_eq!(
+        report.fallbacks,
+        [Fallback::Block {
+            region: schem.regions[0].name.clone().into_owned(),
+            block: modded.clone(),
+        }]
+    );
+    assert_eq!(
+        typed.regions[0].get_block(BlockPos::new(1, 0, 1)),
+        &BlockState::Water {
+            level: BoundedU8::new(0).unwrap(),
+        }
+    );
+    assert_eq!(
+        typed.regions[0].get_block(BlockPos::new(0, 0, 0)),
+        &BlockState::Other(modded)
+    );
+    assert_eq!(typed.metadata.name, schem.metadata.name);
+
+    let (generic, report): (Litematic, _) = typed.convert()?;
+    assert!(report.fallbacks.is_empty());
+    assert_eq!(generic.regions, schem.regions);
+    Ok(())
+}
+
+#[test]
+fn fallbacks_are_detected_structurally() -> Result<()> {
+    let mut region: Region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(4, 1, 1));
+    // a known block with an invalid property value falls back to `Other`
+    let invalid = block_with("minecraft:water", &[("level", "99")]);
+    region.set_block(BlockPos::new(0, 0, 0), invalid.clone());
+    // a known block with an unknown property is still known, without that property
+    region.set_block(
+        BlockPos::new(1, 0, 0),
+        block_with("minecraft:stone", &[("mod:shiny", "true")]),
+    );
+    // a known block without properties
+    region.set_block(BlockPos::new(2, 0, 0), block("minecraft:stone"));
+
+    let (typed, report) = region.convert::<BlockState, GenericEntity, GenericBlockEntity>()?;
+    assert_eq!(
+        report.fallbacks,
+        [Fallback::Block {
+            region: "Region".to_owned(),
+            block: invalid.clone(),
+        }]
+    );
+    assert_eq!(
+        typed.get_block(BlockPos::new(0, 0, 0)),
+        &BlockState::Other(invalid)
+    );
+    assert_eq!(typed.get_block(BlockPos::new(1, 0, 0)), &BlockState::Stone);
+
+    // the generic types keep everything, but have no `Other` variant
+    let (_, report) = region.convert::<Value, Value, Value>()?;
+    assert!(report.fallbacks.is_empty());
+    let (_, report) = typed.convert::<GenericBlockState, GenericEntity, GenericBlockEntity>()?;
+    assert!(report.fallbacks.is_empty());
+    Ok(())
+}
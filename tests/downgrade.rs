@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use fastnbt::Value;
+use mcdata::{
+    data_version::{MC_1_18, MC_1_20_6},
+    util::BlockPos,
+    GenericBlockEntity, GenericBlockState,
+};
+use rustmatica::{DowngradeOptions, LossyChange, Region};
+
+fn block(name: &'static str, properties: &[(&'static str, &'static str)]) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: properties
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect(),
+    }
+}
+
+fn text(messages: [&str; 4]) -> Value {
+    Value::Compound(HashMap::from([
+        (
+            "messages".to_owned(),
+            Value::List(
+                messages
+                    .iter()
+                    .map(|message| Value::String((*message).into()))
+                    .collect(),
+            ),
+        ),
+        ("color".to_owned(), Value::String("black".into())),
+        ("has_glowing_text".to_owned(), Value::Byte(0)),
+    ]))
+}
+
+#[test]
+fn downgrade_1_21_9_to_1_18() {
+    let mut region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(5, 1, 1));
+    region.set_block(
+        BlockPos::new(0, 0, 0),
+        block("minecraft:cherry_planks", &[]),
+    );
+    region.set_block(
+        BlockPos::new(1, 0, 0),
+        block("minecraft:iron_chain", &[("axis", "y")]),
+    );
+    region.set_block(
+        BlockPos::new(2, 0, 0),
+        block(
+            "minecraft:oak_leaves",
+            &[("waterlogged", "false"), ("persistent", "true")],
+        ),
+    );
+    region.set_block(
+        BlockPos::new(3, 0, 0),
+        block("minecraft:oak_shelf", &[("facing", "north")]),
+    );
+    region.set_block(
+        BlockPos::new(4, 0, 0),
+        block("minecraft:oak_sign", &[("rotation", "0")]),
+    );
+    region.block_entities.push(GenericBlockEntity {
+        id: "minecraft:shelf".into(),
+        pos: BlockPos::new(3, 0, 0),
+        properties: HashMap::new(),
+    });
+    region.block_entities.push(GenericBlockEntity {
+        id: "minecraft:sign".into(),
+        pos: BlockPos::new(4, 0, 0),
+        properties: HashMap::from([
+            ("front_text".into(), text(["Hello", "", "", ""])),
+            ("back_text".into(), text(["World", "", "", ""])),
+            ("is_waxed".into(), Value::Byte(0)),
+        ]),
+    });
+    let mut schem = region.as_litematic("", "");
+    schem.metadata.minecraft_data_version = 4554;
+
+    let report = schem.downgrade(MC_1_18, &DowngradeOptions::default());
+    assert_eq!((report.from, report.to), (4554, MC_1_18));
+    assert_eq!(schem.metadata.minecraft_data_version, MC_1_18);
+
+    let region = &schem.regions[0];
+    assert_eq!(
+        region.get_block(BlockPos::new(0, 0, 0)),
+        &block("minecraft:birch_planks", &[])
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(1, 0, 0)),
+        &block("minecraft:chain", &[("axis", "y")])
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(2, 0, 0)),
+        &block("minecraft:oak_leaves", &[("persistent", "true")])
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(3, 0, 0)),
+        &block("minecraft:air", &[])
+    );
+    assert!(region.get_block_entity(BlockPos::new(3, 0, 0)).is_none());
+
+    let sign = &region
+        .get_block_entity(BlockPos::new(4, 0, 0))
+        .unwrap()
+        .properties;
+    assert_eq!(sign["Text1"], Value::String("Hello".into()));
+    assert_eq!(sign["Color"], Value::String("black".into()));
+    assert!(!sign.contains_key("front_text"));
+    assert!(!sign.contains_key("back_text"));
+
+    assert_eq!(report.regions.len(), 1);
+    assert_eq!(report.regions[0].region, "Region");
+    let changes = &report.regions[0].changes;
+    assert_eq!(changes.len(), 6);
+    for change in [
+        LossyChange::BlockEntityDataDropped {
+            pos: BlockPos::new(4, 0, 0),
+            id: "minecraft:sign".into(),
+            change: "make sign text single sided",
+        },
+        LossyChange::BlockEntityNotMigrated {
+            pos: BlockPos::new(4, 0, 0),
+            id: "minecraft:sign".into(),
+            change: "store text components as JSON",
+        },
+        LossyChange::BlockReplaced {
+            from: block("minecraft:cherry_planks", &[]),
+            to: block("minecraft:birch_planks", &[]),
+        },
+        LossyChange::BlockReplaced {
+            from: block("minecraft:oak_shelf", &[("facing", "north")]),
+            to: block("minecraft:air", &[]),
+        },
+        LossyChange::PropertyRemoved {
+            block: block(
+                "minecraft:oak_leaves",
+                &[("waterlogged", "false"), ("persistent", "true")],
+            ),
+            property: "waterlogged".into(),
+        },
+        LossyChange::BlockEntityRemoved {
+            pos: BlockPos::new(3, 0, 0),
+            id: "minecraft:shelf".into(),
+        },
+    ] {
+        assert!(changes.contains(&change), "missing {change:?}");
+    }
+
+    // downgrading to a newer version does nothing
+    let report = schem.downgrade(MC_1_20_6, &DowngradeOptions::default());
+    assert_eq!((report.from, report.to), (MC_1_18, MC_1_18));
+    assert!(report.regions.is_empty());
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use mcdata::{
+    util::{BlockPos, Cuboid},
+    GenericBlockEntity, GenericBlockState,
+};
+use rustmatica::{Litematic, Region};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+#[test]
+fn region_edits() {
+    let mut region: Region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(4, 4, 4));
+    region.set_block(BlockPos::new(3, 3, 3), block("minecraft:chest"));
+    region.block_entities.push(GenericBlockEntity {
+        id: "minecraft:chest".into(),
+        pos: BlockPos::new(3, 3, 3),
+        properties: HashMap::new(),
+    });
+
+    // partially outside the region
+    let filled = region.fill(
+        Cuboid::new(BlockPos::new(2, 2, 2), BlockPos::new(5, 5, 5)),
+        block("minecraft:stone"),
+    );
+    assert_eq!(filled, 8);
+    assert_eq!(
+        region.get_block(BlockPos::new(3, 3, 3)),
+        &block("minecraft:stone")
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(1, 2, 2)),
+        &block("minecraft:air")
+    );
+    assert!(region.block_entities.is_empty());
+
+    // `to` is new, so the palette entry is replaced in place
+    assert_eq!(
+        region.replace(&block("minecraft:stone"), block("minecraft:dirt")),
+        8
+    );
+    assert_eq!(region.palette_id(&block("minecraft:stone")), None);
+    // `to` is already used, so the blocks are remapped
+    assert_eq!(
+        region.replace(&block("minecraft:air"), block("minecraft:dirt")),
+        56
+    );
+    assert_eq!(region.block_palette()[0], block("minecraft:air"));
+    assert!(region
+        .blocks()
+        .all(|(_, block)| block.name == "minecraft:dirt"));
+
+    let replaced = region.replace_in(
+        Cuboid::new(BlockPos::new(0, 0, 0), BlockPos::new(4, 1, 4)),
+        |block| block.name == "minecraft:dirt",
+        block("minecraft:grass_block"),
+    );
+    assert_eq!(replaced, 16);
+    assert_eq!(
+        region.get_block(BlockPos::new(2, 0, 1)),
+        &block("minecraft:grass_block")
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(2, 1, 1)),
+        &block("minecraft:dirt")
+    );
+
+    // blocks which already are the new block are not replaced, and keep their block entities
+    region.block_entities.push(GenericBlockEntity {
+        id: "minecraft:test".into(),
+        pos: BlockPos::new(2, 0, 1),
+        properties: HashMap::new(),
+    });
+    assert_eq!(
+        region.replace_in(
+            Cuboid::new(BlockPos::new(0, 0, 0), BlockPos::new(4, 2, 4)),
+            |_| true,
+            block("minecraft:grass_block"),
+        ),
+        16
+    );
+    assert_eq!(region.block_entities.len(), 1);
+    assert_eq!(
+        region.replace_in(
+            Cuboid::new(BlockPos::new(0, 0, 0), BlockPos::new(4, 2, 4)),
+            |block| block.name == "minecraft:grass_block",
+            block("minecraft:grass_block"),
+        ),
+        0
+    );
+
+    assert_eq!(
+        region.clear(Cuboid::new(BlockPos::new(0, 0, 0), BlockPos::new(4, 4, 4))),
+        64
+    );
+    assert_eq!(region.total_blocks(), 0);
+}
+
+#[test]
+fn litematic_edits_span_regions() {
+    let mut schem: Litematic = Litematic::new("", "", "");
+    schem.regions.push(Region::new(
+        "A",
+        BlockPos::new(0, 0, 0),
+        BlockPos::new(2, 1, 1),
+    ));
+    // negative size, covering x from -3 to -1
+    schem.regions.push(Region::new(
+        "B",
+        BlockPos::new(-1, 0, 0),
+        BlockPos::new(-3, 1, 1),
+    ));
+
+    let filled = schem.fill(
+        Cuboid::new(BlockPos::new(-2, 0, 0), BlockPos::new(3, 1, 1)),
+        block("minecraft:stone"),
+    );
+    assert_eq!(filled, 3);
+    assert_eq!(
+        schem.regions[0].get_block_global(BlockPos::new(0, 0, 0)),
+        &block("minecraft:stone")
+    );
+    assert_eq!(
+        schem.regions[0].get_block_global(BlockPos::new(1, 0, 0)),
+        &block("minecraft:air")
+    );
+    assert_eq!(
+        schem.regions[1].get_block_global(BlockPos::new(-2, 0, 0)),
+        &block("minecraft:stone")
+    );
+    assert_eq!(
+        schem.regions[1].get_block_global(BlockPos::new(-3, 0, 0)),
+        &block("minecraft:air")
+    );
+
+    assert_eq!(
+        schem.replace(&block("minecraft:stone"), block("minecraft:dirt")),
+        3
+    );
+    assert_eq!(
+        schem.replace_in(
+            schem.enclosing_box(),
+            |block| block.name == "minecraft:air",
+            block("minecraft:glass"),
+        ),
+        2
+    );
+    assert_eq!(schem.clear(schem.enclosing_box()), 5);
+    assert_eq!(schem.total_blocks(), 0);
+}
+
+#[test]
+fn enclosing_box_away_from_origin() {
+    let mut schem: Litematic = Litematic::new("Far", "", "");
+    schem.regions.push(Region::new(
+        "A",
+        BlockPos::new(100, 64, -200),
+        BlockPos::new(2, 3, 4),
+    ));
+    // negative size, covering x from 96 to 100 and z from -203 to -200
+    schem.regions.push(Region::new(
+        "B",
+        BlockPos::new(100, 70, -200),
+        BlockPos::new(-5, 1, -4),
+    ));
+    let enclosing = schem.enclosing_box();
+    assert_eq!(enclosing.origin, BlockPos::new(96, 64, -203));
+    assert_eq!(enclosing.size, BlockPos::new(6, 7, 7));
+    assert_eq!(schem.enclosing_size(), BlockPos::new(6, 7, 7));
+
+    schem.regions.truncate(1);
+    let enclosing = schem.enclosing_box();
+    assert_eq!(enclosing.origin, BlockPos::new(100, 64, -200));
+    assert_eq!(enclosing.size, BlockPos::new(2, 3, 4));
+}
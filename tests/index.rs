@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use mcdata::{util::BlockPos, GenericBlockState};
+use rustmatica::{Litematic, Region, RegionIndex, ResizeAnchor};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+fn schem() -> Litematic {
+    let mut schem: Litematic = Litematic::new("", "", "");
+    let mut a = Region::new("A", BlockPos::new(0, 0, 0), BlockPos::new(20, 1, 1));
+    for x in 0..20 {
+        a.set_block(BlockPos::new(x, 0, 0), block("minecraft:stone"));
+    }
+    // negative size, covering x from 15 to 24, overlapping the end of A
+    let mut b = Region::new("B", BlockPos::new(24, 0, 0), BlockPos::new(-10, 1, 1));
+    b.set_block(BlockPos::new(0, 0, 0), block("minecraft:dirt"));
+    b.set_block(BlockPos::new(9, 0, 0), block("minecraft:dirt"));
+    schem.regions = vec![
+        a,
+        b,
+        Region::new("Empty", BlockPos::new(3, 3, 3), BlockPos::new(0, 0, 0)),
+    ]
+    .into();
+    schem
+}
+
+#[test]
+fn global_blocks() {
+    let mut schem = schem();
+    let index = RegionIndex::new(&schem);
+    for x in -1..26 {
+        let pos = BlockPos::new(x, 0, 0);
+        let expected = match x {
+            0..=14 => Some(block("minecraft:stone")),
+            15 | 24 => Some(block("minecraft:dirt")),
+            16..=23 => Some(block("minecraft:air")),
+            _ => None,
+        };
+        assert_eq!(schem.get_block(pos), expected.as_ref(), "{x}");
+        assert_eq!(index.get_block(&schem, pos), expected.as_ref(), "{x}");
+    }
+    assert_eq!(index.region_at(&schem, BlockPos::new(10, 0, 0)), Some(0));
+    assert_eq!(index.region_at(&schem, BlockPos::new(18, 0, 0)), Some(1));
+    assert_eq!(index.region_at(&schem, BlockPos::new(0, 1, 0)), None);
+    assert_eq!(schem.get_block(BlockPos::new(3, 3, 3)), None);
+
+    let blocks = schem.blocks().collect::<Vec<_>>();
+    assert_eq!(blocks.len(), 25);
+    for (pos, block) in blocks {
+        assert_eq!(schem.get_block(pos), Some(block));
+    }
+
+    // only the region with the highest priority is changed
+    assert!(schem.set_block(BlockPos::new(17, 0, 0), block("minecraft:glass")));
+    assert!(index.set_block(
+        &mut schem,
+        BlockPos::new(16, 0, 0),
+        block("minecraft:glass")
+    ));
+    assert!(!schem.set_block(BlockPos::new(30, 0, 0), block("minecraft:glass")));
+    assert!(!index.set_block(
+        &mut schem,
+        BlockPos::new(30, 0, 0),
+        block("minecraft:glass")
+    ));
+    assert_eq!(
+        schem.get_block(BlockPos::new(17, 0, 0)),
+        Some(&block("minecraft:glass"))
+    );
+    assert_eq!(
+        schem.regions[1].get_block_global(BlockPos::new(16, 0, 0)),
+        &block("minecraft:glass")
+    );
+    assert_eq!(
+        schem.regions[0].get_block_global(BlockPos::new(16, 0, 0)),
+        &block("minecraft:stone")
+    );
+}
+
+#[test]
+fn index_follows_region_changes() {
+    let mut schem = schem();
+    let pos = BlockPos::new(30, 0, 0);
+    assert_eq!(schem.get_block(pos), None);
+
+    // moving a region
+    schem.regions[1].position = BlockPos::new(39, 0, 0);
+    assert_eq!(schem.get_block(pos), Some(&block("minecraft:dirt")));
+    assert_eq!(
+        schem.get_block(BlockPos::new(15, 0, 0)),
+        Some(&block("minecraft:stone"))
+    );
+
+    // resizing a region
+    schem.regions[2].resize(BlockPos::new(1, 1, 1), ResizeAnchor::Position);
+    assert_eq!(
+        schem.get_block(BlockPos::new(3, 3, 3)),
+        Some(&block("minecraft:air"))
+    );
+
+    // adding a region taking priority
+    let mut glass = Region::new("Glass", pos, BlockPos::new(1, 1, 1));
+    glass.set_block(BlockPos::new(0, 0, 0), block("minecraft:glass"));
+    schem.regions.push(glass);
+    assert_eq!(schem.get_block(pos), Some(&block("minecraft:glass")));
+    assert!(schem.set_block(pos, block("minecraft:sand")));
+    assert_eq!(schem.get_block(pos), Some(&block("minecraft:sand")));
+
+    // removing it again
+    schem.regions.pop();
+    assert_eq!(schem.get_block(pos), Some(&block("minecraft:dirt")));
+    schem.regions.clear();
+    assert_eq!(schem.get_block(pos), None);
+    assert!(!schem.set_block(pos, block("minecraft:sand")));
+}
@@ -0,0 +1,427 @@
+use std::{collections::HashMap, io::Write};
+
+use fastnbt::{ByteArray, Value};
+use flate2::{write::GzEncoder, Compression};
+use mcdata::{util::BlockPos, GenericBlockState};
+use rustmatica::{Error, Litematic};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Modded block ids which need the `AddBlocks` high bits.
+const MODDED: u16 = 0x12c;
+const OTHER: u16 = 0x22d;
+
+fn compound<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Compound(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
+
+fn byte_array(values: impl IntoIterator<Item = u8>) -> Value {
+    Value::ByteArray(ByteArray::new(
+        values.into_iter().map(|value| value as i8).collect(),
+    ))
+}
+
+/// The NBT of a 4x2x1 MCEdit schematic with the given block ids and data values, indexed by
+/// `x + y * 4`.
+fn schematic(blocks: [(u16, u8); 8]) -> HashMap<String, Value> {
+    let mut add = [0; 4];
+    for (index, (id, _)) in blocks.iter().enumerate() {
+        add[index >> 1] |= ((id >> 8) as u8 & 0x0f) << ((index & 1) * 4);
+    }
+    let Value::Compound(nbt) = compound([
+        ("Width", Value::Short(4)),
+        ("Height", Value::Short(2)),
+        ("Length", Value::Short(1)),
+        ("Materials", Value::String("Alpha".to_owned())),
+        ("Blocks", byte_array(blocks.map(|(id, _)| id as u8))),
+        ("AddBlocks", byte_array(add)),
+        ("Data", byte_array(blocks.map(|(_, data)| data))),
+        ("Entities", Value::List(vec![])),
+        ("TileEntities", Value::List(vec![])),
+    ]) else {
+        unreachable!();
+    };
+    nbt
+}
+
+fn read(nbt: HashMap<String, Value>) -> Result<Litematic> {
+    let mut buf = vec![];
+    let mut gz = GzEncoder::new(&mut buf, Compression::default());
+    gz.write_all(&fastnbt::to_bytes(&Value::Compound(nbt))?)?;
+    gz.finish()?;
+    Ok(Litematic::from_legacy_schematic(&buf, "Legacy")?)
+}
+
+fn check(schem: &Litematic, expected: [(i32, i32, &str); 8]) {
+    let [region] = &schem.regions[..] else {
+        panic!("expected a single region");
+    };
+    assert_eq!(region.size, BlockPos::new(4, 2, 1));
+    for (x, y, state) in expected {
+        let block = region.get_block(BlockPos::new(x, y, 0));
+        let (name, properties) = state.split_once('[').unwrap_or((state, "]"));
+        let properties = properties
+            .trim_end_matches(']')
+            .split(',')
+            .filter(|property| !property.is_empty())
+            .map(|property| property.split_once('=').expect("invalid property"))
+            .collect::<Vec<_>>();
+        let name = format!("minecraft:{name}");
+        assert_eq!(block.name, name, "{x} {y}");
+        assert_eq!(block.properties.len(), properties.len(), "{x} {y}");
+        for (key, value) in properties {
+            assert_eq!(block.properties[key], value, "{x} {y} {key}");
+        }
+    }
+}
+
+#[test]
+fn logs() -> Result<()> {
+    let schem = read(schematic([
+        (17, 0),
+        (17, 5),
+        (17, 10),
+        (17, 12),
+        (162, 1),
+        (162, 4),
+        (162, 9),
+        (162, 13),
+    ]))?;
+    assert_eq!(schem.metadata.minecraft_data_version, 1519);
+    check(
+        &schem,
+        [
+            (0, 0, "oak_log[axis=y]"),
+            (1, 0, "spruce_log[axis=x]"),
+            (2, 0, "birch_log[axis=z]"),
+            // logs with bark on all six sides
+            (3, 0, "oak_wood[axis=y]"),
+            (0, 1, "dark_oak_log[axis=y]"),
+            (1, 1, "acacia_log[axis=x]"),
+            (2, 1, "dark_oak_log[axis=z]"),
+            (3, 1, "dark_oak_wood[axis=y]"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn doors() -> Result<()> {
+    // the lower halves store facing and open, the upper halves hinge and powered
+    let schem = read(schematic([
+        (64, 4),
+        (71, 1),
+        (196, 3),
+        (64, 8),
+        (64, 11),
+        (71, 8),
+        (196, 9),
+        (64, 9),
+    ]))?;
+    check(
+        &schem,
+        [
+            (
+                0,
+                0,
+                "oak_door[facing=east,half=lower,hinge=right,open=true,powered=true]",
+            ),
+            (
+                1,
+                0,
+                "iron_door[facing=south,half=lower,hinge=left,open=false,powered=false]",
+            ),
+            (
+                2,
+                0,
+                "acacia_door[facing=north,half=lower,hinge=right,open=false,powered=false]",
+            ),
+            // an upper half without a lower half
+            (
+                3,
+                0,
+                "oak_door[facing=east,half=upper,hinge=left,open=false,powered=false]",
+            ),
+            (
+                0,
+                1,
+                "oak_door[facing=east,half=upper,hinge=right,open=true,powered=true]",
+            ),
+            (
+                1,
+                1,
+                "iron_door[facing=south,half=upper,hinge=left,open=false,powered=false]",
+            ),
+            (
+                2,
+                1,
+                "acacia_door[facing=north,half=upper,hinge=right,open=false,powered=false]",
+            ),
+            (
+                3,
+                1,
+                "oak_door[facing=east,half=upper,hinge=right,open=false,powered=false]",
+            ),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn beds() -> Result<()> {
+    let mut nbt = schematic([
+        (26, 0),
+        (26, 8),
+        (26, 13),
+        (26, 3),
+        (0, 0),
+        (0, 0),
+        (0, 0),
+        (0, 0),
+    ]);
+    // the bed color is stored in the tile entity, which is missing for the last bed
+    let bed = |x, color| {
+        compound([
+            ("id", Value::String("minecraft:bed".to_owned())),
+            ("x", Value::Int(x)),
+            ("y", Value::Int(0)),
+            ("z", Value::Int(0)),
+            ("color", Value::Int(color)),
+        ])
+    };
+    nbt.insert(
+        "TileEntities".to_owned(),
+        Value::List(vec![bed(0, 11), bed(1, 11), bed(2, 0)]),
+    );
+    let schem = read(nbt)?;
+    check(
+        &schem,
+        [
+            (0, 0, "blue_bed[facing=south,occupied=false,part=foot]"),
+            (1, 0, "blue_bed[facing=south,occupied=false,part=head]"),
+            (2, 0, "white_bed[facing=west,occupied=true,part=head]"),
+            (3, 0, "red_bed[facing=east,occupied=false,part=foot]"),
+            (0, 1, "air"),
+            (1, 1, "air"),
+            (2, 1, "air"),
+            (3, 1, "air"),
+        ],
+    );
+    assert_eq!(schem.regions[0].block_entities.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn skulls() -> Result<()> {
+    // floor skulls in the bottom row and wall skulls in the top row
+    let mut nbt = schematic([
+        (144, 1),
+        (144, 1),
+        (144, 1),
+        (144, 1),
+        (144, 2),
+        (144, 3),
+        (144, 4),
+        (144, 5),
+    ]);
+    let skull = |x, y, skull_type| {
+        compound([
+            ("id", Value::String("minecraft:skull".to_owned())),
+            ("x", Value::Int(x)),
+            ("y", Value::Int(y)),
+            ("z", Value::Int(0)),
+            ("SkullType", Value::Byte(skull_type)),
+            ("Rot", Value::Byte(4)),
+        ])
+    };
+    nbt.insert(
+        "TileEntities".to_owned(),
+        Value::List(
+            (0..4)
+                .flat_map(|x| [skull(x, 0, x as i8 + 2), skull(x, 1, x as i8 + 2)])
+                .collect(),
+        ),
+    );
+    check(
+        &read(nbt)?,
+        [
+            (0, 0, "zombie_head[rotation=4]"),
+            (1, 0, "player_head[rotation=4]"),
+            (2, 0, "creeper_head[rotation=4]"),
+            (3, 0, "dragon_head[rotation=4]"),
+            (0, 1, "zombie_wall_head[facing=north]"),
+            (1, 1, "player_wall_head[facing=south]"),
+            (2, 1, "creeper_wall_head[facing=west]"),
+            (3, 1, "dragon_wall_head[facing=east]"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn add_blocks() -> Result<()> {
+    let blocks = [
+        (1, 0),
+        (MODDED, 0),
+        (OTHER, 0),
+        (1, 3),
+        (OTHER, 0),
+        (35, 14),
+        (MODDED, 0),
+        (0, 0),
+    ];
+    let expected = |first, second| {
+        [
+            (0, 0, "stone"),
+            (1, 0, first),
+            (2, 0, second),
+            (3, 0, "diorite"),
+            (0, 1, second),
+            (1, 1, "red_wool"),
+            (2, 1, first),
+            (3, 1, "air"),
+        ]
+    };
+
+    // modded blocks are replaced with air without a name mapping
+    check(&read(schematic(blocks))?, expected("air", "air"));
+
+    let mut nbt = schematic(blocks);
+    nbt.insert(
+        "SchematicaMapping".to_owned(),
+        compound([
+            ("minecraft:modded_first", Value::Short(MODDED as i16)),
+            ("minecraft:modded_second", Value::Short(OTHER as i16)),
+        ]),
+    );
+    check(&read(nbt)?, expected("modded_first", "modded_second"));
+
+    // the older `Add` format stores the high bits in a full byte per block
+    let mut nbt = schematic(blocks);
+    nbt.remove("AddBlocks");
+    nbt.insert(
+        "Add".to_owned(),
+        byte_array(blocks.map(|(id, _)| (id >> 8) as u8)),
+    );
+    nbt.insert(
+        "SchematicaMapping".to_owned(),
+        compound([
+            ("minecraft:modded_first", Value::Short(MODDED as i16)),
+            ("minecraft:modded_second", Value::Short(OTHER as i16)),
+        ]),
+    );
+    check(&read(nbt)?, expected("modded_first", "modded_second"));
+    Ok(())
+}
+
+#[test]
+fn entities() -> Result<()> {
+    let mut nbt = schematic([(0, 0); 8]);
+    nbt.insert(
+        "Entities".to_owned(),
+        Value::List(vec![compound([
+            ("id", Value::String("minecraft:pig".to_owned())),
+            ("UUIDMost", Value::Long(1)),
+            ("UUIDLeast", Value::Long(2)),
+        ])]),
+    );
+    let schem = read(nbt)?;
+    let [pig] = &schem.regions[0].entities[..] else {
+        panic!("expected a single entity");
+    };
+    assert_eq!(pig.id, "minecraft:pig");
+    assert_eq!(pig.uuid, 1 << 64 | 2);
+    assert!(!pig.properties.contains_key("UUIDMost"));
+    Ok(())
+}
+
+#[test]
+fn unknown_data_values() -> Result<()> {
+    // data values without a flattened state are replaced with air
+    let schem = read(schematic([
+        (1, 7),
+        (12, 2),
+        (35, 0),
+        (0, 0),
+        (0, 0),
+        (0, 0),
+        (0, 0),
+        (0, 0),
+    ]))?;
+    assert_eq!(
+        schem.regions[0].get_block(BlockPos::new(0, 0, 0)),
+        &block("minecraft:air")
+    );
+    assert_eq!(
+        schem.regions[0].get_block(BlockPos::new(1, 0, 0)),
+        &block("minecraft:air")
+    );
+    assert_eq!(
+        schem.regions[0].get_block(BlockPos::new(2, 0, 0)),
+        &block("minecraft:white_wool")
+    );
+    Ok(())
+}
+
+#[test]
+fn too_large() -> Result<()> {
+    let mut nbt = schematic([(0, 0); 8]);
+    for key in ["Width", "Height", "Length"] {
+        nbt.insert(key.to_owned(), Value::Short(-1));
+    }
+    assert!(matches!(
+        read(nbt).map_err(|err| *err.downcast::<Error>().expect("not a rustmatica error")),
+        Err(Error::RegionTooLarge {
+            format: "legacy schematic",
+            region
+        }) if region == "Legacy"
+    ));
+    Ok(())
+}
+
+#[test]
+fn truncated_arrays() -> Result<()> {
+    let mismatch = |nbt, actual: usize| {
+        matches!(
+            read(nbt).map_err(|err| *err.downcast::<Error>().expect("not a rustmatica error")),
+            Err(Error::BlockStatesLengthMismatch { region, expected: 8, actual: a })
+                if region == "Legacy" && a == actual
+        )
+    };
+    for (key, value, actual) in [
+        ("Blocks", byte_array([1; 7]), 7),
+        ("Data", byte_array([]), 0),
+        ("AddBlocks", byte_array([0; 3]), 6),
+    ] {
+        let mut nbt = schematic([(1, 0); 8]);
+        nbt.insert(key.to_owned(), value);
+        assert!(mismatch(nbt, actual), "{key}");
+    }
+
+    // the size is checked against the arrays before any blocks are allocated
+    let mut nbt = schematic([(1, 0); 8]);
+    for key in ["Width", "Height", "Length"] {
+        nbt.insert(key.to_owned(), Value::Short(1500));
+    }
+    assert!(matches!(
+        read(nbt).map_err(|err| *err.downcast::<Error>().expect("not a rustmatica error")),
+        Err(Error::BlockStatesLengthMismatch {
+            expected: 3_375_000_000,
+            actual: 8,
+            ..
+        })
+    ));
+    Ok(())
+}
@@ -0,0 +1,222 @@
+use std::{collections::HashMap, io::Write};
+
+use fastnbt::{IntArray, LongArray, Value};
+use flate2::{write::GzEncoder, Compression};
+use mcdata::util::BlockPos;
+use rustmatica::{Error, Litematic, LitematicMetadata, Region};
+use serde::Serialize;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Create the NBT of a valid schematic with a single 4x4x4 region named `Region`.
+fn valid_nbt() -> Result<Value> {
+    let mut schem: Litematic = Litematic::new("Malformed", "", "");
+    let mut region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(4, 4, 4));
+    region.set_block(
+        BlockPos::new(1, 2, 3),
+        mcdata::GenericBlockState {
+            name: "minecraft:stone".into(),
+            properties: HashMap::new(),
+        },
+    );
+    schem.regions.push(region);
+    Ok(fastnbt::from_bytes(&schem.to_uncompressed_bytes()?)?)
+}
+
+fn compound<'a>(nbt: &'a mut Value, path: &[&str]) -> &'a mut HashMap<String, Value> {
+    let mut nbt = nbt;
+    for key in path {
+        let Value::Compound(map) = nbt else {
+            panic!("expected a compound");
+        };
+        nbt = map.get_mut(*key).expect("missing key");
+    }
+    let Value::Compound(map) = nbt else {
+        panic!("expected a compound");
+    };
+    map
+}
+
+fn read(nbt: &Value) -> Result<rustmatica::Result<Litematic>> {
+    Ok(Litematic::from_uncompressed_bytes(&fastnbt::to_bytes(nbt)?))
+}
+
+#[test]
+fn short_block_states() -> Result<()> {
+    let mut nbt = valid_nbt()?;
+    compound(&mut nbt, &["Regions", "Region"]).insert(
+        "BlockStates".to_owned(),
+        Value::LongArray(LongArray::new(vec![0])),
+    );
+    assert!(matches!(
+        read(&nbt)?,
+        Err(Error::BlockStatesLengthMismatch { region, expected: 64, actual: 32 })
+            if region == "Region"
+    ));
+    Ok(())
+}
+
+#[test]
+fn palette_index_out_of_range() -> Result<()> {
+    let mut nbt = valid_nbt()?;
+    compound(&mut nbt, &["Regions", "Region"]).insert(
+        "BlockStates".to_owned(),
+        Value::LongArray(LongArray::new(vec![3, 0])),
+    );
+    assert!(matches!(
+        read(&nbt)?,
+        Err(Error::PaletteIndexOutOfRange { region, index: 3 }) if region == "Region"
+    ));
+    Ok(())
+}
+
+#[test]
+fn timestamp_out_of_range() -> Result<()> {
+    let mut nbt = valid_nbt()?;
+    compound(&mut nbt, &["Metadata"]).insert("TimeCreated".to_owned(), Value::Long(i64::MAX));
+    let result = LitematicMetadata::from_uncompressed_bytes(&fastnbt::to_bytes(&nbt)?);
+    if cfg!(feature = "chrono") {
+        assert!(matches!(
+            result,
+            Err(Error::TimestampOutOfRange {
+                timestamp: i64::MAX
+            })
+        ));
+    } else {
+        assert!(result.is_ok());
+    }
+    Ok(())
+}
+
+#[test]
+fn invalid_preview_size() -> Result<()> {
+    let mut nbt = valid_nbt()?;
+    compound(&mut nbt, &["Metadata"]).insert(
+        "PreviewImageData".to_owned(),
+        Value::IntArray(IntArray::new(vec![0; 100])),
+    );
+    let result = LitematicMetadata::from_uncompressed_bytes(&fastnbt::to_bytes(&nbt)?);
+    if cfg!(feature = "image") {
+        assert!(matches!(
+            result,
+            Err(Error::InvalidPreviewSize { pixels: 100 })
+        ));
+    } else {
+        assert!(result.is_ok());
+    }
+    Ok(())
+}
+
+#[test]
+fn unsupported_version() -> Result<()> {
+    let mut nbt = valid_nbt()?;
+    compound(&mut nbt, &[]).insert("Version".to_owned(), Value::Int(8));
+    assert!(matches!(
+        read(&nbt)?,
+        Err(Error::UnsupportedVersion {
+            format: "litematica",
+            version: 8
+        })
+    ));
+    Ok(())
+}
+
+#[test]
+fn unsupported_version_layout() -> Result<()> {
+    // regions of unsupported versions may have a different layout, so they must not be
+    // deserialized before the version is checked
+    let mut nbt = valid_nbt()?;
+    compound(&mut nbt, &["Regions", "Region"]).remove("BlockStates");
+    compound(&mut nbt, &[]).insert("Version".to_owned(), Value::Int(8));
+    assert!(matches!(
+        read(&nbt)?,
+        Err(Error::UnsupportedVersion { version: 8, .. })
+    ));
+
+    // when reading in a single pass, regions after an unsupported version are skipped
+    #[derive(Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Ordered {
+        version: i32,
+        metadata: Value,
+        regions: Value,
+    }
+    let root = compound(&mut nbt, &[]);
+    let ordered = Ordered {
+        version: 8,
+        metadata: root.remove("Metadata").expect("missing metadata"),
+        regions: root.remove("Regions").expect("missing regions"),
+    };
+    let mut gz = GzEncoder::new(vec![], Compression::fast());
+    gz.write_all(&fastnbt::to_bytes(&ordered)?)?;
+    let result: rustmatica::Result<Litematic> = Litematic::from_bytes(&gz.finish()?);
+    assert!(matches!(
+        result,
+        Err(Error::UnsupportedVersion { version: 8, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn repeated_version() -> Result<()> {
+    // a supported version after the regions must not hide the unsupported one they were
+    // skipped for
+    #[derive(Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Repeated {
+        version: i32,
+        metadata: Value,
+        regions: Value,
+        #[serde(rename = "Version")]
+        second_version: i32,
+    }
+    let mut nbt = valid_nbt()?;
+    let root = compound(&mut nbt, &[]);
+    let repeated = Repeated {
+        version: 8,
+        metadata: root.remove("Metadata").expect("missing metadata"),
+        regions: root.remove("Regions").expect("missing regions"),
+        second_version: 6,
+    };
+    let mut gz = GzEncoder::new(vec![], Compression::fast());
+    gz.write_all(&fastnbt::to_bytes(&repeated)?)?;
+    let result: rustmatica::Result<Litematic> = Litematic::from_bytes(&gz.finish()?);
+    assert!(matches!(result, Err(Error::Nbt(_))), "{result:?}");
+    Ok(())
+}
+
+fn size(x: i32, y: i32, z: i32) -> Value {
+    Value::Compound(HashMap::from([
+        ("x".to_owned(), Value::Int(x)),
+        ("y".to_owned(), Value::Int(y)),
+        ("z".to_owned(), Value::Int(z)),
+    ]))
+}
+
+#[test]
+fn region_size_overflow() -> Result<()> {
+    let mut nbt = valid_nbt()?;
+    compound(&mut nbt, &["Regions", "Region"])
+        .insert("Size".to_owned(), size(100_000, -100_000, 100_000));
+    assert!(matches!(
+        read(&nbt)?,
+        Err(Error::RegionTooLarge { format: "litematica", region }) if region == "Region"
+    ));
+    Ok(())
+}
+
+#[test]
+fn region_size_too_large_for_block_states() -> Result<()> {
+    // the size is checked against the block data before any blocks are allocated
+    let mut nbt = valid_nbt()?;
+    compound(&mut nbt, &["Regions", "Region"]).insert("Size".to_owned(), size(1024, 1024, 1024));
+    assert!(matches!(
+        read(&nbt)?,
+        Err(Error::BlockStatesLengthMismatch {
+            region,
+            expected: 1_073_741_824,
+            actual: 64,
+        }) if region == "Region"
+    ));
+    Ok(())
+}
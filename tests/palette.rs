@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use fastnbt::Value;
+use mcdata::{util::BlockPos, GenericBlockState};
+use rustmatica::{Litematic, Mirror, PaletteOrder, Region, Rotation};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+fn block_with(
+    name: &'static str,
+    properties: &[(&'static str, &'static str)],
+) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: properties
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect(),
+    }
+}
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const SIZE: BlockPos = BlockPos::new(4, 2, 3);
+
+/// A region whose palette contains unused entries, and whose blocks use the palette entries out
+/// of order.
+fn region() -> Region {
+    let mut region = Region::new("Region", BlockPos::new(0, 0, 0), SIZE);
+    for name in ["a", "b", "c", "d", "e"] {
+        region.add_to_palette(test_block(name));
+    }
+    region.set_block(BlockPos::new(0, 0, 0), test_block("d"));
+    region.set_block(BlockPos::new(1, 0, 0), test_block("b"));
+    region.set_block(BlockPos::new(3, 1, 2), test_block("d"));
+    region.set_block(BlockPos::new(2, 1, 1), test_block("e"));
+    region
+}
+
+fn test_block(name: &'static str) -> GenericBlockState {
+    block_with("test:block", &[("name", name)])
+}
+
+fn names(region: &Region) -> Vec<String> {
+    region
+        .block_palette()
+        .iter()
+        .map(|state| match state.properties.get("name") {
+            Some(name) => name.to_string(),
+            None => state.name.to_string(),
+        })
+        .collect()
+}
+
+fn block_names(region: &Region) -> Vec<(i32, i32, i32, String)> {
+    region
+        .blocks()
+        .map(|(pos, state)| {
+            let name = match state.properties.get("name") {
+                Some(name) => name.to_string(),
+                None => state.name.to_string(),
+            };
+            (pos.x, pos.y, pos.z, name)
+        })
+        .collect()
+}
+
+#[test]
+fn compact_unchanged() {
+    let mut region = region();
+    let blocks = block_names(&region);
+    assert_eq!(names(&region), ["minecraft:air", "a", "b", "c", "d", "e"]);
+
+    region.compact_palette(PaletteOrder::Unchanged);
+    assert_eq!(names(&region), ["minecraft:air", "b", "d", "e"]);
+    assert_eq!(block_names(&region), blocks);
+    assert_eq!(region.palette_id(&test_block("d")), Some(2));
+    assert_eq!(region.palette_id(&test_block("a")), None);
+}
+
+#[test]
+fn compact_first_occurrence() {
+    let mut region = region();
+    let blocks = block_names(&region);
+
+    region.compact_palette(PaletteOrder::FirstOccurrence);
+    assert_eq!(names(&region), ["minecraft:air", "d", "b", "e"]);
+    assert_eq!(block_names(&region), blocks);
+    assert_eq!(region.palette_id(&test_block("d")), Some(1));
+
+    // the same blocks set in a different order result in the same palette
+    let mut other: Region = Region::new("Other", BlockPos::new(0, 0, 0), SIZE);
+    for (x, y, z, name) in [
+        (2, 1, 1, "e"),
+        (1, 0, 0, "b"),
+        (3, 1, 2, "d"),
+        (0, 0, 0, "d"),
+    ] {
+        other.set_block(BlockPos::new(x, y, z), test_block(name));
+    }
+    assert_eq!(names(&other), ["minecraft:air", "e", "b", "d"]);
+    other.compact_palette(PaletteOrder::FirstOccurrence);
+    assert_eq!(other.block_palette(), region.block_palette());
+}
+
+#[test]
+fn compact_keeps_air() {
+    // air stays at id 0 even if no block uses it anymore
+    let mut region = region();
+    for (pos, _) in region.clone().blocks() {
+        region.set_block(pos, test_block("c"));
+    }
+    region.compact_palette(PaletteOrder::FirstOccurrence);
+    assert_eq!(names(&region), ["minecraft:air", "c"]);
+    assert!(region.blocks().all(|(_, state)| state == &test_block("c")));
+
+    let mut empty: Region = Region::new("Empty", BlockPos::new(0, 0, 0), BlockPos::new(0, 0, 0));
+    empty.add_to_palette(test_block("a"));
+    empty.compact_palette(PaletteOrder::Unchanged);
+    assert_eq!(names(&empty), ["minecraft:air"]);
+}
+
+/// Get the palette length and block states of the only region as written to NBT.
+fn written(schem: &Litematic) -> Result<(usize, usize)> {
+    let nbt: Value = fastnbt::from_bytes(&schem.to_uncompressed_bytes()?)?;
+    let Value::Compound(root) = nbt else {
+        panic!("expected a compound");
+    };
+    let Some(Value::Compound(regions)) = root.get("Regions") else {
+        panic!("expected regions");
+    };
+    let Some(Value::Compound(region)) = regions.get("Region") else {
+        panic!("expected a region");
+    };
+    match (region.get("BlockStatePalette"), region.get("BlockStates")) {
+        (Some(Value::List(palette)), Some(Value::LongArray(states))) => {
+            Ok((palette.len(), states.len()))
+        }
+        _ => panic!("expected a palette and block states"),
+    }
+}
+
+#[test]
+fn compact_on_write() -> Result<()> {
+    let volume = SIZE.volume() as usize;
+    let mut schem: Litematic = Litematic::new("Compact", "", "");
+    let mut region = region();
+    // grow the palette to 4 bits per block
+    for name in ["f", "g", "h"] {
+        region.add_to_palette(test_block(name));
+    }
+    schem.regions.push(region);
+    assert_eq!(written(&schem)?, (9, (volume * 4).div_ceil(64)));
+
+    // compacting when writing also reduces the bits per block, without changing the region
+    schem.compact_palettes = Some(PaletteOrder::FirstOccurrence);
+    assert_eq!(written(&schem)?, (4, (volume * 2).div_ceil(64)));
+    assert_eq!(schem.regions[0].block_palette().len(), 9);
+
+    let read: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    assert_eq!(names(&read.regions[0]), ["minecraft:air", "d", "b", "e"]);
+    assert_eq!(
+        block_names(&read.regions[0]),
+        block_names(&schem.regions[0])
+    );
+    Ok(())
+}
+
+/// Check that every palette entry is found at its own index and not added again.
+fn assert_palette_index<B>(region: &mut Region<B>)
+where
+    B: mcdata::BlockState + serde::Serialize + serde::de::DeserializeOwned + std::fmt::Debug,
+{
+    let palette = region.block_palette().to_vec();
+    for (id, state) in palette.iter().enumerate() {
+        assert_eq!(region.palette_id(state), Some(id), "{state:?}");
+        assert_eq!(region.add_to_palette(state.clone()), id, "{state:?}");
+    }
+    assert_eq!(region.block_palette().len(), palette.len());
+}
+
+#[test]
+fn palette_index_stays_in_sync() {
+    let stairs = |facing| {
+        block_with(
+            "minecraft:oak_stairs",
+            &[
+                ("facing", facing),
+                ("half", "bottom"),
+                ("shape", "straight"),
+            ],
+        )
+    };
+    let mut region: Region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(4, 1, 4));
+    region.set_block(BlockPos::new(0, 0, 0), block("minecraft:stone"));
+    region.set_block(BlockPos::new(1, 0, 0), stairs("north"));
+    region.set_block(BlockPos::new(2, 0, 0), stairs("east"));
+    region.set_block(BlockPos::new(3, 0, 0), block("minecraft:dirt"));
+    region.add_to_palette(block("minecraft:unused"));
+    assert_palette_index(&mut region);
+
+    // the same properties inserted in a different order are the same palette entry
+    let mut reversed = stairs("north");
+    reversed.properties = reversed
+        .properties
+        .into_iter()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    assert_eq!(region.palette_id(&reversed), Some(2));
+
+    region.compact_palette(Default::default());
+    assert_eq!(region.palette_id(&block("minecraft:unused")), None);
+    assert_palette_index(&mut region);
+
+    // replacing with a block that is already in the palette merges the entries
+    assert_eq!(region.replace(&stairs("east"), stairs("north")), 1);
+    assert_palette_index(&mut region);
+    region.compact_palette(Default::default());
+    assert_palette_index(&mut region);
+
+    // rotating north stairs turns them into the east stairs removed above
+    region.rotate(Rotation::Clockwise90);
+    assert_eq!(region.palette_id(&stairs("north")), None);
+    assert!(region.palette_id(&stairs("east")).is_some());
+    assert_palette_index(&mut region);
+    region.mirror(Mirror::FrontBack);
+    assert!(region.palette_id(&stairs("west")).is_some());
+    assert_palette_index(&mut region);
+
+    // raw NBT block states are compounds, whose hashes must not depend on the entry order either
+    let mut region: Region<fastnbt::Value> =
+        Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(2, 1, 1));
+    let nbt = fastnbt::to_value(stairs("south")).unwrap();
+    region.set_block(BlockPos::new(0, 0, 0), nbt.clone());
+    let reordered = match nbt {
+        fastnbt::Value::Compound(map) => fastnbt::Value::Compound(
+            map.into_iter()
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .collect(),
+        ),
+        _ => unreachable!(),
+    };
+    assert_eq!(region.palette_id(&reordered), Some(1));
+    region.rotate(Rotation::Clockwise180);
+    assert_palette_index(&mut region);
+}
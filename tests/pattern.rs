@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use mcdata::{
+    util::{BlockPos, Cuboid},
+    GenericBlockState,
+};
+use rustmatica::{Error, Mask, Pattern, Region};
+
+fn block(name: &'static str, properties: &[(&'static str, &'static str)]) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: properties
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect(),
+    }
+}
+
+#[test]
+fn parse_pattern() -> rustmatica::Result<()> {
+    let pattern = Pattern::parse("stone, 50%andesite")?;
+    assert_eq!(
+        pattern.entries().collect::<Vec<_>>(),
+        [
+            (50.0, &block("minecraft:stone", &[])),
+            (50.0, &block("minecraft:andesite", &[])),
+        ]
+    );
+
+    let pattern = Pattern::parse("1%mod:ore,3%oak_stairs[facing=north,half=top]")?;
+    assert_eq!(
+        pattern.entries().collect::<Vec<_>>(),
+        [
+            (25.0, &block("mod:ore", &[])),
+            (
+                75.0,
+                &block(
+                    "minecraft:oak_stairs",
+                    &[("facing", "north"), ("half", "top")]
+                )
+            ),
+        ]
+    );
+
+    for (input, position) in [("", 0), ("stone[facing]", 12), ("stone,", 6), ("0%air", 5)] {
+        assert!(
+            matches!(
+                Pattern::parse(input),
+                Err(Error::InvalidPattern { position: p, .. }) if p == position
+            ),
+            "{input}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn seeded_pattern() -> rustmatica::Result<()> {
+    let mut a = Pattern::parse("stone,25%andesite")?.with_seed(42);
+    let mut b = a.clone();
+    let mut andesite = 0;
+    for _ in 0..1000 {
+        let block: GenericBlockState = a.next_block()?;
+        assert_eq!(block, b.next_block::<GenericBlockState>()?);
+        if block.name == "minecraft:andesite" {
+            andesite += 1;
+        }
+    }
+    assert!((200..300).contains(&andesite), "{andesite}");
+    Ok(())
+}
+
+#[test]
+fn parse_mask() -> rustmatica::Result<()> {
+    let mask = Mask::parse("!air & #minecraft:logs")?;
+    assert!(mask.matches(&block("minecraft:oak_log", &[("axis", "y")])));
+    assert!(mask.matches(&block("minecraft:stripped_crimson_hyphae", &[])));
+    assert!(!mask.matches(&block("minecraft:stone", &[])));
+    assert!(!mask.matches(&block("minecraft:air", &[])));
+
+    let mask = Mask::parse("oak_stairs[facing=north], (stone | dirt) & !dirt")?;
+    assert!(mask.matches(&block(
+        "minecraft:oak_stairs",
+        &[("facing", "north"), ("half", "top")]
+    )));
+    assert!(!mask.matches(&block("minecraft:oak_stairs", &[("facing", "south")])));
+    assert!(mask.matches(&block("minecraft:stone", &[])));
+    assert!(!mask.matches(&block("minecraft:dirt", &[])));
+
+    for tag in Mask::TAGS {
+        Mask::parse(&format!("#{tag}"))?;
+    }
+    assert!(matches!(
+        Mask::parse("stone & #unknown"),
+        Err(Error::InvalidPattern { position: 9, .. })
+    ));
+    assert!(matches!(
+        Mask::parse("(stone"),
+        Err(Error::InvalidPattern { position: 6, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn tag_members() -> rustmatica::Result<()> {
+    let check = |tag: &str, members: &[&'static str], others: &[&'static str]| {
+        let mask = Mask::parse(&format!("#{tag}")).expect("tag should parse");
+        for name in members {
+            assert!(
+                mask.matches(&block(name, &[])),
+                "{name} should be in #{tag}"
+            );
+        }
+        for name in others {
+            assert!(
+                !mask.matches(&block(name, &[])),
+                "{name} should not be in #{tag}"
+            );
+        }
+    };
+
+    check(
+        "logs",
+        &[
+            "minecraft:oak_log",
+            "minecraft:stripped_cherry_wood",
+            "minecraft:crimson_stem",
+            "minecraft:stripped_warped_hyphae",
+        ],
+        &[
+            "minecraft:mushroom_stem",
+            "minecraft:pumpkin_stem",
+            "minecraft:melon_stem",
+            "minecraft:attached_pumpkin_stem",
+            "minecraft:attached_melon_stem",
+            "minecraft:oak_planks",
+        ],
+    );
+    check(
+        "stone_bricks",
+        &[
+            "minecraft:stone_bricks",
+            "minecraft:mossy_stone_bricks",
+            "minecraft:cracked_stone_bricks",
+            "minecraft:chiseled_stone_bricks",
+        ],
+        &[
+            "minecraft:end_stone_bricks",
+            "minecraft:polished_blackstone_bricks",
+            "minecraft:cracked_polished_blackstone_bricks",
+            "minecraft:stone_brick_stairs",
+        ],
+    );
+    check(
+        "wool_carpets",
+        &["minecraft:white_carpet", "minecraft:light_blue_carpet"],
+        &["minecraft:moss_carpet", "minecraft:pale_moss_carpet"],
+    );
+
+    // not a vanilla tag
+    assert!(Mask::parse("#carpets").is_err());
+    Ok(())
+}
+
+#[test]
+fn replace_with_pattern() -> rustmatica::Result<()> {
+    let mut region: Region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(8, 2, 8));
+    let all = Cuboid::new(BlockPos::new(0, 0, 0), BlockPos::new(8, 2, 8));
+    let ground = Cuboid::new(BlockPos::new(0, 0, 0), BlockPos::new(8, 1, 8));
+    region.fill(ground, block("minecraft:stone", &[]));
+
+    let mut pattern = Pattern::parse("granite,diorite")?.with_seed(1);
+    let replaced = region.replace_pattern(all, &Mask::parse("stone")?, &mut pattern)?;
+    assert_eq!(replaced, 64);
+    let mut counts = HashMap::new();
+    for (pos, block) in region.blocks() {
+        *counts.entry(block.name.clone()).or_insert(0) += 1;
+        if pos.y == 1 {
+            assert_eq!(block.name, "minecraft:air");
+        }
+    }
+    assert_eq!(counts.get("minecraft:stone"), None);
+    assert_eq!(
+        counts["minecraft:granite"] + counts["minecraft:diorite"],
+        64
+    );
+    assert!(counts["minecraft:granite"] > 0 && counts["minecraft:diorite"] > 0);
+
+    let filled = region.fill_pattern(all, &mut Pattern::parse("glass")?)?;
+    assert_eq!(filled, 128);
+    assert_eq!(region.total_blocks(), 128);
+    Ok(())
+}
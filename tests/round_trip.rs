@@ -0,0 +1,372 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use fastnbt::Value;
+use flate2::read::GzDecoder;
+use mcdata::util::BlockPos;
+use rustmatica::{Error, Litematic, LitematicMetadata, Region};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn litematic_files(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(litematic_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "litematic") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut nbt = vec![];
+    GzDecoder::new(bytes).read_to_end(&mut nbt)?;
+    Ok(nbt)
+}
+
+/// Split off the first `len` bytes of `nbt`.
+fn take<'a>(nbt: &mut &'a [u8], len: usize) -> &'a [u8] {
+    let (head, tail) = nbt.split_at(len);
+    *nbt = tail;
+    head
+}
+
+/// Read a big endian length of `bytes` bytes and copy it to `out`.
+fn copy_len(nbt: &mut &[u8], bytes: usize, out: &mut Vec<u8>) -> usize {
+    let len = take(nbt, bytes);
+    out.extend(len);
+    len.iter().fold(0, |len, byte| len << 8 | *byte as usize)
+}
+
+/// Copy the payload of a tag of the given type to `out`, with the entries of every compound sorted
+/// by name.
+///
+/// `TimeModified` is set to the time of writing and `TotalBlocks` is out of date in some files of
+/// the corpus, so both are dropped from the `Metadata` compound at `path`.
+fn copy_payload(tag: u8, nbt: &mut &[u8], path: &str, out: &mut Vec<u8>) {
+    match tag {
+        1 => out.extend(take(nbt, 1)),
+        2 => out.extend(take(nbt, 2)),
+        3 | 5 => out.extend(take(nbt, 4)),
+        4 | 6 => out.extend(take(nbt, 8)),
+        7 => {
+            let len = copy_len(nbt, 4, out);
+            out.extend(take(nbt, len));
+        }
+        11 => {
+            let len = copy_len(nbt, 4, out);
+            out.extend(take(nbt, len * 4));
+        }
+        12 => {
+            let len = copy_len(nbt, 4, out);
+            out.extend(take(nbt, len * 8));
+        }
+        8 => {
+            let len = copy_len(nbt, 2, out);
+            out.extend(take(nbt, len));
+        }
+        9 => {
+            let element = take(nbt, 1)[0];
+            out.push(element);
+            for _ in 0..copy_len(nbt, 4, out) {
+                copy_payload(element, nbt, "", out);
+            }
+        }
+        10 => {
+            let mut entries = vec![];
+            loop {
+                let tag = take(nbt, 1)[0];
+                if tag == 0 {
+                    break;
+                }
+                let mut entry = vec![tag];
+                let len = copy_len(nbt, 2, &mut entry);
+                let name = String::from_utf8_lossy(take(nbt, len)).into_owned();
+                entry.extend(name.as_bytes());
+                let path = format!("{path}/{name}");
+                copy_payload(tag, nbt, &path, &mut entry);
+                if !matches!(
+                    path.as_str(),
+                    "/Metadata/TimeModified" | "/Metadata/TotalBlocks"
+                ) {
+                    entries.push((name, entry));
+                }
+            }
+            entries.sort();
+            out.extend(entries.into_iter().flat_map(|(_, entry)| entry));
+            out.push(0);
+        }
+        _ => panic!("invalid tag type {tag}"),
+    }
+}
+
+/// Bring uncompressed NBT into a form in which it can be compared byte by byte.
+///
+/// Litematica writes compound entries in the order of Java's `HashMap`, which the block state and
+/// entity types don't keep, so the entries of every compound are sorted. Everything else, including
+/// the types of empty lists and integers, is left as it is.
+fn normalize(mut nbt: &[u8]) -> Vec<u8> {
+    let tag = take(&mut nbt, 1)[0];
+    let mut out = vec![tag];
+    let len = copy_len(&mut nbt, 2, &mut out);
+    out.extend(take(&mut nbt, len));
+    copy_payload(tag, &mut nbt, "", &mut out);
+    assert!(nbt.is_empty(), "trailing bytes after the root compound");
+    out
+}
+
+#[test]
+fn corpus_round_trip() -> Result<()> {
+    let files = litematic_files("test_files")?;
+    assert!(!files.is_empty());
+    for path in files {
+        // compare the uncompressed NBT, as compression is slow in debug builds
+        let nbt = decompress(&fs::read(&path)?)?;
+        let schem: Litematic = Litematic::from_uncompressed_bytes(&nbt)?;
+        let written = schem.to_uncompressed_bytes()?;
+        let metadata = |nbt: &[u8]| -> Result<_> {
+            let Value::Compound(mut root) = fastnbt::from_bytes(nbt)? else {
+                panic!("'{}' is not a compound", path.display());
+            };
+            match root.remove("Metadata") {
+                Some(Value::Compound(metadata)) => Ok(metadata),
+                _ => panic!("'{}' has no metadata", path.display()),
+            }
+        };
+        let (before, after) = (metadata(&nbt)?, metadata(&written)?);
+        let (Some(Value::Long(modified_before)), Some(Value::Long(modified))) =
+            (before.get("TimeModified"), after.get("TimeModified"))
+        else {
+            panic!("'{}' has no modification time", path.display());
+        };
+        assert!(modified >= modified_before, "'{}'", path.display());
+        assert_eq!(
+            after.get("TotalBlocks").and_then(Value::as_i64),
+            Some(schem.total_blocks()),
+            "'{}'",
+            path.display()
+        );
+
+        let before = normalize(&nbt);
+        let after = normalize(&written);
+        assert!(
+            before == after,
+            "'{}' changed after a round-trip",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn unknown_tags_round_trip() -> Result<()> {
+    let mut schem: Litematic = Litematic::read_file("test_files/axolotl.litematic")?;
+    let custom = Value::Compound(HashMap::from([(
+        "Tool".to_owned(),
+        Value::String("in-house".to_owned()),
+    )]));
+    schem
+        .metadata
+        .root_extra
+        .insert("Custom".to_owned(), custom.clone());
+    schem
+        .metadata
+        .extra
+        .insert("Custom".to_owned(), custom.clone());
+    schem.regions[0]
+        .extra
+        .insert("Custom".to_owned(), custom.clone());
+
+    let mut schem: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    assert_eq!(schem.metadata.root_extra.get("Custom"), Some(&custom));
+    assert_eq!(schem.metadata.extra.get("Custom"), Some(&custom));
+    assert_eq!(schem.regions[0].extra.get("Custom"), Some(&custom));
+
+    // unknown tags are written in the order they were read in
+    for key in ["Zeta", "Alpha", "Mu", "Beta"] {
+        schem.metadata.extra.insert(key.to_owned(), Value::Byte(1));
+    }
+    let schem: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    let read_keys = schem.metadata.extra.keys().collect::<Vec<_>>();
+    assert_eq!(read_keys, ["Custom", "Zeta", "Alpha", "Mu", "Beta"]);
+    Ok(())
+}
+
+#[test]
+fn region_order_round_trip() -> Result<()> {
+    let mut schem: Litematic = Litematic::new("Order", "", "");
+    let names = [
+        "Zeta", "Alpha", "Mu", "Beta", "Omega", "Gamma", "Kappa", "Delta",
+    ];
+    for name in names {
+        schem.regions.push(Region::new(
+            name,
+            BlockPos::new(0, 0, 0),
+            BlockPos::new(1, 1, 1),
+        ));
+    }
+
+    let schem: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    let read_names = schem.regions.iter().map(|r| &r.name).collect::<Vec<_>>();
+    assert_eq!(read_names, names);
+    Ok(())
+}
+
+#[test]
+fn duplicate_region_names() -> Result<()> {
+    let mut schem: Litematic = Litematic::new("Duplicates", "", "");
+    for name in ["Region", "Region (2)", "Region", "Region"] {
+        schem.regions.push(Region::new(
+            name,
+            BlockPos::new(0, 0, 0),
+            BlockPos::new(1, 1, 1),
+        ));
+    }
+    assert!(matches!(
+        schem.to_uncompressed_bytes(),
+        Err(Error::DuplicateRegionName { name }) if name == "Region"
+    ));
+
+    schem.make_region_names_unique();
+    let names = schem.regions.iter().map(|r| &r.name).collect::<Vec<_>>();
+    assert_eq!(names, ["Region", "Region (2)", "Region (3)", "Region (4)"]);
+    schem.to_uncompressed_bytes()?;
+    Ok(())
+}
+
+#[test]
+fn format_versions() -> Result<()> {
+    let mut schem: Litematic = Litematic::read_file("test_files/axolotl.litematic")?;
+    assert!(matches!(
+        schem.set_version(8, None),
+        Err(Error::UnsupportedVersion {
+            format: "litematica",
+            version: 8
+        })
+    ));
+
+    schem.set_version(4, None)?;
+    let nbt = schem.to_uncompressed_bytes()?;
+    let Value::Compound(root) = fastnbt::from_bytes(&nbt)? else {
+        panic!("expected a compound");
+    };
+    assert!(!root.contains_key("MinecraftDataVersion"));
+    let read: Litematic = Litematic::from_uncompressed_bytes(&nbt)?;
+    assert_eq!(read.metadata.version, 4);
+    assert_eq!(read.metadata.minecraft_data_version, 1343);
+    assert_eq!(read.regions, schem.regions);
+
+    schem.set_version(7, Some(1))?;
+    let read: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    assert_eq!(
+        (read.metadata.version, read.metadata.sub_version),
+        (7, Some(1))
+    );
+    assert_eq!(
+        read.metadata.minecraft_data_version,
+        schem.metadata.minecraft_data_version
+    );
+    Ok(())
+}
+
+#[test]
+fn version_4_fixture() -> Result<()> {
+    // the regions are stored before the version, so they are read before it can be checked
+    let schem: Litematic = Litematic::read_file("test_files/format_versions/v4.litematic")?;
+    assert_eq!(
+        (schem.metadata.version, schem.metadata.sub_version),
+        (4, None)
+    );
+    assert_eq!(schem.metadata.minecraft_data_version, 1343);
+    let region = &schem.regions[0];
+    let granite = region.get_block(BlockPos::new(0, 0, 0));
+    assert_eq!(granite.name, "minecraft:stone");
+    assert_eq!(granite.properties["variant"], "granite");
+    assert_eq!(
+        region.get_block(BlockPos::new(1, 1, 1)).name,
+        "minecraft:chest"
+    );
+    assert_eq!(region.block_entities.len(), 2);
+
+    let bytes = decompress(&fs::read("test_files/format_versions/v4.litematic")?)?;
+    let read: Litematic = Litematic::from_uncompressed_bytes(&bytes)?;
+    assert_eq!(read.regions, schem.regions);
+    Ok(())
+}
+
+#[test]
+fn version_7_fixture() -> Result<()> {
+    let mut schem: Litematic = Litematic::read_file("test_files/format_versions/v7.litematic")?;
+    assert_eq!(
+        (schem.metadata.version, schem.metadata.sub_version),
+        (7, Some(1))
+    );
+    assert_eq!(schem.metadata.minecraft_data_version, 3953);
+    let region = &schem.regions[0];
+    assert_eq!(
+        region.get_block(BlockPos::new(0, 1, 0)).name,
+        "minecraft:water"
+    );
+    assert_eq!(region.entities[0].uuid, 1 << 96 | 2 << 64 | 3 << 32 | 4);
+    assert_eq!(region.pending_block_ticks[0].sub_tick, 7);
+    assert_eq!(region.pending_fluid_ticks[0].fluid, "minecraft:water");
+
+    // version 4 can't store the pending fluid ticks, so they aren't dropped silently
+    schem.set_version(4, None)?;
+    assert!(matches!(
+        schem.to_uncompressed_bytes(),
+        Err(Error::UnsupportedByVersion {
+            format: "litematica",
+            version: 4,
+            region,
+            data: "pending fluid ticks",
+        }) if region == "Main"
+    ));
+    schem.regions[0].pending_fluid_ticks.clear();
+    let read: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    assert_eq!(read.metadata.version, 4);
+    assert_eq!(
+        read.regions[0].pending_block_ticks,
+        schem.regions[0].pending_block_ticks
+    );
+    Ok(())
+}
+
+#[test]
+fn metadata_write_to() -> Result<()> {
+    let metadata = LitematicMetadata::read_file("test_files/axolotl.litematic")?;
+    let mut bytes = vec![];
+    metadata.write_to(&mut bytes)?;
+
+    let read = LitematicMetadata::from_bytes(&bytes)?;
+    assert_eq!(
+        (&read.name, &read.author, &read.description),
+        (&metadata.name, &metadata.author, &metadata.description)
+    );
+    assert_eq!(
+        (read.version, read.sub_version, read.minecraft_data_version),
+        (
+            metadata.version,
+            metadata.sub_version,
+            metadata.minecraft_data_version
+        )
+    );
+    assert_eq!(read.time_created, metadata.time_created);
+    assert_eq!(read.stored_totals, metadata.stored_totals);
+
+    // the metadata is written as a schematic without any regions
+    let Value::Compound(root) = fastnbt::from_bytes(&decompress(&bytes)?)? else {
+        panic!("expected a compound");
+    };
+    assert_eq!(root.get("Regions"), Some(&Value::Compound(HashMap::new())));
+    let schem: Litematic = Litematic::from_bytes(&bytes)?;
+    assert!(schem.regions.is_empty());
+    Ok(())
+}
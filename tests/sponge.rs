@@ -0,0 +1,472 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use fastnbt::{ByteArray, IntArray, Value};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use mcdata::{util::BlockPos, GenericBlockState};
+use rustmatica::{Error, Litematic, Region, SpongeVersion};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+fn block_with(
+    name: &'static str,
+    properties: &[(&'static str, &'static str)],
+) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: properties
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect(),
+    }
+}
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The number of entries in the palette of [`schematic`].
+const PALETTE_LEN: i32 = 200;
+
+fn compound<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Compound(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
+
+fn int_array(values: [i32; 3]) -> Value {
+    Value::IntArray(IntArray::new(values.to_vec()))
+}
+
+fn byte_array(values: &[i8]) -> Value {
+    Value::ByteArray(ByteArray::new(values.to_vec()))
+}
+
+fn double_list(values: [f64; 3]) -> Value {
+    Value::List(values.into_iter().map(Value::Double).collect())
+}
+
+/// A 2x1x2 palette containing stone and a block with a palette id which needs two varint bytes.
+fn palette() -> Value {
+    let mut palette = HashMap::from([
+        ("minecraft:air".to_owned(), Value::Int(0)),
+        ("minecraft:stone".to_owned(), Value::Int(1)),
+        (
+            "minecraft:oak_log[axis=x]".to_owned(),
+            Value::Int(PALETTE_LEN - 1),
+        ),
+    ]);
+    for id in 2..PALETTE_LEN - 1 {
+        palette.insert(format!("test:block_{id}"), Value::Int(id));
+    }
+    Value::Compound(palette)
+}
+
+/// The block data matching [`palette`].
+fn block_data() -> Vec<i8> {
+    // stone, air, oak log (199 = 0b1_1000111), stone
+    vec![1, 0, -57, 1, 1]
+}
+
+/// The NBT of a Sponge schematic of the given version as described by the specification.
+fn schematic(version: i32, palette: Value, data: &[i8]) -> Value {
+    let metadata = compound([
+        ("Name", Value::String("Sponge".to_owned())),
+        ("Author", Value::String("Tester".to_owned())),
+    ]);
+    let chest = |data| {
+        let mut nbt = HashMap::from([
+            ("Id".to_owned(), Value::String("minecraft:chest".to_owned())),
+            ("Pos".to_owned(), int_array([1, 0, 1])),
+        ]);
+        nbt.extend(data);
+        Value::Compound(nbt)
+    };
+    let pig = |data| {
+        let mut nbt = HashMap::from([
+            ("Id".to_owned(), Value::String("minecraft:pig".to_owned())),
+            ("Pos".to_owned(), double_list([1.5, 0.0, 0.5])),
+        ]);
+        nbt.extend(data);
+        Value::Compound(nbt)
+    };
+    let lock = || ("Lock".to_owned(), Value::String("key".to_owned()));
+    let health = || ("Health".to_owned(), Value::Float(10.0));
+    match version {
+        2 => compound([
+            ("Version", Value::Int(2)),
+            ("DataVersion", Value::Int(3700)),
+            ("Metadata", metadata),
+            ("Width", Value::Short(2)),
+            ("Height", Value::Short(1)),
+            ("Length", Value::Short(2)),
+            ("Offset", int_array([10, 64, -5])),
+            ("PaletteMax", Value::Int(PALETTE_LEN)),
+            ("Palette", palette),
+            ("BlockData", byte_array(data)),
+            ("BlockEntities", Value::List(vec![chest(vec![lock()])])),
+            ("Entities", Value::List(vec![pig(vec![health()])])),
+        ]),
+        _ => compound([(
+            "Schematic",
+            compound([
+                ("Version", Value::Int(version)),
+                ("DataVersion", Value::Int(3953)),
+                ("Metadata", metadata),
+                ("Width", Value::Short(2)),
+                ("Height", Value::Short(1)),
+                ("Length", Value::Short(2)),
+                ("Offset", int_array([10, 64, -5])),
+                (
+                    "Blocks",
+                    compound([
+                        ("Palette", palette),
+                        ("Data", byte_array(data)),
+                        (
+                            "BlockEntities",
+                            Value::List(vec![chest(vec![(
+                                "Data".to_owned(),
+                                compound([("Lock", Value::String("key".to_owned()))]),
+                            )])]),
+                        ),
+                    ]),
+                ),
+                (
+                    "Entities",
+                    Value::List(vec![pig(vec![(
+                        "Data".to_owned(),
+                        compound([("Health", Value::Float(10.0))]),
+                    )])]),
+                ),
+            ]),
+        )]),
+    }
+}
+
+fn compress(nbt: &Value) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    let mut gz = GzEncoder::new(&mut buf, Compression::default());
+    gz.write_all(&fastnbt::to_bytes(nbt)?)?;
+    gz.finish()?;
+    Ok(buf)
+}
+
+fn decompress(bytes: &[u8]) -> Result<Value> {
+    let mut nbt = vec![];
+    GzDecoder::new(bytes).read_to_end(&mut nbt)?;
+    Ok(fastnbt::from_bytes(&nbt)?)
+}
+
+fn get<'a>(nbt: &'a Value, path: &[&str]) -> &'a Value {
+    path.iter().fold(nbt, |nbt, key| match nbt {
+        Value::Compound(map) => &map[*key],
+        _ => panic!("expected a compound at '{key}'"),
+    })
+}
+
+/// A hashable representation of a position.
+fn key(pos: BlockPos) -> (i32, i32, i32) {
+    (pos.x, pos.y, pos.z)
+}
+
+fn read(version: i32, palette: Value, data: &[i8]) -> Result<rustmatica::Result<Litematic>> {
+    Ok(Litematic::from_sponge(&compress(&schematic(
+        version, palette, data,
+    ))?))
+}
+
+fn check_schematic(schem: &Litematic) {
+    assert_eq!(schem.metadata.name, "Sponge");
+    assert_eq!(schem.metadata.author, "Tester");
+    let [region] = &schem.regions[..] else {
+        panic!("expected a single region");
+    };
+    assert_eq!(region.position, BlockPos::new(10, 64, -5));
+    assert_eq!(region.size, BlockPos::new(2, 1, 2));
+    assert_eq!(
+        region.get_block(BlockPos::new(0, 0, 0)),
+        &block("minecraft:stone")
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(1, 0, 0)),
+        &block("minecraft:air")
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(0, 0, 1)),
+        &block_with("minecraft:oak_log", &[("axis", "x")])
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(1, 0, 1)),
+        &block("minecraft:stone")
+    );
+
+    let [chest] = &region.block_entities[..] else {
+        panic!("expected a single block entity");
+    };
+    assert_eq!(chest.id, "minecraft:chest");
+    assert_eq!(chest.pos, BlockPos::new(1, 0, 1));
+    assert_eq!(
+        chest.properties.get("Lock"),
+        Some(&Value::String("key".to_owned()))
+    );
+
+    let [pig] = &region.entities[..] else {
+        panic!("expected a single entity");
+    };
+    assert_eq!(pig.id, "minecraft:pig");
+    assert_eq!(pig.properties.get("Health"), Some(&Value::Float(10.0)));
+    assert_eq!(
+        pig.properties.get("Pos"),
+        Some(&double_list([1.5, 0.0, 0.5]))
+    );
+}
+
+#[test]
+fn read_v2() -> Result<()> {
+    check_schematic(&read(2, palette(), &block_data())??);
+    Ok(())
+}
+
+#[test]
+fn read_v3() -> Result<()> {
+    check_schematic(&read(3, palette(), &block_data())??);
+    Ok(())
+}
+
+#[test]
+fn write_versions() -> Result<()> {
+    let schem = read(3, palette(), &block_data())??;
+
+    let v2 = decompress(&schem.to_sponge(SpongeVersion::V2)?)?;
+    assert_eq!(get(&v2, &["Version"]), &Value::Int(2));
+    assert_eq!(get(&v2, &["Offset"]), &int_array([10, 64, -5]));
+    let Value::Compound(palette) = get(&v2, &["Palette"]) else {
+        panic!("expected a palette compound");
+    };
+    assert_eq!(get(&v2, &["PaletteMax"]), &Value::Int(palette.len() as i32));
+    assert_eq!(
+        get(&v2, &["BlockEntities"]),
+        &Value::List(vec![compound([
+            ("Id", Value::String("minecraft:chest".to_owned())),
+            ("Pos", int_array([1, 0, 1])),
+            ("Lock", Value::String("key".to_owned())),
+        ])])
+    );
+    check_schematic(&Litematic::from_sponge(
+        &schem.to_sponge(SpongeVersion::V2)?,
+    )?);
+
+    let v3 = decompress(&schem.to_sponge(SpongeVersion::V3)?)?;
+    assert_eq!(get(&v3, &["Schematic", "Version"]), &Value::Int(3));
+    assert_eq!(
+        get(&v3, &["Schematic", "Blocks", "BlockEntities"]),
+        &Value::List(vec![compound([
+            ("Id", Value::String("minecraft:chest".to_owned())),
+            ("Pos", int_array([1, 0, 1])),
+            (
+                "Data",
+                compound([("Lock", Value::String("key".to_owned()))])
+            ),
+        ])])
+    );
+    check_schematic(&Litematic::from_sponge(
+        &schem.to_sponge(SpongeVersion::V3)?,
+    )?);
+    Ok(())
+}
+
+#[test]
+fn litematic_round_trip() -> Result<()> {
+    for path in ["test_files/axolotl.litematic", "test_files/donut.litematic"] {
+        let schem: Litematic = Litematic::read_file(path)?;
+        // later regions only take precedence with non-air blocks
+        let mut expected = HashMap::new();
+        for region in &schem.regions {
+            for (pos, block) in region.blocks() {
+                if block.name != "minecraft:air" {
+                    expected.insert(key(region.pos_to_global(pos)), block.clone());
+                }
+            }
+        }
+
+        for version in [SpongeVersion::V2, SpongeVersion::V3] {
+            let sponge: Litematic = Litematic::from_sponge(&schem.to_sponge(version)?)?;
+            let [region] = &sponge.regions[..] else {
+                panic!("expected a single region");
+            };
+            let actual = region
+                .blocks()
+                .filter(|(_, block)| block.name != "minecraft:air")
+                .map(|(pos, block)| (key(region.pos_to_global(pos)), block.clone()))
+                .collect::<HashMap<_, GenericBlockState>>();
+            assert_eq!(actual.len(), expected.len(), "{path} {version:?}");
+            for (pos, block) in &expected {
+                assert_eq!(actual.get(pos), Some(block), "{path} {version:?}");
+            }
+
+            // missing block entity ids are filled in from the block, so only compare positions
+            let block_entities = |schem: &Litematic| {
+                let mut positions = schem
+                    .regions
+                    .iter()
+                    .flat_map(|region| {
+                        region
+                            .block_entities
+                            .iter()
+                            .map(|block_entity| key(region.pos_to_global(block_entity.pos)))
+                    })
+                    .collect::<Vec<_>>();
+                positions.sort();
+                positions
+            };
+            assert_eq!(block_entities(&sponge), block_entities(&schem), "{path}");
+            assert_eq!(
+                region.entities.len(),
+                schem
+                    .regions
+                    .iter()
+                    .map(|r| r.entities.len())
+                    .sum::<usize>(),
+                "{path}"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn negative_palette_id() -> Result<()> {
+    let Value::Compound(mut entries) = palette() else {
+        unreachable!();
+    };
+    entries.insert("minecraft:dirt".to_owned(), Value::Int(-1));
+    assert!(matches!(
+        read(2, Value::Compound(entries), &block_data())?,
+        Err(Error::PaletteIndexOutOfRange { region, .. }) if region == "Sponge"
+    ));
+    Ok(())
+}
+
+#[test]
+fn palette_id_out_of_range() -> Result<()> {
+    let Value::Compound(mut entries) = palette() else {
+        unreachable!();
+    };
+    entries.insert("minecraft:dirt".to_owned(), Value::Int(i32::MAX));
+    assert!(matches!(
+        read(3, Value::Compound(entries), &block_data())?,
+        Err(Error::PaletteIndexOutOfRange { index, .. }) if index == i32::MAX as usize
+    ));
+
+    // block data referencing an id that is not in the palette
+    assert!(matches!(
+        read(3, palette(), &[1, 0, -1, 1, 1])?,
+        Err(Error::PaletteIndexOutOfRange { index: 255, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn overlong_varint() -> Result<()> {
+    let mut data = vec![1, 0, 1];
+    data.extend([-1; 40]);
+    data.push(1);
+    assert!(matches!(
+        read(2, palette(), &data)?,
+        Err(Error::InvalidVarInt { region }) if region == "Sponge"
+    ));
+    Ok(())
+}
+
+#[test]
+fn short_block_data() -> Result<()> {
+    assert!(matches!(
+        read(3, palette(), &[1, 0, 1])?,
+        Err(Error::BlockStatesLengthMismatch {
+            expected: 4,
+            actual: 3,
+            ..
+        })
+    ));
+    Ok(())
+}
+
+#[test]
+fn without_blocks() -> Result<()> {
+    let without_blocks = |size: i16| {
+        let mut nbt = schematic(3, palette(), &block_data());
+        let Value::Compound(root) = &mut nbt else {
+            unreachable!();
+        };
+        let Some(Value::Compound(schematic)) = root.get_mut("Schematic") else {
+            unreachable!();
+        };
+        schematic.remove("Blocks");
+        for key in ["Width", "Height", "Length"] {
+            schematic.insert(key.to_owned(), Value::Short(size));
+        }
+        compress(&nbt)
+    };
+
+    // schematics only storing entities are filled with air
+    let schem: Litematic = Litematic::from_sponge(&without_blocks(2)?)?;
+    let [region] = &schem.regions[..] else {
+        panic!("expected a single region");
+    };
+    assert_eq!(region.size, BlockPos::new(2, 2, 2));
+    assert_eq!(region.total_blocks(), 0);
+    assert!(region.block_entities.is_empty());
+    assert_eq!(region.entities.len(), 1);
+
+    // the size isn't backed by any block data, so it can't be arbitrarily large
+    assert!(matches!(
+        Litematic::<GenericBlockState>::from_sponge(&without_blocks(-1)?),
+        Err(Error::RegionTooLarge {
+            format: "sponge schematic",
+            region,
+        }) if region == "Sponge"
+    ));
+    Ok(())
+}
+
+#[test]
+fn unsupported_version() -> Result<()> {
+    assert!(matches!(
+        read(4, palette(), &block_data())?,
+        Err(Error::UnsupportedVersion {
+            format: "sponge schematic",
+            version: 4
+        })
+    ));
+    Ok(())
+}
+
+#[test]
+fn too_large() {
+    let mut schem: Litematic = Litematic::new("Large", "", "");
+    schem.regions.push(Region::new(
+        "Start",
+        BlockPos::new(0, 0, 0),
+        BlockPos::new(1, 1, 1),
+    ));
+    schem.regions.push(Region::new(
+        "End",
+        BlockPos::new(70_000, 0, 0),
+        BlockPos::new(1, 1, 1),
+    ));
+    assert!(matches!(
+        schem.to_sponge(SpongeVersion::V3),
+        Err(Error::RegionTooLarge {
+            format: "sponge schematic",
+            region
+        }) if region == "Large"
+    ));
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use fastnbt::{IntArray, Value};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use mcdata::{util::BlockPos, GenericBlockEntity, GenericBlockState, GenericEntity};
+use rustmatica::{Error, Region};
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+fn block_with(
+    name: &'static str,
+    properties: &[(&'static str, &'static str)],
+) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: properties
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect(),
+    }
+}
+
+fn block_entity(id: &'static str, pos: BlockPos) -> GenericBlockEntity {
+    GenericBlockEntity {
+        id: id.into(),
+        pos,
+        properties: HashMap::new(),
+    }
+}
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn compound<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Compound(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
+
+fn string(value: &str) -> Value {
+    Value::String(value.to_owned())
+}
+
+fn int_list(values: [i32; 3]) -> Value {
+    Value::List(values.into_iter().map(Value::Int).collect())
+}
+
+fn double_list(values: [f64; 3]) -> Value {
+    Value::List(values.into_iter().map(Value::Double).collect())
+}
+
+fn state(name: &str, properties: &[(&str, &str)]) -> Value {
+    let mut nbt = HashMap::from([("Name".to_owned(), string(name))]);
+    if !properties.is_empty() {
+        nbt.insert(
+            "Properties".to_owned(),
+            Value::Compound(
+                properties
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), string(value)))
+                    .collect(),
+            ),
+        );
+    }
+    Value::Compound(nbt)
+}
+
+fn palette() -> Value {
+    Value::List(vec![
+        state("minecraft:stone", &[]),
+        state(
+            "minecraft:oak_stairs",
+            &[
+                ("facing", "east"),
+                ("half", "bottom"),
+                ("shape", "straight"),
+                ("waterlogged", "false"),
+            ],
+        ),
+        state("minecraft:air", &[]),
+        state(
+            "minecraft:chest",
+            &[
+                ("facing", "north"),
+                ("type", "single"),
+                ("waterlogged", "false"),
+            ],
+        ),
+    ])
+}
+
+fn structure_block(pos: [i32; 3], state: i32) -> Value {
+    compound([("pos", int_list(pos)), ("state", Value::Int(state))])
+}
+
+/// The NBT of a 2x2x2 structure as saved by a structure block, with `palette` stored under the
+/// given key and a structure void at (1, 1, 1).
+fn structure(palette_key: &str, palette: Value) -> Value {
+    let chest = compound([
+        ("pos", int_list([0, 1, 0])),
+        ("state", Value::Int(3)),
+        (
+            "nbt",
+            compound([("id", string("minecraft:chest")), ("Lock", string("key"))]),
+        ),
+    ]);
+    compound([
+        ("DataVersion", Value::Int(3953)),
+        ("size", int_list([2, 2, 2])),
+        (palette_key, palette),
+        (
+            "blocks",
+            Value::List(vec![
+                structure_block([0, 0, 0], 0),
+                structure_block([1, 0, 0], 1),
+                structure_block([0, 0, 1], 2),
+                structure_block([1, 0, 1], 0),
+                chest,
+                structure_block([1, 1, 0], 2),
+                structure_block([0, 1, 1], 1),
+            ]),
+        ),
+        (
+            "entities",
+            Value::List(vec![compound([
+                ("pos", double_list([1.5, 0.0, 0.25])),
+                ("blockPos", int_list([1, 0, 0])),
+                (
+                    "nbt",
+                    compound([
+                        ("id", string("minecraft:pig")),
+                        ("UUID", Value::IntArray(IntArray::new(vec![1, 2, 3, 4]))),
+                        ("Pos", double_list([101.5, 64.0, 200.25])),
+                    ]),
+                ),
+            ])]),
+        ),
+    ])
+}
+
+fn compress(nbt: &Value) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    let mut gz = GzEncoder::new(&mut buf, Compression::default());
+    gz.write_all(&fastnbt::to_bytes(nbt)?)?;
+    gz.finish()?;
+    Ok(buf)
+}
+
+fn decompress(bytes: &[u8]) -> Result<Value> {
+    let mut nbt = vec![];
+    GzDecoder::new(bytes).read_to_end(&mut nbt)?;
+    Ok(fastnbt::from_bytes(&nbt)?)
+}
+
+fn get<'a>(nbt: &'a Value, key: &str) -> &'a Value {
+    match nbt {
+        Value::Compound(map) => &map[key],
+        _ => panic!("expected a compound at '{key}'"),
+    }
+}
+
+fn stairs() -> mcdata::GenericBlockState {
+    block_with(
+        "minecraft:oak_stairs",
+        &[
+            ("facing", "east"),
+            ("half", "bottom"),
+            ("shape", "straight"),
+            ("waterlogged", "false"),
+        ],
+    )
+}
+
+fn check_region(region: &Region) {
+    assert_eq!(region.name, "Structure");
+    assert_eq!(region.position, BlockPos::new(0, 0, 0));
+    assert_eq!(region.size, BlockPos::new(2, 2, 2));
+    let expected = [
+        ((0, 0, 0), block("minecraft:stone")),
+        ((1, 0, 0), stairs()),
+        ((0, 0, 1), block("minecraft:air")),
+        ((1, 0, 1), block("minecraft:stone")),
+        (
+            (0, 1, 0),
+            block_with(
+                "minecraft:chest",
+                &[
+                    ("facing", "north"),
+                    ("type", "single"),
+                    ("waterlogged", "false"),
+                ],
+            ),
+        ),
+        ((1, 1, 0), block("minecraft:air")),
+        ((0, 1, 1), stairs()),
+        // the structure void is filled with air
+        ((1, 1, 1), block("minecraft:air")),
+    ];
+    for ((x, y, z), block) in expected {
+        assert_eq!(
+            region.get_block(BlockPos::new(x, y, z)),
+            &block,
+            "{x} {y} {z}"
+        );
+    }
+
+    let [chest] = &region.block_entities[..] else {
+        panic!("expected a single block entity");
+    };
+    assert_eq!(chest.id, "minecraft:chest");
+    assert_eq!(chest.pos, BlockPos::new(0, 1, 0));
+    assert_eq!(chest.properties.get("Lock"), Some(&string("key")));
+
+    // the entity position is taken from the structure and not from the entity nbt
+    let [pig] = &region.entities[..] else {
+        panic!("expected a single entity");
+    };
+    assert_eq!(pig.id, "minecraft:pig");
+    assert_eq!(
+        pig.properties.get("Pos"),
+        Some(&double_list([1.5, 0.0, 0.25]))
+    );
+}
+
+#[test]
+fn import() -> Result<()> {
+    let region: Region =
+        Region::from_structure(&compress(&structure("palette", palette()))?, "Structure")?;
+    check_region(&region);
+    Ok(())
+}
+
+#[test]
+fn import_palettes() -> Result<()> {
+    // structures with random variants, like shipwrecks, store multiple palettes
+    let Value::List(mut variant) = palette() else {
+        unreachable!();
+    };
+    variant[0] = state("minecraft:mossy_cobblestone", &[]);
+    let region: Region = Region::from_structure(
+        &compress(&structure(
+            "palettes",
+            Value::List(vec![palette(), Value::List(variant)]),
+        ))?,
+        "Structure",
+    )?;
+    check_region(&region);
+    Ok(())
+}
+
+#[test]
+fn import_palette_index_out_of_range() -> Result<()> {
+    let Value::List(mut palette) = palette() else {
+        unreachable!();
+    };
+    palette.truncate(3);
+    assert!(matches!(
+        Region::<mcdata::GenericBlockState>::from_structure(
+            &compress(&structure("palette", Value::List(palette)))?,
+            "Structure",
+        ),
+        Err(Error::PaletteIndexOutOfRange { region, index: 3 }) if region == "Structure"
+    ));
+    Ok(())
+}
+
+#[test]
+fn import_too_large() -> Result<()> {
+    let Value::Compound(mut nbt) = structure("palette", palette()) else {
+        unreachable!();
+    };
+    nbt.insert("size".to_owned(), int_list([100_000, 100_000, 100_000]));
+    assert!(matches!(
+        Region::<mcdata::GenericBlockState>::from_structure(
+            &compress(&Value::Compound(nbt))?,
+            "Structure",
+        ),
+        Err(Error::RegionTooLarge {
+            format: "structure",
+            region
+        }) if region == "Structure"
+    ));
+    Ok(())
+}
+
+#[test]
+fn import_mostly_void() -> Result<()> {
+    // only the listed blocks are backed by the file, the rest of the size is structure void
+    let Value::Compound(mut nbt) = structure("palette", palette()) else {
+        unreachable!();
+    };
+    nbt.insert("size".to_owned(), int_list([1000, 1000, 1000]));
+    assert!(matches!(
+        Region::<mcdata::GenericBlockState>::from_structure(
+            &compress(&Value::Compound(nbt))?,
+            "Structure",
+        ),
+        Err(Error::RegionTooLarge {
+            format: "structure",
+            region
+        }) if region == "Structure"
+    ));
+    Ok(())
+}
+
+#[test]
+fn export() -> Result<()> {
+    // entity positions are relative to the region position, but exported relative to the
+    // minimum corner
+    let mut region: Region = Region::new("Export", BlockPos::new(5, 5, 5), BlockPos::new(-2, 1, 1));
+    region.set_block(BlockPos::new(0, 0, 0), block("minecraft:stone"));
+    region.set_block(BlockPos::new(1, 0, 0), block("minecraft:chest"));
+    region
+        .block_entities
+        .push(block_entity("minecraft:chest", BlockPos::new(1, 0, 0)));
+    region.entities.push(GenericEntity {
+        id: "minecraft:pig".into(),
+        uuid: 1,
+        properties: HashMap::from([("Pos".into(), double_list([0.5, 0.0, 0.5]))]),
+    });
+
+    let nbt = decompress(&region.to_structure(3953)?)?;
+    assert_eq!(get(&nbt, "DataVersion"), &Value::Int(3953));
+    assert_eq!(get(&nbt, "size"), &int_list([2, 1, 1]));
+    let Value::List(palette) = get(&nbt, "palette") else {
+        panic!("expected a palette list");
+    };
+    let Value::List(blocks) = get(&nbt, "blocks") else {
+        panic!("expected a blocks list");
+    };
+    // air is saved as well
+    assert_eq!(blocks.len(), 2);
+    for (pos, name) in [
+        ([0, 0, 0], "minecraft:stone"),
+        ([1, 0, 0], "minecraft:chest"),
+    ] {
+        let block = blocks
+            .iter()
+            .find(|block| get(block, "pos") == &int_list(pos))
+            .expect("missing block");
+        let Value::Int(state) = get(block, "state") else {
+            panic!("expected an int state");
+        };
+        assert_eq!(get(&palette[*state as usize], "Name"), &string(name));
+    }
+    let chest = blocks
+        .iter()
+        .find(|block| get(block, "pos") == &int_list([1, 0, 0]))
+        .expect("missing chest");
+    assert_eq!(
+        get(chest, "nbt"),
+        &compound([("id", string("minecraft:chest"))])
+    );
+
+    let Value::List(entities) = get(&nbt, "entities") else {
+        panic!("expected an entity list");
+    };
+    let [pig] = &entities[..] else {
+        panic!("expected a single entity");
+    };
+    assert_eq!(get(pig, "pos"), &double_list([1.5, 0.0, 0.5]));
+    assert_eq!(get(pig, "blockPos"), &int_list([1, 0, 0]));
+    Ok(())
+}
+
+#[test]
+fn round_trip() -> Result<()> {
+    let region: Region =
+        Region::from_structure(&compress(&structure("palette", palette()))?, "Structure")?;
+    let read: Region = Region::from_structure(&region.to_structure(3953)?, "Structure")?;
+    check_region(&read);
+    Ok(())
+}
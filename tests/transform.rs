@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use fastnbt::Value;
+use mcdata::{util::BlockPos, GenericBlockEntity, GenericBlockState, GenericEntity};
+use rustmatica::{Mirror, PendingBlockTick, Region, Rotation};
+
+fn block_with(
+    name: &'static str,
+    properties: &[(&'static str, &'static str)],
+) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: properties
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect(),
+    }
+}
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+fn block_entity(id: &'static str, pos: BlockPos) -> GenericBlockEntity {
+    GenericBlockEntity {
+        id: id.into(),
+        pos,
+        properties: HashMap::new(),
+    }
+}
+
+fn entity(pos: [f64; 3], yaw: f32) -> GenericEntity {
+    GenericEntity {
+        id: "minecraft:pig".into(),
+        uuid: 1,
+        properties: HashMap::from([
+            ("Pos".into(), Value::List(pos.map(Value::Double).to_vec())),
+            (
+                "Rotation".into(),
+                Value::List(vec![Value::Float(yaw), Value::Float(0.0)]),
+            ),
+        ]),
+    }
+}
+
+fn entity_pos(entity: &GenericEntity) -> [f64; 3] {
+    match &entity.properties["Pos"] {
+        Value::List(pos) => match pos[..] {
+            [Value::Double(x), Value::Double(y), Value::Double(z)] => [x, y, z],
+            _ => panic!("invalid entity position"),
+        },
+        _ => panic!("invalid entity position"),
+    }
+}
+
+fn entity_yaw(entity: &GenericEntity) -> f32 {
+    match &entity.properties["Rotation"] {
+        Value::List(rotation) => match rotation[0] {
+            Value::Float(yaw) => yaw,
+            _ => panic!("invalid entity rotation"),
+        },
+        _ => panic!("invalid entity rotation"),
+    }
+}
+
+/// A region with a chest, its block entity, a pending tick, and a pig in the center of the chest.
+fn region(position: BlockPos, size: BlockPos) -> Region {
+    let mut region = Region::new("Region", position, size);
+    let chest = BlockPos::new(2, 0, 1);
+    region.set_block(chest, block("minecraft:chest"));
+    region
+        .block_entities
+        .push(block_entity("minecraft:chest", chest));
+    region.pending_block_ticks.push(PendingBlockTick {
+        block: "minecraft:chest".into(),
+        priority: 0,
+        sub_tick: 0,
+        time: 1,
+        x: chest.x,
+        y: chest.y,
+        z: chest.z,
+    });
+    let global = region.pos_to_global(chest);
+    let pos = global - region.position;
+    region.entities.push(entity(
+        [pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 + 0.5],
+        0.0,
+    ));
+    region
+}
+
+/// Assert that the block entity, pending tick, and entity are still at the chest.
+fn assert_aligned(region: &Region) {
+    let (chest, _) = region
+        .blocks()
+        .find(|(_, block)| block.name == "minecraft:chest")
+        .unwrap();
+    assert_eq!(region.block_entities[0].pos, chest);
+    let tick = &region.pending_block_ticks[0];
+    assert_eq!(BlockPos::new(tick.x, tick.y, tick.z), chest);
+    let global = region.pos_to_global(chest);
+    let [x, y, z] = entity_pos(&region.entities[0]);
+    assert_eq!(
+        [
+            x + region.position.x as f64,
+            y + region.position.y as f64,
+            z + region.position.z as f64,
+        ],
+        [
+            global.x as f64 + 0.5,
+            global.y as f64,
+            global.z as f64 + 0.5,
+        ],
+    );
+}
+
+#[test]
+fn rotate_keeps_contents_aligned() {
+    for (position, size) in [
+        (BlockPos::new(0, 0, 0), BlockPos::new(3, 1, 2)),
+        (BlockPos::new(5, 2, -7), BlockPos::new(3, 1, 2)),
+        (BlockPos::new(5, 2, -7), BlockPos::new(-3, 1, -2)),
+    ] {
+        for rotation in [
+            Rotation::Clockwise90,
+            Rotation::Clockwise180,
+            Rotation::CounterClockwise90,
+        ] {
+            let mut rotated = region(position, size);
+            let chest = rotated.pos_to_global(BlockPos::new(2, 0, 1));
+            rotated.rotate(rotation);
+            assert_aligned(&rotated);
+            let (x, z) = match rotation {
+                Rotation::Clockwise90 => (-chest.z, chest.x),
+                Rotation::Clockwise180 => (-chest.x, -chest.z),
+                _ => (chest.z, -chest.x),
+            };
+            assert_eq!(
+                rotated.get_block_global(BlockPos::new(x, chest.y, z)),
+                &block("minecraft:chest"),
+            );
+        }
+    }
+}
+
+#[test]
+fn rotate_single_block() {
+    let mut region: Region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(1, 1, 1));
+    region.entities.push(entity([0.5, 0.0, 0.5], 0.0));
+    region.rotate(Rotation::Clockwise90);
+    assert_eq!(entity_pos(&region.entities[0]), [0.5, 0.0, 0.5]);
+    assert_eq!(entity_yaw(&region.entities[0]), 90.0);
+}
+
+#[test]
+fn mirror_keeps_contents_aligned() {
+    for (position, size) in [
+        (BlockPos::new(0, 0, 0), BlockPos::new(3, 1, 2)),
+        (BlockPos::new(5, 2, -7), BlockPos::new(-3, 1, -2)),
+    ] {
+        for mirror in [Mirror::LeftRight, Mirror::FrontBack] {
+            let mut mirrored = region(position, size);
+            let chest = mirrored.pos_to_global(BlockPos::new(2, 0, 1));
+            mirrored.mirror(mirror);
+            assert_aligned(&mirrored);
+            let (x, z) = match mirror {
+                Mirror::LeftRight => (chest.x, -chest.z),
+                _ => (-chest.x, chest.z),
+            };
+            assert_eq!(
+                mirrored.get_block_global(BlockPos::new(x, chest.y, z)),
+                &block("minecraft:chest"),
+            );
+        }
+    }
+}
+
+#[test]
+fn mirror_entities() {
+    let mut region: Region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(1, 1, 1));
+    // facing south and east
+    region.entities.push(entity([0.25, 0.0, 0.75], 0.0));
+    region.entities.push(entity([0.25, 0.0, 0.75], 270.0));
+
+    let mut left_right = region.clone();
+    left_right.mirror(Mirror::LeftRight);
+    assert_eq!(entity_pos(&left_right.entities[0]), [0.25, 0.0, 0.25]);
+    assert_eq!(entity_yaw(&left_right.entities[0]), 180.0);
+    assert_eq!(entity_yaw(&left_right.entities[1]), 270.0);
+
+    let mut front_back = region;
+    front_back.mirror(Mirror::FrontBack);
+    assert_eq!(entity_pos(&front_back.entities[0]), [0.75, 0.0, 0.75]);
+    assert_eq!(entity_yaw(&front_back.entities[0]), 0.0);
+    assert_eq!(entity_yaw(&front_back.entities[1]), 90.0);
+}
+
+#[test]
+fn mirror_block_states() {
+    for (mirror, before, after) in [
+        // stairs swap their corner shapes in addition to their facing
+        (
+            Mirror::LeftRight,
+            block_with(
+                "minecraft:oak_stairs",
+                &[
+                    ("facing", "north"),
+                    ("half", "top"),
+                    ("shape", "inner_left"),
+                ],
+            ),
+            block_with(
+                "minecraft:oak_stairs",
+                &[
+                    ("facing", "south"),
+                    ("half", "top"),
+                    ("shape", "inner_right"),
+                ],
+            ),
+        ),
+        (
+            Mirror::FrontBack,
+            block_with(
+                "minecraft:oak_stairs",
+                &[
+                    ("facing", "north"),
+                    ("half", "bottom"),
+                    ("shape", "outer_right"),
+                ],
+            ),
+            block_with(
+                "minecraft:oak_stairs",
+                &[
+                    ("facing", "north"),
+                    ("half", "bottom"),
+                    ("shape", "outer_left"),
+                ],
+            ),
+        ),
+        (
+            Mirror::FrontBack,
+            block_with(
+                "minecraft:oak_stairs",
+                &[
+                    ("facing", "east"),
+                    ("half", "bottom"),
+                    ("shape", "straight"),
+                ],
+            ),
+            block_with(
+                "minecraft:oak_stairs",
+                &[
+                    ("facing", "west"),
+                    ("half", "bottom"),
+                    ("shape", "straight"),
+                ],
+            ),
+        ),
+        // doors swap their hinge
+        (
+            Mirror::LeftRight,
+            block_with(
+                "minecraft:oak_door",
+                &[("facing", "north"), ("half", "lower"), ("hinge", "left")],
+            ),
+            block_with(
+                "minecraft:oak_door",
+                &[("facing", "south"), ("half", "lower"), ("hinge", "right")],
+            ),
+        ),
+        (
+            Mirror::FrontBack,
+            block_with(
+                "minecraft:oak_door",
+                &[("facing", "north"), ("half", "upper"), ("hinge", "right")],
+            ),
+            block_with(
+                "minecraft:oak_door",
+                &[("facing", "north"), ("half", "upper"), ("hinge", "left")],
+            ),
+        ),
+        // rails
+        (
+            Mirror::LeftRight,
+            block_with("minecraft:rail", &[("shape", "north_east")]),
+            block_with("minecraft:rail", &[("shape", "south_east")]),
+        ),
+        (
+            Mirror::FrontBack,
+            block_with("minecraft:rail", &[("shape", "north_east")]),
+            block_with("minecraft:rail", &[("shape", "north_west")]),
+        ),
+        (
+            Mirror::LeftRight,
+            block_with("minecraft:powered_rail", &[("shape", "ascending_north")]),
+            block_with("minecraft:powered_rail", &[("shape", "ascending_south")]),
+        ),
+        (
+            Mirror::LeftRight,
+            block_with("minecraft:powered_rail", &[("shape", "east_west")]),
+            block_with("minecraft:powered_rail", &[("shape", "east_west")]),
+        ),
+    ] {
+        let mut state = before.clone();
+        mirror.mirror_block_state(&mut state);
+        assert_eq!(state, after, "{mirror:?} {before:?}");
+
+        // the same through a region
+        let mut region: Region =
+            Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(1, 1, 1));
+        region.set_block(BlockPos::new(0, 0, 0), before);
+        region.mirror(mirror);
+        assert_eq!(region.get_block(BlockPos::new(0, 0, 0)), &after);
+    }
+}
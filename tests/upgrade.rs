@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use fastnbt::Value;
+use mcdata::{
+    data_version::MC_1_16_5, util::BlockPos, GenericBlockEntity, GenericBlockState, GenericEntity,
+};
+use rustmatica::{Litematic, Region, Unmigrated};
+
+fn block(name: &'static str, properties: &[(&'static str, &'static str)]) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: properties
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect(),
+    }
+}
+
+#[test]
+fn upgrade_1_16_to_1_21_9() {
+    let mut region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(4, 1, 1));
+    region.set_block(BlockPos::new(0, 0, 0), block("minecraft:grass", &[]));
+    region.set_block(
+        BlockPos::new(1, 0, 0),
+        block(
+            "minecraft:cobblestone_wall",
+            &[("north", "true"), ("up", "true")],
+        ),
+    );
+    region.set_block(BlockPos::new(2, 0, 0), block("minecraft:oak_sign", &[]));
+    region.set_block(
+        BlockPos::new(3, 0, 0),
+        block("minecraft:chain", &[("axis", "y")]),
+    );
+    region.block_entities.push(GenericBlockEntity {
+        id: "minecraft:sign".into(),
+        pos: BlockPos::new(2, 0, 0),
+        properties: HashMap::from([
+            ("Text1".into(), Value::String(r#"{"text":"Hello"}"#.into())),
+            ("Color".into(), Value::String("red".into())),
+        ]),
+    });
+    region.entities.push(GenericEntity {
+        id: "minecraft:chest_minecart".into(),
+        uuid: 42,
+        properties: HashMap::from([(
+            "Items".into(),
+            Value::List(vec![Value::Compound(HashMap::new())]),
+        )]),
+    });
+    let mut schem = region.as_litematic("", "");
+    schem.metadata.minecraft_data_version = MC_1_16_5;
+
+    let report = schem.upgrade(4554);
+    assert_eq!((report.from, report.to), (MC_1_16_5, 4554));
+    assert_eq!(schem.metadata.minecraft_data_version, 4554);
+    assert_eq!(
+        report.applied,
+        [
+            "rename grass paths and split filled cauldrons",
+            "make sign text double sided",
+            "rename grass to short grass",
+            "rename chains to iron chains",
+        ]
+    );
+
+    let region = &schem.regions[0];
+    assert_eq!(
+        region.get_block(BlockPos::new(0, 0, 0)),
+        &block("minecraft:short_grass", &[])
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(1, 0, 0)),
+        &block(
+            "minecraft:cobblestone_wall",
+            &[("north", "true"), ("up", "true")]
+        )
+    );
+    assert_eq!(
+        region.get_block(BlockPos::new(3, 0, 0)),
+        &block("minecraft:iron_chain", &[("axis", "y")])
+    );
+
+    let sign = &region.block_entities[0].properties;
+    let Some(Value::Compound(front_text)) = sign.get("front_text") else {
+        panic!("sign has no front text");
+    };
+    assert_eq!(front_text["color"], Value::String("red".into()));
+    let Value::List(messages) = &front_text["messages"] else {
+        panic!("front text has no messages");
+    };
+    assert_eq!(messages[0], Value::String(r#"{"text":"Hello"}"#.into()));
+    assert!(!sign.contains_key("Text1"));
+
+    assert_eq!(
+        report.unmigrated,
+        [
+            Unmigrated::Entity {
+                region: "Region".into(),
+                uuid: 42,
+                id: "minecraft:chest_minecart".into(),
+                change: "store item stacks with data components",
+            },
+            Unmigrated::BlockEntity {
+                region: "Region".into(),
+                pos: BlockPos::new(2, 0, 0),
+                id: "minecraft:sign".into(),
+                change: "store text components as NBT",
+            },
+        ]
+    );
+}
+
+#[test]
+fn upgrade_walls() {
+    let mut region = Region::new("Region", BlockPos::new(0, 0, 0), BlockPos::new(2, 1, 1));
+    region.set_block(
+        BlockPos::new(0, 0, 0),
+        block(
+            "minecraft:cobblestone_wall",
+            &[("north", "true"), ("south", "false")],
+        ),
+    );
+    region.set_block(
+        BlockPos::new(1, 0, 0),
+        block(
+            "minecraft:cobblestone_wall",
+            &[("north", "low"), ("south", "none")],
+        ),
+    );
+    let mut schem: Litematic = region.as_litematic("", "");
+    schem.metadata.minecraft_data_version = 2230;
+    let report = schem.upgrade(MC_1_16_5);
+    assert!(report.unmigrated.is_empty());
+    let region = &schem.regions[0];
+    // both states are merged into one palette entry
+    assert_eq!(region.block_palette().len(), 2);
+    assert_eq!(
+        region.get_block(BlockPos::new(0, 0, 0)),
+        &block(
+            "minecraft:cobblestone_wall",
+            &[("north", "low"), ("south", "none")]
+        )
+    );
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use mcdata::{util::BlockPos, GenericBlockEntity, GenericBlockState};
+use rustmatica::{Litematic, PendingFluidTick, Region, ValidationIssue};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn block(name: &'static str) -> GenericBlockState {
+    GenericBlockState {
+        name: name.into(),
+        properties: HashMap::new(),
+    }
+}
+
+fn block_entity(id: &'static str, pos: BlockPos) -> GenericBlockEntity {
+    GenericBlockEntity {
+        id: id.into(),
+        pos,
+        properties: HashMap::new(),
+    }
+}
+
+fn region(name: &'static str) -> Region {
+    let mut region = Region::new(name, BlockPos::new(0, 0, 0), BlockPos::new(3, 3, 3));
+    region.set_block(BlockPos::new(0, 0, 0), block("minecraft:chest"));
+    region.set_block(BlockPos::new(1, 0, 0), block("minecraft:oak_wall_sign"));
+    region.set_block(BlockPos::new(2, 0, 0), block("minecraft:stone"));
+    region.block_entities = vec![
+        block_entity("minecraft:chest", BlockPos::new(0, 0, 0)),
+        block_entity("minecraft:sign", BlockPos::new(1, 0, 0)),
+    ];
+    region
+}
+
+#[test]
+fn valid_schematic() {
+    let mut schem: Litematic = Litematic::new("Valid", "", "");
+    schem.regions.push(region("Region"));
+    assert_eq!(schem.validate(), []);
+}
+
+#[test]
+fn validate_and_repair() -> Result<()> {
+    let mut schem: Litematic = Litematic::new("Invalid", "", "");
+    let mut first = region("Region");
+    first
+        .block_entities
+        .push(block_entity("minecraft:chest", BlockPos::new(2, 0, 0)));
+    first
+        .block_entities
+        .push(block_entity("minecraft:furnace", BlockPos::new(0, 1, 0)));
+    first
+        .block_entities
+        .push(block_entity("minecraft:chest", BlockPos::new(3, 0, 0)));
+    first.pending_fluid_ticks.push(PendingFluidTick {
+        fluid: "minecraft:water".into(),
+        priority: 0,
+        sub_tick: 0,
+        time: 1,
+        x: 0,
+        y: -1,
+        z: 0,
+    });
+    schem.regions.push(first);
+    schem.regions.push(region("Region"));
+
+    // read the schematic back to get stored metadata totals, then make them stale
+    schem.make_region_names_unique();
+    let mut schem: Litematic = Litematic::from_uncompressed_bytes(&schem.to_uncompressed_bytes()?)?;
+    schem.regions[1].name = "Region".into();
+    schem.regions[1].set_block(BlockPos::new(2, 2, 2), block("minecraft:stone"));
+
+    let issues = schem.validate();
+    assert_eq!(issues.len(), 6, "{issues:#?}");
+    assert_eq!(
+        issues[0],
+        ValidationIssue::DuplicateRegionName {
+            name: "Region".into()
+        }
+    );
+    assert_eq!(
+        issues[1..5],
+        [
+            ValidationIssue::BlockEntityMismatch {
+                region: "Region".into(),
+                pos: BlockPos::new(2, 0, 0),
+                id: "minecraft:chest".into(),
+                block: "minecraft:stone".into(),
+            },
+            ValidationIssue::BlockEntityOnAir {
+                region: "Region".into(),
+                pos: BlockPos::new(0, 1, 0),
+            },
+            ValidationIssue::BlockEntityOutOfBounds {
+                region: "Region".into(),
+                pos: BlockPos::new(3, 0, 0),
+            },
+            ValidationIssue::PendingFluidTickOutOfBounds {
+                region: "Region".into(),
+                pos: BlockPos::new(0, -1, 0),
+            },
+        ]
+    );
+    assert!(matches!(issues[5], ValidationIssue::TotalsMismatch { .. }));
+
+    assert_eq!(schem.repair(), issues);
+    assert_eq!(schem.validate(), []);
+    assert_eq!(schem.regions[0].block_entities.len(), 2);
+    assert_eq!(schem.regions[0].pending_fluid_ticks, []);
+    assert_eq!(schem.regions[1].name, "Region (2)");
+    Ok(())
+}